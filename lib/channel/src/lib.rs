@@ -16,6 +16,7 @@ use std::{
         Arc,
     },
     task::{Context, Poll},
+    time::{Duration, Instant},
 };
 
 // Represents the soft limit that a channel has. Can either be dynamic or static.
@@ -54,7 +55,12 @@ struct Channel<T: Serialize> {
     on_demand_events: Event,
     receiver_events: Event,
     sender_events: Event,
-    queue: ConcurrentQueue<T>,
+    /// each item is stored with the time it expires (if the channel has a ttl)
+    queue: ConcurrentQueue<(Option<Instant>, T)>,
+    /// how long an item can stay in the channel before it is discarded
+    ttl: Option<Duration>,
+    /// the number of items that have been discarded because they outlived the ttl
+    expired_count: AtomicUsize,
     on_demand_count: AtomicUsize,
     receiver_count: AtomicUsize,
     sender_count: AtomicUsize,
@@ -64,7 +70,7 @@ struct Channel<T: Serialize> {
 
 impl<T: Serialize> Channel<T> {
     // TODO: Make unique an enum with false, true, and forever/always (doesn't remove from hashset)
-    fn new(limit: Limit, unique: bool, name: &str) -> Self {
+    fn new(limit: Limit, unique: bool, ttl: Option<Duration>, name: &str) -> Self {
         let unique = match unique {
             true => Some(HashSet::new()),
             false => None,
@@ -77,6 +83,8 @@ impl<T: Serialize> Channel<T> {
             receiver_events: Event::new(),
             sender_events: Event::new(),
             queue: ConcurrentQueue::unbounded(),
+            ttl,
+            expired_count: AtomicUsize::new(0),
             on_demand_count: AtomicUsize::new(1),
             receiver_count: AtomicUsize::new(1),
             sender_count: AtomicUsize::new(1),
@@ -85,8 +93,9 @@ impl<T: Serialize> Channel<T> {
     }
 
     /// push a value into the channel (for a unique channel, if the value already exists in the
-    /// channel it is discarded)
-    fn send(&self, item: T) {
+    /// channel it is discarded). The value expires at `expires` if given, otherwise after the
+    /// channel's ttl
+    fn send(&self, item: T, expires: Option<Instant>) {
         // if this is a unique channel check that the item is not in the set
         let should_send = self
             .unique
@@ -100,23 +109,24 @@ impl<T: Serialize> Channel<T> {
             serde_json::to_string(&item)
         );
         if should_send {
+            let expires = expires.or_else(|| self.ttl.map(|ttl| Instant::now() + ttl));
             self.queue
-                .push(item)
+                .push((expires, item))
                 .ok()
                 .expect("should never error because queue is unbounded");
             self.notify_receiver();
         }
     }
 
-    /// receive a value from the channel, if available
-    fn recv(&self) -> Option<T> {
-        let item = self.queue.pop().ok();
+    /// receive a value from the channel, if available, along with the time it expires
+    fn recv(&self) -> Option<(T, Option<Instant>)> {
+        let item = self.pop_unexpired();
         debug!(
             "Channel::recv: {}, item: {:?}",
             self.name,
-            serde_json::to_string(&item)
+            serde_json::to_string(&item.as_ref().map(|(item, _)| item))
         );
-        if let Some(item) = &item {
+        if let Some((item, _)) = &item {
             info!("Channel::recv: {}, item received", self.name);
             // if this is a unique channel, remove this item from the set
             // TODO: Make unique an enum with false, true, and forever/always (doesn't remove from hashset)
@@ -166,6 +176,23 @@ impl<T: Serialize> Channel<T> {
         item
     }
 
+    /// pop the next item from the queue, discarding any items which have outlived the ttl
+    fn pop_unexpired(&self) -> Option<(T, Option<Instant>)> {
+        loop {
+            let (expires, item) = self.queue.pop().ok()?;
+            match expires {
+                Some(expires) if expires <= Instant::now() => {
+                    info!("Channel::recv: {}, item expired", self.name);
+                    if let Some(set) = &self.unique {
+                        set.remove(&item);
+                    }
+                    self.expired_count.fetch_add(1, Ordering::Release);
+                }
+                _ => return Some((item, expires)),
+            }
+        }
+    }
+
     /// get how many items are currently stored in the channel (they've been sent in but not yet received)
     fn len(&self) -> usize {
         self.queue.len()
@@ -217,6 +244,11 @@ impl<T: Serialize> Channel<T> {
         self.receiver_events.listen()
     }
 
+    /// get the number of items discarded because they outlived the ttl
+    fn expired_count(&self) -> usize {
+        self.expired_count.load(Ordering::Acquire)
+    }
+
     /// get the number of on_demand receivers
     fn on_demand_count(&self) -> usize {
         self.on_demand_count.load(Ordering::Acquire)
@@ -306,6 +338,12 @@ impl<T: Serialize> Sender<T> {
     /// attempt to put data into the channel if 1) there are receivers and 2) the channel hasn't
     /// exceeded it's soft limit
     pub fn try_send(&self, item: T) -> SendState<T> {
+        self.try_send_with_expiry(item, None)
+    }
+
+    /// the same as `try_send` except the item expires at `expires` rather than after the channel's
+    /// ttl. Used to put a received item back without extending its life
+    pub fn try_send_with_expiry(&self, item: T, expires: Option<Instant>) -> SendState<T> {
         if self.no_receivers() {
             info!(
                 "No receivers on channel {}, length: {}",
@@ -315,7 +353,7 @@ impl<T: Serialize> Sender<T> {
             SendState::Closed(item)
         } else if self.channel.len() < self.channel.limit() {
             info!("sending to channel {}, length: {}", self.name(), self.len());
-            self.force_send(item);
+            self.force_send_with_expiry(item, expires);
             SendState::Success
         } else if self.no_receivers() {
             info!(
@@ -332,7 +370,13 @@ impl<T: Serialize> Sender<T> {
 
     //; puts data into the channel regardless of whether there are receivers or it's "over" capacity
     pub fn force_send(&self, item: T) {
-        self.channel.send(item)
+        self.channel.send(item, None)
+    }
+
+    /// the same as `force_send` except the item expires at `expires` rather than after the
+    /// channel's ttl
+    pub fn force_send_with_expiry(&self, item: T, expires: Option<Instant>) {
+        self.channel.send(item, expires)
     }
 }
 
@@ -504,6 +548,7 @@ impl<T: Serialize> ChannelStatsReader<T> {
             receiver_count: self.channel.receiver_count(),
             sender_count: self.channel.sender_count(),
             on_demand_count: self.channel.on_demand_count(),
+            expired_count: self.channel.expired_count(),
         }
     }
 }
@@ -520,6 +565,7 @@ pub struct ChannelStats<'a> {
     pub receiver_count: usize,
     pub sender_count: usize,
     pub on_demand_count: usize,
+    pub expired_count: usize,
 }
 
 pub struct Receiver<T: Serialize> {
@@ -563,11 +609,35 @@ impl<T: Serialize> Drop for Receiver<T> {
     }
 }
 
-/// the only means of getting data out of a receiver is through the `Stream` apis
-impl<T: Serialize> Stream for Receiver<T> {
-    type Item = T;
+impl<T: Serialize> Receiver<T> {
+    /// remove all items currently stored in the channel, along with the time each expires, without
+    /// waiting for more. Expired items are discarded. Used to save off the contents of a channel when
+    /// a test ends
+    pub fn drain(&self) -> Vec<(T, Option<Instant>)> {
+        let mut items = Vec::with_capacity(self.channel.len());
+        while let Some((item, expires)) = self.channel.pop_unexpired() {
+            if let Some(set) = &self.channel.unique {
+                set.remove(&item);
+            }
+            items.push((item, expires));
+        }
+        debug!(
+            "Receiver:drain channel {}, drained {} items",
+            self.channel.name,
+            items.len()
+        );
+        self.channel.notify_all_senders();
+        items
+    }
+}
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+impl<T: Serialize> Receiver<T> {
+    /// turn this into a `Stream` which gives each item along with the time it expires
+    pub fn with_expiry(self) -> WithExpiry<T> {
+        WithExpiry(self)
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context) -> Poll<Option<(T, Option<Instant>)>> {
         debug!(
             "Receiver:poll_next channel {}, length: {}",
             self.channel.name, self.channel.name
@@ -606,13 +676,45 @@ impl<T: Serialize> Stream for Receiver<T> {
     }
 }
 
+/// aside from `drain`, the only means of getting data out of a receiver is through the `Stream` apis
+impl<T: Serialize> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.poll_recv(cx).map(|item| item.map(|(item, _)| item))
+    }
+}
+
+/// a `Stream` of the items from a `Receiver` along with the time each expires (if the channel has
+/// a ttl), so an item can be sent back into the channel without extending its life
+pub struct WithExpiry<T: Serialize>(Receiver<T>);
+
+impl<T: Serialize> Stream for WithExpiry<T> {
+    type Item = (T, Option<Instant>);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.0.poll_recv(cx)
+    }
+}
+
 /// entry point for creating a channel
 pub fn channel<T: Serialize>(limit: Limit, unique: bool, name: &str) -> (Sender<T>, Receiver<T>) {
+    channel_with_ttl(limit, unique, None, name)
+}
+
+/// create a channel where items are discarded (rather than received) once they have been in the
+/// channel longer than `ttl`
+pub fn channel_with_ttl<T: Serialize>(
+    limit: Limit,
+    unique: bool,
+    ttl: Option<Duration>,
+    name: &str,
+) -> (Sender<T>, Receiver<T>) {
     info!(
-        "Creating channel {}, limit: {:?}, unique: {}",
-        name, limit, unique
+        "Creating channel {}, limit: {:?}, unique: {}, ttl: {:?}",
+        name, limit, unique, ttl
     );
-    let channel = Arc::new(Channel::new(limit, unique, name));
+    let channel = Arc::new(Channel::new(limit, unique, ttl, name));
     let receiver = Receiver {
         channel: channel.clone(),
         listener: None,
@@ -950,4 +1052,103 @@ mod tests {
         let right = None;
         assert_eq!(left, right, "on_demand stream should not be ready until the done_fn is called and receiver is polled2");
     }
+
+    #[test]
+    fn expired_items_are_discarded() {
+        let (tx, mut rx) = channel_with_ttl::<usize>(
+            Limit::dynamic(5),
+            true,
+            Some(Duration::from_millis(20)),
+            "expired_items_are_discarded",
+        );
+        let stats_reader = ChannelStatsReader::new("expired".into(), &rx);
+        let mut on_demand = OnDemandReceiver::new(&rx);
+
+        tx.force_send(1);
+        tx.force_send(2);
+        std::thread::sleep(Duration::from_millis(40));
+        tx.force_send(3);
+        assert_eq!(tx.len(), 3);
+
+        let left = rx.next().now_or_never();
+        let right = Some(Some(3));
+        assert_eq!(left, right, "receiver skips the expired values");
+        assert_eq!(stats_reader.get_stats(0).expired_count, 2);
+
+        // expired values are removed from the unique set
+        tx.force_send(1);
+        assert_eq!(tx.len(), 1);
+        let _ = rx.next().now_or_never();
+
+        let left = on_demand.next().now_or_never();
+        let right = None;
+        assert_eq!(left, right, "on_demand stream should not be ready");
+
+        let left = rx.next().now_or_never();
+        let right = None;
+        assert_eq!(left, right, "receiver should not be ready");
+        assert_eq!(stats_reader.get_stats(0).expired_count, 2);
+
+        let left = on_demand.next().now_or_never();
+        let right = Some(Some(()));
+        assert_eq!(left, right, "on_demand stream should be ready");
+    }
+
+    #[test]
+    fn returned_items_keep_their_expiry() {
+        let (tx, rx) = channel_with_ttl::<usize>(
+            Limit::dynamic(5),
+            false,
+            Some(Duration::from_millis(50)),
+            "returned_items_keep_their_expiry",
+        );
+        let stats_reader = ChannelStatsReader::new("returned".into(), &rx);
+        let mut rx = rx.with_expiry();
+
+        tx.force_send(1);
+        let (_, first_expires) = rx.next().now_or_never().unwrap().unwrap();
+        assert!(first_expires.is_some());
+        // return the value more often than the ttl, as an `auto_return` would
+        for _ in 0..4 {
+            std::thread::sleep(Duration::from_millis(20));
+            tx.force_send_with_expiry(1, first_expires);
+            match rx.next().now_or_never() {
+                Some(Some((_, expires))) => assert_eq!(expires, first_expires),
+                _ => break,
+            }
+        }
+
+        let left = rx.next().now_or_never();
+        let right = None;
+        assert_eq!(
+            left, right,
+            "the value is dropped once its original ttl has passed"
+        );
+        assert_eq!(stats_reader.get_stats(0).expired_count, 1);
+
+        assert!(tx.try_send_with_expiry(2, first_expires).is_success());
+        let left = rx.next().now_or_never();
+        let right = None;
+        assert_eq!(left, right, "an already expired value is never received");
+    }
+
+    #[test]
+    fn drain_works() {
+        let (tx, mut rx) = channel::<usize>(Limit::Static(2), true, "drain_works");
+
+        for n in 0..4 {
+            tx.force_send(n);
+        }
+
+        let left: Vec<_> = rx.drain().into_iter().map(|(n, _)| n).collect();
+        assert_eq!(left, vec![0, 1, 2, 3]);
+        assert_eq!(tx.len(), 0);
+
+        let left = rx.next().now_or_never();
+        let right = None;
+        assert_eq!(left, right, "receiver should not be ready");
+
+        tx.force_send(0);
+        assert_eq!(tx.len(), 1, "drained items are removed from the unique set");
+    }
 }
@@ -1,7 +1,12 @@
 use config::{BodyTemplate, LoadTest, Provider};
 use js_sys::Map;
 use log::{debug, LevelFilter};
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 use wasm_bindgen::{prelude::wasm_bindgen, throw_str, JsValue, UnwrapThrowExt};
 
 // Only valid because we are using this in a WebAssembly context without threads.
@@ -48,16 +53,31 @@ pub struct Config(LoadTest);
 #[allow(clippy::unused_unit)]
 #[wasm_bindgen]
 impl Config {
-    // build a config object from raw bytes (in javascript this is passing in a Uint8Array)
+    // build a config object from raw bytes (in javascript this is passing in a Uint8Array).
+    // `input_files` maps the path of a traffic file used by a `from_file` load_pattern to its
+    // contents, as there is no file system to read it from
     #[wasm_bindgen(constructor)]
     pub fn from_bytes(
         bytes: &[u8],
         env_vars: Map,
         log_level: Option<String>,
+        input_files: Option<Map>,
     ) -> Result<Config, JsValue> {
         init_logging(log_level);
         let env_vars = serde_wasm_bindgen::from_value(env_vars.into())?;
-        let load_test = LoadTest::from_config(bytes, &PathBuf::default(), &env_vars)
+        let input_files: BTreeMap<String, String> = match input_files {
+            Some(files) => serde_wasm_bindgen::from_value(files.into())?,
+            None => BTreeMap::new(),
+        };
+        let read_file = |path: &Path| {
+            input_files
+                .get(&*path.to_string_lossy())
+                .cloned()
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, "file contents were not passed in")
+                })
+        };
+        let load_test = LoadTest::from_config(bytes, &PathBuf::default(), &env_vars, &read_file)
             .map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
         Ok(Config(load_test))
     }
@@ -90,10 +110,12 @@ impl Config {
     pub fn get_input_files(&self) -> Box<[JsValue]> {
         // We also need to include file bodies so we can validate that we have those as well.
         // Endpoint file bodies - BodyTemplate(File)
+        let steps = self.0.scenarios.iter().flat_map(|s| s.steps.iter());
         let mut body_files: Vec<JsValue> = self
             .0
             .endpoints
             .iter()
+            .chain(steps)
             .filter_map(|endpoint| {
                 if let BodyTemplate::File(_, template) = &endpoint.body {
                     // The path is the base path, the template.pieces has the real path
@@ -104,20 +126,20 @@ impl Config {
                 }
             })
             .collect::<Vec<_>>();
-        // file providers
+        // file and sqlite providers
         let mut provider_files = self
             .0
             .providers
             .iter()
-            .filter_map(|(_, v)| {
-                if let Provider::File(f) = v {
-                    Some(f.path.as_str().into())
-                } else {
-                    None
-                }
+            .filter_map(|(_, v)| match v {
+                Provider::File(f) => Some(f.path.as_str().into()),
+                Provider::Sqlite(s) => Some(s.path.as_str().into()),
+                _ => None,
             })
             .collect::<Vec<_>>();
         provider_files.append(&mut body_files);
+        // traffic files for `from_file` load_patterns
+        provider_files.extend(self.0.traffic_files.iter().map(|f| f.as_str().into()));
         provider_files.into_boxed_slice()
     }
 
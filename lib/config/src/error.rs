@@ -7,14 +7,19 @@ type PestError = pest::error::Error<crate::select_parser::Rule>;
 
 #[derive(Clone, Debug)]
 pub enum ExecutingExpressionError {
+    DecodingFailed(String, Marker),
     IndexingIntoJson(String, json::Value, Marker),
+    InvalidDate(String, Marker),
     InvalidFunctionArguments(&'static str, Marker),
+    InvalidJwt(String, Marker),
+    MissingMapKey(String, String, Marker),
 }
 
 #[derive(Clone, Debug)]
 pub enum CreatingExpressionError {
     Executing(ExecutingExpressionError),
     InvalidExpression(PestError, Marker),
+    InvalidLambda(String, Marker),
     UnknownFunction(String, Marker),
     UnknownProvider(String, Marker),
 }
@@ -46,18 +51,28 @@ impl From<ExecutingExpressionError> for Error {
 #[derive(Clone, Debug)]
 pub enum Error {
     ExpressionErr(CreatingExpressionError),
+    InvalidClockTime(String, Marker),
     InvalidDuration(String, Marker),
+    InvalidGenerator(String, Marker),
+    InvalidGlob(String, Marker),
     InvalidLoadPattern(Marker),
+    InvalidMapProviderUse(String, Marker),
+    InvalidPartition(String, Marker),
     InvalidPeakLoad(String, Marker),
     InvalidPercent(String, Marker),
+    InvalidSequence(String, Marker),
+    InvalidTimezone(String, Marker),
+    InvalidTrafficFile(String, String, Marker),
     InvalidYaml(ScanError),
     MissingEnvironmentVariable(String, Marker),
     MissingForEach(Marker),
     MissingPeakLoad(Marker),
     MissingLoadPattern(Marker),
     MissingYamlField(&'static str, Marker),
+    NotAMapProvider(String, Marker),
     RecursiveForEachReference(Marker),
     UnknownLogger(String, Marker),
+    UnknownRateLimit(String, Marker),
     UnrecognizedKey(String, Option<String>, Marker),
     YamlDeserialize(Option<String>, Marker),
 }
@@ -66,18 +81,28 @@ pub enum Error {
 // fn marker(&self) -> Marker {
 //     match &self {
 //         ExpressionErr(e) => e.marker(),
+//         InvalidClockTime(_, marker) => *marker,
 //         InvalidDuration(_, marker) => *marker,
+//         InvalidGenerator(_, marker) => *marker,
+//         InvalidGlob(_, marker) => *marker,
 //         InvalidLoadPattern(marker) => *marker,
+//         InvalidMapProviderUse(_, marker) => *marker,
+//         InvalidPartition(_, marker) => *marker,
 //         InvalidPeakLoad(_, marker) => *marker,
 //         InvalidPercent(_, marker) => *marker,
+//         InvalidSequence(_, marker) => *marker,
+//         InvalidTimezone(_, marker) => *marker,
+//         InvalidTrafficFile(_, _, marker) => *marker,
 //         InvalidYaml(e) => *e.marker(),
 //         MissingEnvironmentVariable(_, marker) => *marker,
 //         MissingForEach(marker) => *marker,
 //         MissingPeakLoad(marker) => *marker,
 //         MissingLoadPattern(marker) => *marker,
 //         MissingYamlField(_, marker) => *marker,
+//         NotAMapProvider(_, marker) => *marker,
 //         RecursiveForEachReference(marker) => *marker,
 //         UnknownLogger(_, marker) => *marker,
+//         UnknownRateLimit(_, marker) => *marker,
 //         UnrecognizedKey(_, _, marker) => *marker,
 //         YamlDeserialize(_, marker) => *marker,
 //     }
@@ -96,6 +121,13 @@ impl fmt::Display for CreatingExpressionError {
                 m.col()
             ),
             Executing(e) => e.fmt(f),
+            InvalidLambda(e, m) => write!(
+                f,
+                "invalid lambda. {} at line {} column {}",
+                e,
+                m.line(),
+                m.col()
+            ),
             UnknownFunction(func, m) => write!(
                 f,
                 "unknown function `{}` at line {} column {}",
@@ -125,6 +157,20 @@ impl fmt::Display for ExecutingExpressionError {
                 m.line(),
                 m.col()
             ),
+            DecodingFailed(e, m) => write!(
+                f,
+                "could not decode value. {} at line {} column {}",
+                e,
+                m.line(),
+                m.col()
+            ),
+            InvalidDate(d, m) => write!(
+                f,
+                "invalid date `{}` at line {} column {}",
+                d,
+                m.line(),
+                m.col()
+            ),
             InvalidFunctionArguments(func, m) => write!(
                 f,
                 "invalid arguments for function `{}` at line {} column {}",
@@ -132,6 +178,21 @@ impl fmt::Display for ExecutingExpressionError {
                 m.line(),
                 m.col()
             ),
+            InvalidJwt(e, m) => write!(
+                f,
+                "invalid jwt. {} at line {} column {}",
+                e,
+                m.line(),
+                m.col()
+            ),
+            MissingMapKey(p, k, m) => write!(
+                f,
+                "key `{}` not found in map provider `{}` at line {} column {}",
+                k,
+                p,
+                m.line(),
+                m.col()
+            ),
         }
     }
 }
@@ -141,10 +202,18 @@ impl fmt::Display for Error {
         use Error::*;
         match self {
             ExpressionErr(e) => e.fmt(f),
+            InvalidClockTime(t, m) => write!(f, "invalid clock time `{}` at line {} column {}", t, m.line(), m.col()),
             InvalidDuration(d, m) => write!(f, "invalid duration `{}` at line {} column {}", d, m.line(), m.col()),
+            InvalidGenerator(g, m) => write!(f, "invalid generator `{}` at line {} column {}", g, m.line(), m.col()),
+            InvalidGlob(g, m) => write!(f, "invalid glob {} at line {} column {}", g, m.line(), m.col()),
             InvalidLoadPattern(m) => write!(f, "invalid load_pattern at line {} column {}", m.line(), m.col()),
+            InvalidMapProviderUse(p, m) => write!(f, "map provider `{}` can only be used with `lookup` in a `declare` at line {} column {}", p, m.line(), m.col()),
+            InvalidPartition(p, m) => write!(f, "invalid partition. {} at line {} column {}", p, m.line(), m.col()),
             InvalidPeakLoad(p, m) => write!(f, "invalid peak_load `{}` at line {} column {}", p, m.line(), m.col()),
             InvalidPercent(p, m) => write!(f, "invalid percent `{}` at line {} column {}", p, m.line(), m.col()),
+            InvalidSequence(e, m) => write!(f, "invalid sequence. {} at line {} column {}", e, m.line(), m.col()),
+            InvalidTimezone(t, m) => write!(f, "invalid timezone `{}` at line {} column {}", t, m.line(), m.col()),
+            InvalidTrafficFile(p, e, m) => write!(f, "invalid traffic file `{}` at line {} column {}: {}", p, m.line(), m.col(), e),
             InvalidYaml(e) => write!(f, "yaml syntax error:\n\t{}", e),
             MissingEnvironmentVariable(v, m) => write!(f, "undefined environment variable `{}` at line {} column {}", v, m.line(), m.col()),
             MissingForEach(m) => write!(f, "missing `for_each` at line {} column {}", m.line(), m.col()),
@@ -154,8 +223,10 @@ impl fmt::Display for Error {
                 "endpoint must either have a `peak_load`, a provides which is `send: block`, or depend on a `response` provider. See line {} column {}", m.line(), m.col()
             ),
             MissingYamlField(field, m) => write!(f, "missing field `{}` at line {} column {}", field, m.line(), m.col()),
+            NotAMapProvider(p, m) => write!(f, "`lookup` requires a map provider but `{}` is not one at line {} column {}", p, m.line(), m.col()),
             RecursiveForEachReference(m) => write!(f, "recursive `for_each` reference at line {} column {}", m.line(), m.col()),
             UnknownLogger(l, m) => write!(f, "unknown logger `{}` at line {} column {}", l, m.line(), m.col()),
+            UnknownRateLimit(r, m) => write!(f, "unknown rate limit `{}` at line {} column {}", r, m.line(), m.col()),
             UnrecognizedKey(k, Some(name), m) => write!(f, "unrecognized key `{}` in `{}` at line {} column {}", k, name, m.line(), m.col()),
             UnrecognizedKey(k, None, m) => write!(f, "unrecognized key `{}` at line {} column {}", k, m.line(), m.col()),
             YamlDeserialize(Some(name), m) => write!(f, "unexpected value for `{}` at line {} column {}", name, m.line(), m.col()),
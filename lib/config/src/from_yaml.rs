@@ -147,6 +147,16 @@ impl FromYaml for i64 {
     }
 }
 
+impl FromYaml for u64 {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let (event, marker) = decoder.next()?;
+        event
+            .as_x()
+            .map(|i| (i, marker))
+            .ok_or(Error::YamlDeserialize(None, marker))
+    }
+}
+
 impl FromYaml for usize {
     fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
         let (event, marker) = decoder.next()?;
@@ -442,7 +452,9 @@ where
                 }
                 YamlEvent::MappingStart | YamlEvent::SequenceStart => {
                     if first_round {
+                        // the collection could be empty, so peek again for its first item
                         decoder.next()?;
+                        continue;
                     }
                     let v = FromYaml::parse_into(decoder)?;
                     let event = first_event.as_ref().expect("should have first event");
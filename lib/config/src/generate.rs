@@ -0,0 +1,538 @@
+use crate::error::Error;
+use crate::from_yaml::{FromYaml, ParseResult, TupleVec, YamlDecoder, YamlEvent};
+use crate::map_yaml_deserialize_err;
+
+use chrono::{
+    format::strftime::StrftimeItems, format::Item, DateTime, NaiveDate, NaiveDateTime, TimeZone,
+    Utc,
+};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde_json as json;
+
+use std::{collections::BTreeMap, num::NonZeroUsize};
+
+// the most times an unbounded repetition (`*`, `+`, `{n,}`) is repeated by a `regex` generator
+const MAX_REGEX_REPEAT: u32 = 10;
+const DEFAULT_LOREM_WORDS: usize = 8;
+
+const FIRST_NAMES: &[&str] = &[
+    "Aaliyah", "Aiden", "Amara", "Andre", "Ava", "Benjamin", "Camila", "Carlos", "Chloe", "Daniel",
+    "Diego", "Elena", "Elijah", "Emma", "Ethan", "Fatima", "Gabriel", "Grace", "Hannah", "Hiro",
+    "Isaac", "Isabella", "Jamal", "Jasmine", "Kai", "Layla", "Liam", "Lucas", "Maya", "Mei",
+    "Mohammed", "Noah", "Olivia", "Omar", "Priya", "Rafael", "Sofia", "Tariq", "Yuki", "Zoe",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "Adams",
+    "Ahmed",
+    "Alvarez",
+    "Brown",
+    "Chen",
+    "Clark",
+    "Davis",
+    "Diaz",
+    "Evans",
+    "Garcia",
+    "Gonzalez",
+    "Hall",
+    "Hernandez",
+    "Ito",
+    "Jackson",
+    "Johnson",
+    "Kim",
+    "Kowalski",
+    "Lee",
+    "Lopez",
+    "Martin",
+    "Martinez",
+    "Miller",
+    "Moore",
+    "Nguyen",
+    "Okafor",
+    "Patel",
+    "Perez",
+    "Rossi",
+    "Sanchez",
+    "Singh",
+    "Smith",
+    "Tanaka",
+    "Taylor",
+    "Thomas",
+    "Walker",
+    "White",
+    "Williams",
+    "Wilson",
+    "Young",
+];
+
+const LOREM_WORDS: &[&str] = &[
+    "lorem",
+    "ipsum",
+    "dolor",
+    "sit",
+    "amet",
+    "consectetur",
+    "adipiscing",
+    "elit",
+    "sed",
+    "do",
+    "eiusmod",
+    "tempor",
+    "incididunt",
+    "ut",
+    "labore",
+    "et",
+    "dolore",
+    "magna",
+    "aliqua",
+    "enim",
+    "ad",
+    "minim",
+    "veniam",
+    "quis",
+    "nostrud",
+    "exercitation",
+    "ullamco",
+    "laboris",
+    "nisi",
+    "aliquip",
+    "ex",
+    "ea",
+    "commodo",
+    "consequat",
+    "duis",
+    "aute",
+    "irure",
+    "in",
+    "reprehenderit",
+    "voluptate",
+    "velit",
+    "esse",
+    "cillum",
+    "fugiat",
+    "nulla",
+    "pariatur",
+    "excepteur",
+    "sint",
+    "occaecat",
+    "cupidatat",
+    "non",
+    "proident",
+    "sunt",
+    "culpa",
+    "qui",
+    "officia",
+    "deserunt",
+    "mollit",
+    "anim",
+    "id",
+    "est",
+    "laborum",
+];
+
+// reserved domains (RFC 2606) so generated emails never reach a real inbox
+const EMAIL_DOMAINS: &[&str] = &["example.com", "example.net", "example.org"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Generator {
+    // seconds since the unix epoch (UTC) and the chrono format string used for the output
+    Date {
+        start: i64,
+        end: i64,
+        format: String,
+    },
+    Email,
+    FirstName,
+    LastName,
+    Lorem(usize),
+    Name,
+    Regex(String),
+    Uuid,
+}
+
+fn compile_regex(pattern: &str) -> Result<rand_regex::Regex, rand_regex::Error> {
+    rand_regex::Regex::compile(pattern, MAX_REGEX_REPEAT)
+}
+
+// parses a `date` generator bound, returning the seconds since the unix epoch and whether it
+// included a time
+fn parse_date_bound(s: &str) -> Option<(i64, bool)> {
+    if let Ok(d) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Some((d.and_hms(0, 0, 0).timestamp(), false));
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Some((dt.timestamp(), true));
+    }
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S")
+        .ok()
+        .map(|dt| (dt.timestamp(), true))
+}
+
+fn date_generator(
+    mut options: BTreeMap<String, String>,
+    marker: yaml_rust::scanner::Marker,
+) -> Result<Generator, Error> {
+    let mut bound = |name: &'static str| {
+        let s = options
+            .remove(name)
+            .ok_or(Error::MissingYamlField(name, marker))?;
+        parse_date_bound(&s).ok_or_else(|| Error::InvalidGenerator(s, marker))
+    };
+    let (start, start_has_time) = bound("start")?;
+    let (end, end_has_time) = bound("end")?;
+    if end < start {
+        return Err(Error::InvalidGenerator(
+            "date `end` is before `start`".into(),
+            marker,
+        ));
+    }
+    let format = match options.remove("format") {
+        Some(f) => {
+            if StrftimeItems::new(&f).any(|i| matches!(i, Item::Error)) {
+                return Err(Error::InvalidGenerator(f, marker));
+            }
+            f
+        }
+        None if start_has_time || end_has_time => "%Y-%m-%dT%H:%M:%SZ".into(),
+        None => "%Y-%m-%d".into(),
+    };
+    if let Some(key) = options.into_keys().next() {
+        return Err(Error::UnrecognizedKey(key, Some("date".into()), marker));
+    }
+    Ok(Generator::Date { start, end, format })
+}
+
+impl FromYaml for Generator {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let (event, marker) = decoder.next()?;
+        match event {
+            YamlEvent::Scalar(s, ..) => {
+                let generator = match s.as_str() {
+                    "email" => Generator::Email,
+                    "first_name" => Generator::FirstName,
+                    "last_name" => Generator::LastName,
+                    "lorem" => Generator::Lorem(DEFAULT_LOREM_WORDS),
+                    "name" => Generator::Name,
+                    "uuid" => Generator::Uuid,
+                    _ => return Err(Error::InvalidGenerator(s, marker)),
+                };
+                Ok((generator, marker))
+            }
+            YamlEvent::MappingStart => {
+                let (key, key_marker) = String::parse(decoder)?;
+                let generator = match key.as_str() {
+                    "date" => {
+                        let (options, options_marker) = FromYaml::parse(decoder)
+                            .map_err(map_yaml_deserialize_err(key.clone()))?;
+                        date_generator(options, options_marker)?
+                    }
+                    "lorem" => {
+                        let words: NonZeroUsize = FromYaml::parse_into(decoder)
+                            .map_err(map_yaml_deserialize_err(key.clone()))?;
+                        Generator::Lorem(words.get())
+                    }
+                    "regex" => {
+                        let (pattern, pattern_marker): (String, _) = FromYaml::parse(decoder)
+                            .map_err(map_yaml_deserialize_err(key.clone()))?;
+                        if let Err(e) = compile_regex(&pattern) {
+                            let e = format!("{} ({})", pattern, e);
+                            return Err(Error::InvalidGenerator(e, pattern_marker));
+                        }
+                        Generator::Regex(pattern)
+                    }
+                    _ => return Err(Error::InvalidGenerator(key, key_marker)),
+                };
+                let (event, end_marker) = decoder.next()?;
+                match event {
+                    YamlEvent::MappingEnd => Ok((generator, marker)),
+                    _ => Err(Error::YamlDeserialize(None, end_marker)),
+                }
+            }
+            _ => Err(Error::YamlDeserialize(None, marker)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GenerateProvider {
+    pub seed: Option<u64>,
+    pub template: Vec<(String, Generator)>,
+    pub unique: bool,
+}
+
+impl FromYaml for GenerateProvider {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let mut seed = None;
+        let mut template: Option<TupleVec<String, Generator>> = None;
+        let mut unique = false;
+
+        let mut first_marker = None;
+        let mut saw_opening = false;
+        loop {
+            let (event, marker) = decoder.next()?;
+            if first_marker.is_none() {
+                first_marker = Some(marker);
+            }
+            match event {
+                YamlEvent::MappingStart => {
+                    if saw_opening {
+                        return Err(Error::YamlDeserialize(None, marker));
+                    } else {
+                        saw_opening = true;
+                    }
+                }
+                YamlEvent::SequenceStart => {
+                    return Err(Error::YamlDeserialize(None, marker));
+                }
+                YamlEvent::MappingEnd => {
+                    break;
+                }
+                YamlEvent::SequenceEnd => {
+                    unreachable!("shouldn't see sequence end");
+                }
+                YamlEvent::Scalar(s, ..) => match s.as_str() {
+                    "seed" => {
+                        let (v, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        seed = Some(v);
+                    }
+                    "template" => {
+                        let (t, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        template = Some(t);
+                    }
+                    "unique" => {
+                        let (u, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        unique = u;
+                    }
+                    _ => return Err(Error::UnrecognizedKey(s, None, marker)),
+                },
+            }
+        }
+        let marker = first_marker.expect("should have a marker");
+        let template = template
+            .ok_or(Error::MissingYamlField("template", marker))?
+            .0;
+        let ret = Self {
+            seed,
+            template,
+            unique,
+        };
+        Ok((ret, marker))
+    }
+}
+
+impl IntoIterator for GenerateProvider {
+    type Item = json::Value;
+    type IntoIter = GenerateIterator;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let template = self
+            .template
+            .into_iter()
+            .map(|(name, generator)| {
+                let regex = match &generator {
+                    Generator::Regex(pattern) => {
+                        Some(compile_regex(pattern).expect("regex was validated when parsed"))
+                    }
+                    _ => None,
+                };
+                (name, generator, regex)
+            })
+            .collect();
+        GenerateIterator { rng, template }
+    }
+}
+
+// an endless iterator which creates a json object from the template for each value
+pub struct GenerateIterator {
+    rng: StdRng,
+    template: Vec<(String, Generator, Option<rand_regex::Regex>)>,
+}
+
+impl GenerateIterator {
+    fn pick(&mut self, words: &[&'static str]) -> &'static str {
+        words
+            .choose(&mut self.rng)
+            .expect("word lists are not empty")
+    }
+
+    fn generate(
+        &mut self,
+        generator: &Generator,
+        regex: Option<&rand_regex::Regex>,
+    ) -> json::Value {
+        match generator {
+            Generator::Date { start, end, format } => {
+                let secs = self.rng.gen_range(*start..=*end);
+                // formatted as a UTC date time so the offset specifiers (`%z`, `%Z`, `%+`) have a
+                // timezone to print
+                Utc.timestamp(secs, 0).format(format).to_string().into()
+            }
+            Generator::Email => {
+                let first = self.pick(FIRST_NAMES);
+                let last = self.pick(LAST_NAMES);
+                let n: u16 = self.rng.gen_range(0..1000);
+                let domain = self.pick(EMAIL_DOMAINS);
+                format!("{}.{}{}@{}", first, last, n, domain)
+                    .to_lowercase()
+                    .into()
+            }
+            Generator::FirstName => self.pick(FIRST_NAMES).into(),
+            Generator::LastName => self.pick(LAST_NAMES).into(),
+            Generator::Lorem(words) => {
+                let words: Vec<_> = (0..*words).map(|_| self.pick(LOREM_WORDS)).collect();
+                words.join(" ").into()
+            }
+            Generator::Name => {
+                format!("{} {}", self.pick(FIRST_NAMES), self.pick(LAST_NAMES)).into()
+            }
+            Generator::Regex(_) => {
+                let regex = regex.expect("regex generators are compiled");
+                self.rng.sample::<String, _>(regex).into()
+            }
+            Generator::Uuid => {
+                let bytes = self.rng.gen();
+                uuid::Builder::from_random_bytes(bytes)
+                    .into_uuid()
+                    .to_string()
+                    .into()
+            }
+        }
+    }
+}
+
+impl Iterator for GenerateIterator {
+    type Item = json::Value;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let template = std::mem::take(&mut self.template);
+        let object = template
+            .iter()
+            .map(|(name, generator, regex)| {
+                (name.clone(), self.generate(generator, regex.as_ref()))
+            })
+            .collect();
+        self.template = template;
+        Some(json::Value::Object(object))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use regex::Regex;
+
+    #[test]
+    fn parses_generators() {
+        let values = vec![
+            ("uuid", Ok(Generator::Uuid)),
+            ("name", Ok(Generator::Name)),
+            ("lorem", Ok(Generator::Lorem(DEFAULT_LOREM_WORDS))),
+            ("lorem: 3", Ok(Generator::Lorem(3))),
+            ("regex: '[a-z]{3}'", Ok(Generator::Regex("[a-z]{3}".into()))),
+            (
+                "date: { start: 2020-01-01, end: 2020-01-02 }",
+                Ok(Generator::Date {
+                    start: 1_577_836_800,
+                    end: 1_577_923_200,
+                    format: "%Y-%m-%d".into(),
+                }),
+            ),
+            (
+                "date: { start: 2020-01-01, end: '2020-01-01T01:00:00Z', format: '%s' }",
+                Ok(Generator::Date {
+                    start: 1_577_836_800,
+                    end: 1_577_840_400,
+                    format: "%s".into(),
+                }),
+            ),
+            ("phone_number", Err(())),
+            ("regex: '[a-z'", Err(())),
+            ("date: { start: 2020-01-02, end: 2020-01-01 }", Err(())),
+            ("date: { start: 2020-01-01 }", Err(())),
+            (
+                "date: { start: 2020-01-01, end: 2020-01-02, foo: bar }",
+                Err(()),
+            ),
+        ];
+
+        for (i, (yaml, expect)) in values.into_iter().enumerate() {
+            let actual = Generator::from_yaml_str(yaml).map_err(drop);
+            assert_eq!(actual, expect, "index {}", i);
+        }
+    }
+
+    #[test]
+    fn generates_values_from_template() {
+        let yaml = "
+            seed: 7
+            template:
+                id: uuid
+                name: name
+                email: email
+                bio:
+                    lorem: 4
+                joined:
+                    date:
+                        start: 2020-01-01
+                        end: 2020-12-31
+                sku:
+                    regex: '[A-Z]{3}-[0-9]{4}'
+        ";
+        let provider = GenerateProvider::from_yaml_str(yaml).unwrap();
+        let first: Vec<_> = provider.clone().into_iter().take(20).collect();
+        let second: Vec<_> = provider.into_iter().take(20).collect();
+        assert_eq!(first, second, "same seed gives the same values");
+
+        let uuid =
+            Regex::new("^[0-9a-f]{8}-[0-9a-f]{4}-4[0-9a-f]{3}-[89ab][0-9a-f]{3}-[0-9a-f]{12}$")
+                .unwrap();
+        let email = Regex::new(r"^[a-z]+\.[a-z]+\d{1,3}@example\.(com|net|org)$").unwrap();
+        let sku = Regex::new("^[A-Z]{3}-[0-9]{4}$").unwrap();
+        for value in first {
+            let keys: Vec<_> = value.as_object().unwrap().keys().cloned().collect();
+            assert_eq!(keys, vec!["id", "name", "email", "bio", "joined", "sku"]);
+            assert!(uuid.is_match(value["id"].as_str().unwrap()), "{}", value);
+            assert!(
+                email.is_match(value["email"].as_str().unwrap()),
+                "{}",
+                value
+            );
+            assert!(sku.is_match(value["sku"].as_str().unwrap()), "{}", value);
+            assert_eq!(value["bio"].as_str().unwrap().split(' ').count(), 4);
+            assert_eq!(value["name"].as_str().unwrap().split(' ').count(), 2);
+            let joined = value["joined"].as_str().unwrap();
+            assert!(
+                ("2020-01-01"..="2020-12-31").contains(&joined),
+                "{}",
+                joined
+            );
+        }
+    }
+
+    #[test]
+    fn generates_dates_with_offset_formats() {
+        let yaml = "
+            seed: 3
+            template:
+                at:
+                    date:
+                        start: 2021-06-01T12:00:00
+                        end: 2021-06-01T12:00:00
+                        format: '%Y-%m-%dT%H:%M:%S%:z %z %Z|%+'
+        ";
+        let provider = GenerateProvider::from_yaml_str(yaml).unwrap();
+        let value = provider.into_iter().next().unwrap();
+        assert_eq!(
+            value["at"],
+            "2021-06-01T12:00:00+00:00 +0000 UTC|2021-06-01T12:00:00+00:00"
+        );
+    }
+}
@@ -3,23 +3,27 @@
 mod error;
 mod expression_functions;
 mod from_yaml;
+mod generate;
 mod select_parser;
 
+use chrono::{DateTime, NaiveDateTime};
 pub use error::{CreatingExpressionError, Error, ExecutingExpressionError};
 use ether::{Either, Either3};
 pub use from_yaml::FromYaml;
 use from_yaml::{Nullable, ParseResult, TupleVec, YamlDecoder, YamlEvent};
+pub use generate::{GenerateIterator, GenerateProvider, Generator};
 use http::Method;
 use rand::{
-    distributions::{Distribution, Uniform},
+    distributions::{Distribution, Uniform, WeightedIndex},
+    seq::SliceRandom,
     Rng,
 };
 use regex::Regex;
 use select_parser::ValueOrExpression;
 pub use select_parser::{
-    ProviderStream, RequiredProviders, Select, Template, REQUEST_BODY, REQUEST_HEADERS,
-    REQUEST_HEADERS_ALL, REQUEST_STARTLINE, REQUEST_URL, RESPONSE_BODY, RESPONSE_HEADERS,
-    RESPONSE_HEADERS_ALL, RESPONSE_STARTLINE, STATS,
+    ProviderLookup, ProviderStream, RequiredProviders, Select, Template, REQUEST_BODY,
+    REQUEST_HEADERS, REQUEST_HEADERS_ALL, REQUEST_STARTLINE, REQUEST_URL, RESPONSE_BODY,
+    RESPONSE_HEADERS, RESPONSE_HEADERS_ALL, RESPONSE_STARTLINE, STATS,
 };
 use serde::Serialize;
 use serde_json as json;
@@ -28,8 +32,9 @@ use yaml_rust::scanner::{Marker, Scanner};
 use log::{debug, error, LevelFilter};
 use std::{
     borrow::Cow,
+    cell::RefCell,
     collections::{BTreeMap, BTreeSet},
-    fmt, iter,
+    fmt, io, iter,
     num::{NonZeroU16, NonZeroUsize},
     path::{Path, PathBuf},
     str::FromStr,
@@ -123,31 +128,89 @@ pub enum HitsPer {
     Minute(f32),
 }
 
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Arrival {
+    #[default]
+    Constant,
+    Poisson,
+    Uniform,
+}
+
+impl FromYaml for Arrival {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let (event, marker) = decoder.next()?;
+        let arrival = match event.as_str() {
+            Some("constant") => Arrival::Constant,
+            Some("poisson") => Arrival::Poisson,
+            Some("uniform") => Arrival::Uniform,
+            _ => return Err(Error::YamlDeserialize(None, marker)),
+        };
+        Ok((arrival, marker))
+    }
+}
+
 #[derive(Clone)]
 pub struct LinearBuilder {
     pub pieces: Vec<LinearBuilderPiece>,
     pub duration: Duration,
+    pub schedule: Option<Schedule>,
 }
 
 impl LinearBuilder {
-    pub fn new(start_percent: f64, end_percent: f64, duration: Duration) -> Self {
+    pub fn new(
+        start_percent: f64,
+        end_percent: f64,
+        duration: Duration,
+        shape: SegmentShape,
+    ) -> Self {
         let mut ret = LinearBuilder {
             pieces: Vec::new(),
             duration: Duration::from_secs(0),
+            schedule: None,
         };
-        ret.append(start_percent, end_percent, duration);
+        ret.append(start_percent, end_percent, duration, shape);
         ret
     }
 
-    pub fn append(&mut self, start_percent: f64, end_percent: f64, duration: Duration) {
+    pub fn append(
+        &mut self,
+        start_percent: f64,
+        end_percent: f64,
+        duration: Duration,
+        shape: SegmentShape,
+    ) {
         self.duration += duration;
-        let lb = LinearBuilderPiece::new(start_percent, end_percent, duration);
+        let lb = LinearBuilderPiece::new(start_percent, end_percent, duration, shape);
         self.pieces.push(lb);
     }
 
     pub fn duration(&self) -> Duration {
         self.duration
     }
+
+    // sets the length of each piece which lasts `until` a time of day, for the pattern starting at
+    // `start` (milliseconds since the epoch). A piece which spans a daylight saving change is
+    // longer or shorter than the clock times suggest. `offset_at` gives the timezone's offset in
+    // milliseconds at a time in milliseconds since the epoch
+    fn fit_to_clock(&mut self, start: i64, offset_at: &dyn Fn(i64) -> i64) {
+        let day = DAY.as_millis() as i64;
+        let mut t = start;
+        let mut duration = Duration::from_secs(0);
+        for piece in &mut self.pieces {
+            if let Some(until) = piece.until {
+                let clock = (t + offset_at(t)).rem_euclid(day);
+                let length = (until.as_millis() as i64 - clock).rem_euclid(day);
+                if length > 0 {
+                    let end = t + length;
+                    let end = end + offset_at(t) - offset_at(end);
+                    piece.duration = Duration::from_millis((end - t).max(0) as u64);
+                }
+            }
+            t += piece.duration.as_millis() as i64;
+            duration += piece.duration;
+        }
+        self.duration = duration;
+    }
 }
 
 #[derive(Clone)]
@@ -155,14 +218,83 @@ pub struct LinearBuilderPiece {
     pub start_percent: f64,
     pub end_percent: f64,
     pub duration: Duration,
+    pub shape: SegmentShape,
+    // the time of day the piece ends at, when its length comes from an `until`
+    pub until: Option<Duration>,
 }
 
 impl LinearBuilderPiece {
-    fn new(start_percent: f64, end_percent: f64, duration: Duration) -> Self {
+    fn new(start_percent: f64, end_percent: f64, duration: Duration, shape: SegmentShape) -> Self {
         LinearBuilderPiece {
             start_percent,
             end_percent,
             duration,
+            shape,
+            until: None,
+        }
+    }
+}
+
+// the curve a load_pattern segment follows between its start and end percents
+#[derive(Clone, Debug, PartialEq)]
+pub enum SegmentShape {
+    Linear,
+    Step,
+    Exponential,
+    Sine {
+        amplitude_percent: f64,
+        period: Duration,
+    },
+}
+
+const DAY: Duration = Duration::from_secs(60 * 60 * 24);
+
+// anchors a load_pattern to the clock. The pattern starts each day at `start`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+    // the time since midnight
+    pub start: Duration,
+    pub timezone: Timezone,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Timezone {
+    Local,
+    // seconds east of UTC
+    Offset(i32),
+}
+
+impl Timezone {
+    // parses the timezones accepted by a schedule and the date expression functions
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        if s.eq_ignore_ascii_case("local") {
+            return Some(Timezone::Local);
+        }
+        if s.eq_ignore_ascii_case("utc") || s == "Z" {
+            return Some(Timezone::Offset(0));
+        }
+        let re = Regex::new(r"^(?i)(?:utc)?([+-])(\d{1,2})(?::?(\d{2}))?$")
+            .expect("should be a valid regex");
+        let captures = re.captures(s)?;
+        let piece = |i| {
+            captures
+                .get(i)
+                .map(|m| {
+                    m.as_str()
+                        .parse::<i32>()
+                        .expect("should be valid digits for timezone")
+                })
+                .unwrap_or_default()
+        };
+        let (hours, minutes) = (piece(2), piece(3));
+        if hours > 14 || minutes > 59 {
+            return None;
+        }
+        let offset = hours * 60 * 60 + minutes * 60;
+        if &captures[1] == "-" {
+            Some(Timezone::Offset(-offset))
+        } else {
+            Some(Timezone::Offset(offset))
         }
     }
 }
@@ -175,6 +307,11 @@ trait DefaultWithMarker {
 #[derive(Debug)]
 enum LoadPatternPreProcessed {
     Linear(LinearBuilderPreProcessed),
+    Step(StepBuilderPreProcessed),
+    Exponential(LinearBuilderPreProcessed),
+    Sine(SineBuilderPreProcessed),
+    Schedule(ScheduleBuilderPreProcessed),
+    FromFile(PreTemplate),
 }
 
 impl FromYaml for LoadPatternPreProcessed {
@@ -191,6 +328,34 @@ impl FromYaml for LoadPatternPreProcessed {
                 log::debug!("LoadPatternPreProcessed.parse linear: {:?}", linear);
                 (LoadPatternPreProcessed::Linear(linear), marker)
             }
+            Ok(s) if s.as_str() == "step" => {
+                let (step, marker) = FromYaml::parse(decoder)?;
+                log::debug!("LoadPatternPreProcessed.parse step: {:?}", step);
+                (LoadPatternPreProcessed::Step(step), marker)
+            }
+            Ok(s) if s.as_str() == "exponential" => {
+                let (exponential, marker) = FromYaml::parse(decoder)?;
+                log::debug!(
+                    "LoadPatternPreProcessed.parse exponential: {:?}",
+                    exponential
+                );
+                (LoadPatternPreProcessed::Exponential(exponential), marker)
+            }
+            Ok(s) if s.as_str() == "sine" => {
+                let (sine, marker) = FromYaml::parse(decoder)?;
+                log::debug!("LoadPatternPreProcessed.parse sine: {:?}", sine);
+                (LoadPatternPreProcessed::Sine(sine), marker)
+            }
+            Ok(s) if s.as_str() == "schedule" => {
+                let (schedule, marker) = FromYaml::parse(decoder)?;
+                log::debug!("LoadPatternPreProcessed.parse schedule: {:?}", schedule);
+                (LoadPatternPreProcessed::Schedule(schedule), marker)
+            }
+            Ok(s) if s.as_str() == "from_file" => {
+                let (path, marker) = FromYaml::parse(decoder)?;
+                log::debug!("LoadPatternPreProcessed.parse from_file: {:?}", path);
+                (LoadPatternPreProcessed::FromFile(path), marker)
+            }
             Ok(s) => return Err(Error::UnrecognizedKey(s, None, marker)),
             Err(_) => return Err(Error::YamlDeserialize(None, marker)),
         };
@@ -208,14 +373,14 @@ impl FromYaml for LoadPatternPreProcessed {
 struct LinearBuilderPreProcessed {
     from: Option<PrePercent>,
     to: PrePercent,
-    over: PreDuration,
+    length: PreSegmentLength,
 }
 
 impl FromYaml for LinearBuilderPreProcessed {
     fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
         let mut from = None;
         let mut to = None;
-        let mut over = None;
+        let mut length = None;
 
         let mut first_marker = None;
         let mut saw_opening = false;
@@ -255,7 +420,12 @@ impl FromYaml for LinearBuilderPreProcessed {
                     "over" => {
                         let b = FromYaml::parse_into(decoder)?;
                         log::debug!("LinearBuilderPreProcessed.parse over: {:?}", b);
-                        over = Some(b);
+                        length = PreSegmentLength::set(length, PreSegmentLength::Over(b), marker)?;
+                    }
+                    "until" => {
+                        let b = FromYaml::parse_into(decoder)?;
+                        log::debug!("LinearBuilderPreProcessed.parse until: {:?}", b);
+                        length = PreSegmentLength::set(length, PreSegmentLength::Until(b), marker)?;
                     }
                     _ => return Err(Error::UnrecognizedKey(s, None, marker)),
                 },
@@ -263,47 +433,96 @@ impl FromYaml for LinearBuilderPreProcessed {
         }
         let marker = first_marker.expect("should have a marker");
         let to = to.ok_or(Error::MissingYamlField("to", marker))?;
-        let over = over.ok_or(Error::MissingYamlField("over", marker))?;
-        let ret = Self { from, to, over };
+        let length = length.ok_or(Error::MissingYamlField("over", marker))?;
+        let ret = Self { from, to, length };
         Ok((ret, marker))
     }
 }
 
-#[derive(Clone)]
-pub enum LoadPattern {
-    Linear(LinearBuilder),
+#[cfg_attr(debug_assertions, derive(PartialEq))]
+#[derive(Debug)]
+struct StepBuilderPreProcessed {
+    to: PrePercent,
+    length: PreSegmentLength,
 }
 
-impl LoadPattern {
-    pub fn duration(&self) -> Duration {
-        match self {
-            LoadPattern::Linear(lb) => lb.duration(),
-        }
-    }
+impl FromYaml for StepBuilderPreProcessed {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let mut to = None;
+        let mut length = None;
 
-    pub fn builder(self) -> LinearBuilder {
-        match self {
-            LoadPattern::Linear(lb) => lb,
+        let mut first_marker = None;
+        let mut saw_opening = false;
+        loop {
+            let (event, marker) = decoder.next()?;
+            if first_marker.is_none() {
+                first_marker = Some(marker);
+            }
+            match event {
+                YamlEvent::MappingStart => {
+                    if saw_opening {
+                        return Err(Error::YamlDeserialize(None, marker));
+                    } else {
+                        saw_opening = true;
+                    }
+                }
+                YamlEvent::SequenceStart => {
+                    return Err(Error::YamlDeserialize(None, marker));
+                }
+                YamlEvent::MappingEnd => {
+                    break;
+                }
+                YamlEvent::SequenceEnd => {
+                    unreachable!("shouldn't see sequence end");
+                }
+                YamlEvent::Scalar(s, ..) => match s.as_str() {
+                    "to" => {
+                        let a = FromYaml::parse_into(decoder)?;
+                        log::debug!("StepBuilderPreProcessed.parse to: {:?}", a);
+                        to = Some(a);
+                    }
+                    "over" => {
+                        let b = FromYaml::parse_into(decoder)?;
+                        log::debug!("StepBuilderPreProcessed.parse over: {:?}", b);
+                        length = PreSegmentLength::set(length, PreSegmentLength::Over(b), marker)?;
+                    }
+                    "until" => {
+                        let b = FromYaml::parse_into(decoder)?;
+                        log::debug!("StepBuilderPreProcessed.parse until: {:?}", b);
+                        length = PreSegmentLength::set(length, PreSegmentLength::Until(b), marker)?;
+                    }
+                    _ => return Err(Error::UnrecognizedKey(s, None, marker)),
+                },
+            }
         }
+        let marker = first_marker.expect("should have a marker");
+        let to = to.ok_or(Error::MissingYamlField("to", marker))?;
+        let length = length.ok_or(Error::MissingYamlField("over", marker))?;
+        let ret = Self { to, length };
+        Ok((ret, marker))
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
-pub struct ListWithOptions {
-    pub random: bool,
-    pub repeat: bool,
-    pub values: Vec<json::Value>,
-    pub unique: bool,
+#[cfg_attr(debug_assertions, derive(PartialEq))]
+#[derive(Debug)]
+struct SineBuilderPreProcessed {
+    from: Option<PrePercent>,
+    to: PrePercent,
+    amplitude: PrePercent,
+    period: PreDuration,
+    length: PreSegmentLength,
 }
 
-impl FromYaml for ListWithOptions {
+impl FromYaml for SineBuilderPreProcessed {
     fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
-        let mut saw_opening = false;
-        let mut random = false;
-        let mut repeat = true;
-        let mut values = None;
-        let mut unique = false;
+        let mut from = None;
+        let mut to = None;
+        let mut amplitude = None;
+        let mut period = None;
+        let mut length = None;
+
         let mut first_marker = None;
+        let mut saw_opening = false;
         loop {
             let (event, marker) = decoder.next()?;
             if first_marker.is_none() {
@@ -327,158 +546,122 @@ impl FromYaml for ListWithOptions {
                     unreachable!("shouldn't see sequence end");
                 }
                 YamlEvent::Scalar(s, ..) => match s.as_str() {
-                    "random" => {
-                        let (r, _): (bool, _) =
-                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        log::debug!("ListWithOptions.parse random: {:?}", r);
-                        random = r;
+                    "from" => {
+                        let c = FromYaml::parse_into(decoder)?;
+                        log::debug!("SineBuilderPreProcessed.parse from: {:?}", c);
+                        from = Some(c);
                     }
-                    "repeat" => {
-                        let (r, _) =
-                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        log::debug!("ListWithOptions.parse repeat: {:?}", r);
-                        repeat = r;
+                    "to" => {
+                        let a = FromYaml::parse_into(decoder)?;
+                        log::debug!("SineBuilderPreProcessed.parse to: {:?}", a);
+                        to = Some(a);
                     }
-                    "values" => {
-                        let (v, _) =
-                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        log::debug!("ListWithOptions.parse values: {:?}", v);
-                        values = Some(v);
+                    "amplitude" => {
+                        let a = FromYaml::parse_into(decoder)?;
+                        log::debug!("SineBuilderPreProcessed.parse amplitude: {:?}", a);
+                        amplitude = Some(a);
                     }
-                    "unique" => {
-                        let (u, _) =
-                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        log::debug!("ListWithOptions.parse unique: {:?}", u);
-                        unique = u;
+                    "period" => {
+                        let p = FromYaml::parse_into(decoder)?;
+                        log::debug!("SineBuilderPreProcessed.parse period: {:?}", p);
+                        period = Some(p);
+                    }
+                    "over" => {
+                        let b = FromYaml::parse_into(decoder)?;
+                        log::debug!("SineBuilderPreProcessed.parse over: {:?}", b);
+                        length = PreSegmentLength::set(length, PreSegmentLength::Over(b), marker)?;
+                    }
+                    "until" => {
+                        let b = FromYaml::parse_into(decoder)?;
+                        log::debug!("SineBuilderPreProcessed.parse until: {:?}", b);
+                        length = PreSegmentLength::set(length, PreSegmentLength::Until(b), marker)?;
                     }
                     _ => return Err(Error::UnrecognizedKey(s, None, marker)),
                 },
             }
         }
         let marker = first_marker.expect("should have a marker");
-        let values = values.ok_or(Error::MissingYamlField("values", marker))?;
+        let to = to.ok_or(Error::MissingYamlField("to", marker))?;
+        let amplitude = amplitude.ok_or(Error::MissingYamlField("amplitude", marker))?;
+        let period = period.ok_or(Error::MissingYamlField("period", marker))?;
+        let length = length.ok_or(Error::MissingYamlField("over", marker))?;
         let ret = Self {
-            random,
-            repeat,
-            values,
-            unique,
+            from,
+            to,
+            amplitude,
+            period,
+            length,
         };
         Ok((ret, marker))
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
-pub enum ListProvider {
-    WithOptions(ListWithOptions),
-    DefaultOptions(Vec<json::Value>),
-}
-
-impl ListProvider {
-    pub fn unique(&self) -> bool {
-        matches!(self, ListProvider::WithOptions(l) if l.unique)
-    }
+// how long a load_pattern segment lasts, either a duration or until a clock time
+#[cfg_attr(debug_assertions, derive(PartialEq))]
+#[derive(Debug)]
+enum PreSegmentLength {
+    Over(PreDuration),
+    Until(PreClockTime),
 }
 
-impl FromYaml for ListProvider {
-    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
-        let (event, marker) = decoder.peek()?;
-        match event {
-            YamlEvent::SequenceStart => {
-                let (e, marker) = FromYaml::parse(decoder)?;
-                log::debug!("ListProvider.parse SequenceStart: {:?}", e);
-                let value = (ListProvider::DefaultOptions(e), marker);
-                Ok(value)
+impl PreSegmentLength {
+    // a segment can have either an `over` or an `until`, but not both. The error names the key
+    // which was seen second
+    fn set(current: Option<Self>, new: Self, marker: Marker) -> Result<Option<Self>, Error> {
+        match (current, new) {
+            (Some(_), PreSegmentLength::Over(_)) => {
+                Err(Error::YamlDeserialize(Some("over".into()), marker))
             }
-            YamlEvent::MappingStart => {
-                let (i, marker) = FromYaml::parse(decoder)?;
-                log::debug!("ListProvider.parse MappingStart: {:?}", i);
-                let value = (ListProvider::WithOptions(i), marker);
-                Ok(value)
+            (Some(_), PreSegmentLength::Until(_)) => {
+                Err(Error::YamlDeserialize(Some("until".into()), marker))
             }
-            _ => Err(Error::YamlDeserialize(None, *marker)),
+            (None, new) => Ok(Some(new)),
         }
     }
-}
-
-impl From<Vec<json::Value>> for ListProvider {
-    fn from(v: Vec<json::Value>) -> Self {
-        ListProvider::DefaultOptions(v)
-    }
-}
-
-impl From<ListWithOptions> for ListProvider {
-    fn from(e: ListWithOptions) -> Self {
-        ListProvider::WithOptions(e)
-    }
-}
-
-impl IntoIterator for ListProvider {
-    type Item = json::Value;
-    type IntoIter = Either3<
-        ListRepeatRandomIterator,
-        std::vec::IntoIter<json::Value>,
-        std::iter::Cycle<std::vec::IntoIter<json::Value>>,
-    >;
 
-    fn into_iter(self) -> Self::IntoIter {
+    // the length of the segment and, for an `until`, the time of day it ends. `clock` is the time
+    // of day the segment starts at, when the load_pattern has a schedule
+    fn evaluate(
+        &self,
+        static_vars: &BTreeMap<String, json::Value>,
+        clock: Option<Duration>,
+        marker: Marker,
+    ) -> Result<(Duration, Option<Duration>), Error> {
         match self {
-            ListProvider::WithOptions(mut e) => match (e.repeat, e.random) {
-                (true, true) => {
-                    let a = ListRepeatRandomIterator {
-                        random: Uniform::new(0, e.values.len()),
-                        values: e.values,
-                    };
-                    Either3::A(a)
-                }
-                (false, false) => Either3::B(e.values.into_iter()),
-                (false, true) => {
-                    let mut rng = rand::thread_rng();
-                    e.values.sort_unstable_by_key(|_| rng.gen::<usize>());
-                    Either3::B(e.values.into_iter())
+            PreSegmentLength::Over(d) => Ok((d.evaluate(static_vars)?, None)),
+            PreSegmentLength::Until(t) => {
+                let clock = clock.ok_or(Error::InvalidLoadPattern(marker))?;
+                let until = t.evaluate(static_vars)?;
+                // `until` can be past midnight
+                let length = if until > clock {
+                    until - clock
+                } else {
+                    DAY + until - clock
+                };
+                if length == DAY {
+                    return Err(Error::InvalidLoadPattern(marker));
                 }
-                (true, false) => Either3::C(e.values.into_iter().cycle()),
-            },
-            ListProvider::DefaultOptions(v) => Either3::C(v.into_iter().cycle()),
+                Ok((length, Some(until)))
+            }
         }
     }
 }
 
-pub struct ListRepeatRandomIterator {
-    values: Vec<json::Value>,
-    random: Uniform<usize>,
-}
-
-impl Iterator for ListRepeatRandomIterator {
-    type Item = json::Value;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let pos_index = self.random.sample(&mut rand::thread_rng());
-        self.values.get(pos_index).cloned()
-    }
-}
-
 #[cfg_attr(debug_assertions, derive(PartialEq))]
 #[derive(Debug)]
-enum ProviderPreProcessed {
-    File(FileProviderPreProcessed),
-    Range(RangeProviderPreProcessed),
-    Response(ResponseProvider),
-    List(ListProvider),
-}
-
-#[derive(Clone, PartialEq)]
-pub enum Provider {
-    File(FileProvider),
-    Range(RangeProvider),
-    Response(ResponseProvider),
-    List(ListProvider),
+struct ScheduleBuilderPreProcessed {
+    start: Option<PreClockTime>,
+    timezone: Option<PreTimezone>,
 }
 
-impl FromYaml for ProviderPreProcessed {
+impl FromYaml for ScheduleBuilderPreProcessed {
     fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let mut start = None;
+        let mut timezone = None;
+
         let mut first_marker = None;
         let mut saw_opening = false;
-        let ret = loop {
+        loop {
             let (event, marker) = decoder.next()?;
             if first_marker.is_none() {
                 first_marker = Some(marker);
@@ -495,116 +678,1153 @@ impl FromYaml for ProviderPreProcessed {
                     return Err(Error::YamlDeserialize(None, marker));
                 }
                 YamlEvent::MappingEnd => {
-                    return Err(Error::YamlDeserialize(None, marker));
+                    break;
                 }
                 YamlEvent::SequenceEnd => {
                     unreachable!("shouldn't see sequence end");
                 }
                 YamlEvent::Scalar(s, ..) => match s.as_str() {
-                    "file" => {
-                        let (c, marker) =
-                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        log::debug!("ProviderPreProcessed.parse file: {:?}", c);
-                        break (ProviderPreProcessed::File(c), marker);
+                    "start" => {
+                        let a = FromYaml::parse_into(decoder)?;
+                        log::debug!("ScheduleBuilderPreProcessed.parse start: {:?}", a);
+                        start = Some(a);
                     }
-                    "range" => {
-                        let (c, marker) =
-                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        log::debug!("ProviderPreProcessed.parse range: {:?}", c);
-                        break (ProviderPreProcessed::Range(c), marker);
-                    }
-                    "response" => {
-                        let (c, marker) =
-                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        log::debug!("ProviderPreProcessed.parse response: {:?}", c);
-                        break (ProviderPreProcessed::Response(c), marker);
-                    }
-                    "list" => {
-                        let (c, marker) =
-                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        log::debug!("ProviderPreProcessed.parse list: {:?}", c);
-                        break (ProviderPreProcessed::List(c), marker);
+                    "timezone" => {
+                        let b = FromYaml::parse_into(decoder)?;
+                        log::debug!("ScheduleBuilderPreProcessed.parse timezone: {:?}", b);
+                        timezone = Some(b);
                     }
-                    _ => return Err(Error::UnrecognizedKey(s, None, marker)),
+                    _ => return Err(Error::UnrecognizedKey(s, Some("schedule".into()), marker)),
                 },
             }
+        }
+        let marker = first_marker.expect("should have a marker");
+        let ret = Self { start, timezone };
+        Ok((ret, marker))
+    }
+}
+
+impl ScheduleBuilderPreProcessed {
+    fn evaluate(&self, static_vars: &BTreeMap<String, json::Value>) -> Result<Schedule, Error> {
+        let start = self
+            .start
+            .as_ref()
+            .map(|s| s.evaluate(static_vars))
+            .transpose()?
+            .unwrap_or_default();
+        let timezone = self
+            .timezone
+            .as_ref()
+            .map(|t| t.evaluate(static_vars))
+            .transpose()?
+            .unwrap_or(Timezone::Local);
+        Ok(Schedule { start, timezone })
+    }
+}
+
+// a time of day in the format `HH:MM` or `HH:MM:SS`
+#[cfg_attr(debug_assertions, derive(PartialEq))]
+#[derive(Debug)]
+struct PreClockTime(PreTemplate);
+
+impl PreClockTime {
+    // evaluates to the time since midnight
+    fn evaluate(&self, static_vars: &BTreeMap<String, json::Value>) -> Result<Duration, Error> {
+        let string = self
+            .0
+            .evaluate(static_vars, &mut RequiredProviders::new())?;
+        let re = Regex::new(r"^(\d{1,2}):(\d{2})(?::(\d{2}))?$").expect("should be a valid regex");
+        let invalid = || Error::InvalidClockTime(string.clone(), ((self.0).0).marker);
+        let captures = re.captures(&string).ok_or_else(invalid)?;
+        let piece = |i| {
+            captures
+                .get(i)
+                .map(|m| {
+                    m.as_str()
+                        .parse::<u64>()
+                        .expect("should be valid digits for clock time")
+                })
+                .unwrap_or_default()
         };
-        let (event, marker) = decoder.next()?;
-        match event {
-            YamlEvent::MappingEnd => (),
-            _ => return Err(Error::YamlDeserialize(None, marker)),
+        let (hours, minutes, seconds) = (piece(1), piece(2), piece(3));
+        if hours > 23 || minutes > 59 || seconds > 59 {
+            return Err(invalid());
         }
-        Ok(ret)
+        Ok(Duration::from_secs(
+            hours * 60 * 60 + minutes * 60 + seconds,
+        ))
     }
 }
 
-impl ProviderPreProcessed {
-    fn is_response_provider(&self) -> bool {
-        matches!(self, ProviderPreProcessed::Response(_))
+impl FromYaml for PreClockTime {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let (t, marker) = FromYaml::parse(decoder)?;
+        Ok((Self(t), marker))
     }
 }
 
-type RangeProviderIteratorA = iter::StepBy<std::ops::RangeInclusive<i64>>;
+// `local`, `UTC` or an offset from UTC such as `-07:00` or `UTC+05:30`
+#[cfg_attr(debug_assertions, derive(PartialEq))]
+#[derive(Debug)]
+struct PreTimezone(PreTemplate);
+
+impl PreTimezone {
+    fn evaluate(&self, static_vars: &BTreeMap<String, json::Value>) -> Result<Timezone, Error> {
+        let string = self
+            .0
+            .evaluate(static_vars, &mut RequiredProviders::new())?;
+        Timezone::parse(&string)
+            .ok_or_else(|| Error::InvalidTimezone(string.clone(), ((self.0).0).marker))
+    }
+}
+
+impl FromYaml for PreTimezone {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let (t, marker) = FromYaml::parse(decoder)?;
+        Ok((Self(t), marker))
+    }
+}
 
 #[derive(Clone)]
-pub struct RangeProvider(
-    pub Either<RangeProviderIteratorA, iter::Cycle<RangeProviderIteratorA>>,
-    RangeProviderPreProcessed,
-);
+pub enum LoadPattern {
+    Linear(LinearBuilder),
+}
 
-impl RangeProvider {
-    pub fn unique(&self) -> bool {
-        self.1.unique
+impl LoadPattern {
+    pub fn duration(&self) -> Duration {
+        match self {
+            LoadPattern::Linear(lb) => lb.duration(),
+        }
+    }
+
+    pub fn builder(self) -> LinearBuilder {
+        match self {
+            LoadPattern::Linear(lb) => lb,
+        }
+    }
+
+    // For a load_pattern with a schedule, returns how far into the pattern the clock is at the
+    // given time, so the test picks up where the schedule is whenever it starts. If the pattern
+    // has already finished for the day it first holds at 0% until the next start.
+    // `local_offset` gives the local timezone's offset from UTC in seconds at a time in seconds
+    // since the epoch, so a daylight saving change is accounted for
+    pub fn align_to_clock(
+        &mut self,
+        since_epoch: Duration,
+        local_offset: &dyn Fn(i64) -> i32,
+    ) -> Option<Duration> {
+        let LoadPattern::Linear(lb) = self;
+        let schedule = lb.schedule.as_ref()?;
+        let timezone = schedule.timezone;
+        let start = schedule.start.as_millis() as i64;
+        // the offset in milliseconds at a time in milliseconds since the epoch
+        let offset_at = |t: i64| {
+            let offset = match timezone {
+                Timezone::Local => local_offset(t.div_euclid(1000)),
+                Timezone::Offset(offset) => offset,
+            };
+            offset as i64 * 1000
+        };
+        let day = DAY.as_millis() as i64;
+        let now = since_epoch.as_millis() as i64;
+        let clock = (now + offset_at(now)).rem_euclid(day);
+        // the most recent start, moved by any change in the offset since then
+        let today = now - (clock - start).rem_euclid(day);
+        let today = today + offset_at(now) - offset_at(today);
+        lb.fit_to_clock(today, &offset_at);
+        if now - today < lb.duration.as_millis() as i64 {
+            Some(Duration::from_millis((now - today).max(0) as u64))
+        } else {
+            // a day on the clock is not always 24 hours
+            let next = today + day;
+            let next = next + offset_at(today) - offset_at(next);
+            lb.fit_to_clock(next, &offset_at);
+            let wait = Duration::from_millis((next - now).max(0) as u64);
+            let piece = LinearBuilderPiece::new(0.0, 0.0, wait, SegmentShape::Step);
+            lb.pieces.insert(0, piece);
+            lb.duration += wait;
+            Some(Duration::from_secs(0))
+        }
     }
 }
 
-impl PartialEq for RangeProvider {
-    fn eq(&self, rhs: &Self) -> bool {
-        self.1 == rhs.1
+#[derive(Clone, Debug, PartialEq)]
+pub struct ListWithOptions {
+    pub random: bool,
+    pub repeat: bool,
+    pub values: Vec<json::Value>,
+    // the relative likelihood of each value being picked, when the list uses `weighted` entries
+    pub weights: Option<Vec<f64>>,
+    pub shuffle: bool,
+    pub unique: bool,
+}
+
+// an entry in a list provider's `weighted` values
+#[cfg_attr(debug_assertions, derive(PartialEq))]
+#[derive(Debug)]
+struct WeightedValue {
+    value: json::Value,
+    weight: f64,
+}
+
+impl FromYaml for WeightedValue {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let mut saw_opening = false;
+        let mut value = None;
+        let mut weight = None;
+        let mut first_marker = None;
+        loop {
+            let (event, marker) = decoder.next()?;
+            if first_marker.is_none() {
+                first_marker = Some(marker);
+            }
+            match event {
+                YamlEvent::MappingStart => {
+                    if saw_opening {
+                        return Err(Error::YamlDeserialize(None, marker));
+                    } else {
+                        saw_opening = true;
+                    }
+                }
+                YamlEvent::SequenceStart => {
+                    return Err(Error::YamlDeserialize(None, marker));
+                }
+                YamlEvent::MappingEnd => {
+                    break;
+                }
+                YamlEvent::SequenceEnd => {
+                    unreachable!("shouldn't see sequence end");
+                }
+                YamlEvent::Scalar(s, ..) => match s.as_str() {
+                    "value" => {
+                        let (v, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        value = Some(v);
+                    }
+                    "weight" => {
+                        let (w, marker): (String, _) = FromYaml::parse(decoder)
+                            .map_err(map_yaml_deserialize_err(s.clone()))?;
+                        let w = f64::from_str(&w)
+                            .ok()
+                            .filter(|w| w.is_finite() && *w > 0.0)
+                            .ok_or(Error::YamlDeserialize(Some(s), marker))?;
+                        weight = Some(w);
+                    }
+                    _ => return Err(Error::UnrecognizedKey(s, None, marker)),
+                },
+            }
+        }
+        let marker = first_marker.expect("should have a marker");
+        let value = value.ok_or(Error::MissingYamlField("value", marker))?;
+        let weight = weight.ok_or(Error::MissingYamlField("weight", marker))?;
+        Ok((Self { value, weight }, marker))
     }
 }
 
-impl From<RangeProviderPreProcessed> for RangeProvider {
-    fn from(rppp: RangeProviderPreProcessed) -> Self {
-        let start = rppp.start;
-        let end = rppp.end;
-        let step = rppp.step.get().into();
-        let iter = (start..=end).step_by(step);
-        let iter = if rppp.repeat {
-            Either::B(iter.cycle())
-        } else {
-            Either::A(iter)
+impl FromYaml for ListWithOptions {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let mut saw_opening = false;
+        let mut random = false;
+        let mut repeat = true;
+        let mut values = None;
+        let mut weighted = None;
+        let mut shuffle = false;
+        let mut unique = false;
+        let mut first_marker = None;
+        loop {
+            let (event, marker) = decoder.next()?;
+            if first_marker.is_none() {
+                first_marker = Some(marker);
+            }
+            match event {
+                YamlEvent::MappingStart => {
+                    if saw_opening {
+                        return Err(Error::YamlDeserialize(None, marker));
+                    } else {
+                        saw_opening = true;
+                    }
+                }
+                YamlEvent::SequenceStart => {
+                    return Err(Error::YamlDeserialize(None, marker));
+                }
+                YamlEvent::MappingEnd => {
+                    break;
+                }
+                YamlEvent::SequenceEnd => {
+                    unreachable!("shouldn't see sequence end");
+                }
+                YamlEvent::Scalar(s, ..) => match s.as_str() {
+                    "random" => {
+                        let (r, _): (bool, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("ListWithOptions.parse random: {:?}", r);
+                        random = r;
+                    }
+                    "repeat" => {
+                        let (r, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("ListWithOptions.parse repeat: {:?}", r);
+                        repeat = r;
+                    }
+                    "shuffle" => {
+                        let (r, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("ListWithOptions.parse shuffle: {:?}", r);
+                        shuffle = r;
+                    }
+                    "values" => {
+                        let (v, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("ListWithOptions.parse values: {:?}", v);
+                        values = Some(v);
+                    }
+                    "weighted" => {
+                        let (w, m): (Vec<WeightedValue>, _) = FromYaml::parse(decoder)
+                            .map_err(map_yaml_deserialize_err(s.clone()))?;
+                        log::debug!("ListWithOptions.parse weighted: {:?}", w);
+                        // every weight is positive, but the list still needs an entry and a sum
+                        // which can be sampled from
+                        let sum: f64 = w.iter().map(|v| v.weight).sum();
+                        if w.is_empty() || sum <= 0.0 || !sum.is_finite() {
+                            return Err(Error::YamlDeserialize(Some(s), m));
+                        }
+                        weighted = Some(w);
+                    }
+                    "unique" => {
+                        let (u, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("ListWithOptions.parse unique: {:?}", u);
+                        unique = u;
+                    }
+                    _ => return Err(Error::UnrecognizedKey(s, None, marker)),
+                },
+            }
+        }
+        let marker = first_marker.expect("should have a marker");
+        let (values, weights) = match (values, weighted) {
+            (Some(values), None) => (values, None),
+            (None, Some(_)) if shuffle => {
+                return Err(Error::YamlDeserialize(Some("shuffle".into()), marker))
+            }
+            (None, Some(weighted)) => {
+                let (values, weights) = weighted
+                    .into_iter()
+                    .map(|WeightedValue { value, weight }| (value, weight))
+                    .unzip();
+                (values, Some(weights))
+            }
+            (Some(_), Some(_)) => {
+                return Err(Error::YamlDeserialize(Some("weighted".into()), marker))
+            }
+            (None, None) => return Err(Error::MissingYamlField("values", marker)),
         };
-        RangeProvider(iter, rppp)
+        let ret = Self {
+            random,
+            repeat,
+            values,
+            weights,
+            shuffle,
+            unique,
+        };
+        Ok((ret, marker))
     }
 }
 
-impl fmt::Display for RangeProvider {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", serde_json::to_string(&self.1).unwrap_or_default())
+#[derive(Clone, Debug, PartialEq)]
+pub enum ListProvider {
+    WithOptions(ListWithOptions),
+    DefaultOptions(Vec<json::Value>),
+}
+
+impl ListProvider {
+    pub fn unique(&self) -> bool {
+        matches!(self, ListProvider::WithOptions(l) if l.unique)
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize)]
-pub struct RangeProviderPreProcessed {
-    start: i64,
-    end: i64,
-    step: NonZeroU16,
+impl FromYaml for ListProvider {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let (event, marker) = decoder.peek()?;
+        match event {
+            YamlEvent::SequenceStart => {
+                let (e, marker) = FromYaml::parse(decoder)?;
+                log::debug!("ListProvider.parse SequenceStart: {:?}", e);
+                let value = (ListProvider::DefaultOptions(e), marker);
+                Ok(value)
+            }
+            YamlEvent::MappingStart => {
+                let (i, marker) = FromYaml::parse(decoder)?;
+                log::debug!("ListProvider.parse MappingStart: {:?}", i);
+                let value = (ListProvider::WithOptions(i), marker);
+                Ok(value)
+            }
+            _ => Err(Error::YamlDeserialize(None, *marker)),
+        }
+    }
+}
+
+impl From<Vec<json::Value>> for ListProvider {
+    fn from(v: Vec<json::Value>) -> Self {
+        ListProvider::DefaultOptions(v)
+    }
+}
+
+impl From<ListWithOptions> for ListProvider {
+    fn from(e: ListWithOptions) -> Self {
+        ListProvider::WithOptions(e)
+    }
+}
+
+impl IntoIterator for ListProvider {
+    type Item = json::Value;
+    type IntoIter = Either3<
+        ListRepeatRandomIterator,
+        std::vec::IntoIter<json::Value>,
+        std::iter::Cycle<std::vec::IntoIter<json::Value>>,
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        match self {
+            // weighted values are always provided in random order
+            ListProvider::WithOptions(mut e) => {
+                let random = e.random || e.shuffle || e.weights.is_some();
+                match (e.repeat, random) {
+                    (true, true) => {
+                        let random = match e.weights {
+                            Some(weights) => ListRandom::Weighted(
+                                WeightedIndex::new(weights)
+                                    .expect("weights are validated when parsed"),
+                            ),
+                            // start at the end so the values are shuffled before the first pass
+                            None if e.shuffle => ListRandom::Shuffle(e.values.len()),
+                            None => ListRandom::Uniform(Uniform::new(0, e.values.len())),
+                        };
+                        let a = ListRepeatRandomIterator {
+                            random,
+                            values: e.values,
+                        };
+                        Either3::A(a)
+                    }
+                    (false, false) => Either3::B(e.values.into_iter()),
+                    (false, true) => {
+                        let mut rng = rand::thread_rng();
+                        match e.weights {
+                            // a weighted shuffle where each value's key is `u^(1/weight)`, so
+                            // values with a higher weight tend to come first
+                            Some(weights) => {
+                                let mut keyed: Vec<_> = weights
+                                    .into_iter()
+                                    .map(|w| rng.gen::<f64>().powf(1.0 / w))
+                                    .zip(e.values)
+                                    .collect();
+                                keyed.sort_unstable_by(|(a, _), (b, _)| b.total_cmp(a));
+                                e.values = keyed.into_iter().map(|(_, v)| v).collect();
+                            }
+                            None => e.values.shuffle(&mut rng),
+                        }
+                        Either3::B(e.values.into_iter())
+                    }
+                    (true, false) => Either3::C(e.values.into_iter().cycle()),
+                }
+            }
+            ListProvider::DefaultOptions(v) => Either3::C(v.into_iter().cycle()),
+        }
+    }
+}
+
+enum ListRandom {
+    Uniform(Uniform<usize>),
+    Weighted(WeightedIndex<f64>),
+    // the index of the next value to provide. The values are shuffled again after each pass
+    Shuffle(usize),
+}
+
+pub struct ListRepeatRandomIterator {
+    values: Vec<json::Value>,
+    random: ListRandom,
+}
+
+impl Iterator for ListRepeatRandomIterator {
+    type Item = json::Value;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut rng = rand::thread_rng();
+        let pos_index = match &mut self.random {
+            ListRandom::Uniform(uniform) => uniform.sample(&mut rng),
+            ListRandom::Weighted(weighted) => weighted.sample(&mut rng),
+            ListRandom::Shuffle(next) => {
+                if *next >= self.values.len() {
+                    self.values.shuffle(&mut rng);
+                    *next = 0;
+                }
+                *next += 1;
+                *next - 1
+            }
+        };
+        self.values.get(pos_index).cloned()
+    }
+}
+
+#[cfg_attr(debug_assertions, derive(PartialEq))]
+#[derive(Debug)]
+enum ProviderPreProcessed {
+    File(FileProviderPreProcessed),
+    Range(RangeProviderPreProcessed),
+    Response(ResponseProviderPreProcessed),
+    List(ListProvider),
+    Sqlite(SqliteProviderPreProcessed),
+    Generate(GenerateProvider),
+    Map(MapProvider),
+    Sequence(SequenceProviderPreProcessed),
+    Files(FilesProviderPreProcessed),
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Provider {
+    File(FileProvider),
+    Range(RangeProvider),
+    Response(ResponseProvider),
+    List(ListProvider),
+    Sqlite(SqliteProvider),
+    Generate(GenerateProvider),
+    Map(MapProvider),
+    Sequence(SequenceProvider),
+    Files(FilesProvider),
+}
+
+impl FromYaml for ProviderPreProcessed {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let mut first_marker = None;
+        let mut saw_opening = false;
+        let ret = loop {
+            let (event, marker) = decoder.next()?;
+            if first_marker.is_none() {
+                first_marker = Some(marker);
+            }
+            match event {
+                YamlEvent::MappingStart => {
+                    if saw_opening {
+                        return Err(Error::YamlDeserialize(None, marker));
+                    } else {
+                        saw_opening = true;
+                    }
+                }
+                YamlEvent::SequenceStart => {
+                    return Err(Error::YamlDeserialize(None, marker));
+                }
+                YamlEvent::MappingEnd => {
+                    return Err(Error::YamlDeserialize(None, marker));
+                }
+                YamlEvent::SequenceEnd => {
+                    unreachable!("shouldn't see sequence end");
+                }
+                YamlEvent::Scalar(s, ..) => match s.as_str() {
+                    "file" => {
+                        let (c, marker) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("ProviderPreProcessed.parse file: {:?}", c);
+                        break (ProviderPreProcessed::File(c), marker);
+                    }
+                    "range" => {
+                        let (c, marker) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("ProviderPreProcessed.parse range: {:?}", c);
+                        break (ProviderPreProcessed::Range(c), marker);
+                    }
+                    "response" => {
+                        let (c, marker) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("ProviderPreProcessed.parse response: {:?}", c);
+                        break (ProviderPreProcessed::Response(c), marker);
+                    }
+                    "list" => {
+                        let (c, marker) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("ProviderPreProcessed.parse list: {:?}", c);
+                        break (ProviderPreProcessed::List(c), marker);
+                    }
+                    "sqlite" => {
+                        let (c, marker) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("ProviderPreProcessed.parse sqlite: {:?}", c);
+                        break (ProviderPreProcessed::Sqlite(c), marker);
+                    }
+                    "generate" => {
+                        let (c, marker) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("ProviderPreProcessed.parse generate: {:?}", c);
+                        break (ProviderPreProcessed::Generate(c), marker);
+                    }
+                    "map" => {
+                        let (c, marker) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("ProviderPreProcessed.parse map: {:?}", c);
+                        break (ProviderPreProcessed::Map(c), marker);
+                    }
+                    "sequence" => {
+                        let (c, marker) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("ProviderPreProcessed.parse sequence: {:?}", c);
+                        break (ProviderPreProcessed::Sequence(c), marker);
+                    }
+                    "files" => {
+                        let (c, marker) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("ProviderPreProcessed.parse files: {:?}", c);
+                        break (ProviderPreProcessed::Files(c), marker);
+                    }
+                    _ => return Err(Error::UnrecognizedKey(s, None, marker)),
+                },
+            }
+        };
+        let (event, marker) = decoder.next()?;
+        match event {
+            YamlEvent::MappingEnd => (),
+            _ => return Err(Error::YamlDeserialize(None, marker)),
+        }
+        Ok(ret)
+    }
+}
+
+impl ProviderPreProcessed {
+    // map providers are also filled by responses
+    fn is_response_provider(&self) -> bool {
+        matches!(
+            self,
+            ProviderPreProcessed::Response(_) | ProviderPreProcessed::Map(_)
+        )
+    }
+}
+
+type RangeProviderIteratorA = iter::StepBy<std::ops::RangeInclusive<i64>>;
+
+#[derive(Clone)]
+pub struct RangeProvider(
+    pub Either<RangeProviderIteratorA, iter::Cycle<RangeProviderIteratorA>>,
+    RangeProviderPreProcessed,
+);
+
+impl RangeProvider {
+    pub fn unique(&self) -> bool {
+        self.1.unique
+    }
+}
+
+impl PartialEq for RangeProvider {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.1 == rhs.1
+    }
+}
+
+impl From<RangeProviderPreProcessed> for RangeProvider {
+    fn from(rppp: RangeProviderPreProcessed) -> Self {
+        let start = rppp.start;
+        let end = rppp.end;
+        let step = rppp.step.get().into();
+        let iter = (start..=end).step_by(step);
+        let iter = if rppp.repeat {
+            Either::B(iter.cycle())
+        } else {
+            Either::A(iter)
+        };
+        RangeProvider(iter, rppp)
+    }
+}
+
+impl fmt::Display for RangeProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", serde_json::to_string(&self.1).unwrap_or_default())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RangeProviderPreProcessed {
+    start: i64,
+    end: i64,
+    step: NonZeroU16,
+    repeat: bool,
+    unique: bool,
+}
+
+impl FromYaml for RangeProviderPreProcessed {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let mut saw_opening = false;
+
+        let mut start = 0;
+        let mut end = std::i64::MAX;
+        let mut step = NonZeroU16::new(1).expect("1 is non-zero");
+        let mut repeat = false;
+        let mut unique = false;
+
+        let mut first_marker = None;
+        loop {
+            let (event, marker) = decoder.next()?;
+            if first_marker.is_none() {
+                first_marker = Some(marker);
+            }
+            match event {
+                YamlEvent::MappingStart => {
+                    if saw_opening {
+                        return Err(Error::YamlDeserialize(None, marker));
+                    } else {
+                        saw_opening = true;
+                    }
+                }
+                YamlEvent::SequenceStart => {
+                    return Err(Error::YamlDeserialize(None, marker));
+                }
+                YamlEvent::MappingEnd => {
+                    break;
+                }
+                YamlEvent::SequenceEnd => {
+                    unreachable!("shouldn't see sequence end");
+                }
+                YamlEvent::Scalar(s, ..) => match s.as_str() {
+                    "start" => {
+                        let (s, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        start = s;
+                    }
+                    "end" => {
+                        let (e, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        end = e;
+                    }
+                    "step" => {
+                        let (s, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        step = s;
+                    }
+                    "repeat" => {
+                        let (r, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        repeat = r;
+                    }
+                    "unique" => {
+                        let (u, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        unique = u;
+                    }
+                    _ => return Err(Error::UnrecognizedKey(s, None, marker)),
+                },
+            }
+        }
+        let marker = first_marker.expect("should have a marker");
+        let ret = Self {
+            start,
+            end,
+            step,
+            repeat,
+            unique,
+        };
+        Ok((ret, marker))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FileFormat {
+    Csv,
+    Json,
+    Line,
+}
+
+impl FromYaml for FileFormat {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let (event, marker) = decoder.next()?;
+        let format = match event.as_str() {
+            Some("csv") => FileFormat::Csv,
+            Some("json") => FileFormat::Json,
+            Some("line") => FileFormat::Line,
+            _ => return Err(Error::YamlDeserialize(None, marker)),
+        };
+        Ok((format, marker))
+    }
+}
+
+impl Default for FileFormat {
+    fn default() -> Self {
+        FileFormat::Line
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CsvHeader {
+    Bool(bool),
+    String(String),
+}
+
+impl FromYaml for CsvHeader {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let (event, marker) = decoder.next()?;
+        match event.as_bool() {
+            Some(b) => Ok((CsvHeader::Bool(b), marker)),
+            None => event
+                .into_string()
+                .map(|s| (CsvHeader::String(s), marker))
+                .map_err(|_| Error::YamlDeserialize(None, marker)),
+        }
+    }
+}
+
+impl Default for CsvHeader {
+    fn default() -> Self {
+        CsvHeader::Bool(false)
+    }
+}
+
+fn from_yaml_char_u8<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> Result<u8, Error> {
+    let (event, marker) = decoder.next()?;
+    match event.as_x::<char>() {
+        Some(c) if c.is_ascii() => {
+            let mut b = [0; 1];
+            let _ = c.encode_utf8(&mut b);
+            Ok(b[0])
+        }
+        _ => Err(Error::YamlDeserialize(None, marker)),
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CsvSettings {
+    pub comment: Option<u8>,
+    pub delimiter: Option<u8>,
+    pub double_quote: Option<bool>,
+    pub escape: Option<u8>,
+    pub headers: CsvHeader,
+    pub terminator: Option<u8>,
+    pub quote: Option<u8>,
+}
+
+impl FromYaml for CsvSettings {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let mut comment = None;
+        let mut delimiter = None;
+        let mut double_quote = None;
+        let mut escape = None;
+        let mut headers = None;
+        let mut terminator = None;
+        let mut quote = None;
+
+        let mut first_marker = None;
+        let mut saw_opening = false;
+        loop {
+            let (event, marker) = decoder.next()?;
+            if first_marker.is_none() {
+                first_marker = Some(marker);
+            }
+            match event {
+                YamlEvent::MappingStart => {
+                    if saw_opening {
+                        return Err(Error::YamlDeserialize(None, marker));
+                    } else {
+                        saw_opening = true;
+                    }
+                }
+                YamlEvent::SequenceStart => {
+                    return Err(Error::YamlDeserialize(None, marker));
+                }
+                YamlEvent::MappingEnd => {
+                    break;
+                }
+                YamlEvent::SequenceEnd => {
+                    unreachable!("shouldn't see sequence end");
+                }
+                YamlEvent::Scalar(s, ..) => match s.as_str() {
+                    "comment" => {
+                        let c = from_yaml_char_u8(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        comment = Some(c);
+                    }
+                    "delimiter" => {
+                        let a = from_yaml_char_u8(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        delimiter = Some(a);
+                    }
+                    "double_quote" => {
+                        let (b, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        double_quote = Some(b);
+                    }
+                    "escape" => {
+                        let f = from_yaml_char_u8(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        escape = Some(f);
+                    }
+                    "headers" => {
+                        let (p, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        headers = Some(p);
+                    }
+                    "terminator" => {
+                        let r = from_yaml_char_u8(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        terminator = Some(r);
+                    }
+                    "quote" => {
+                        let r = from_yaml_char_u8(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        quote = Some(r);
+                    }
+                    _ => return Err(Error::UnrecognizedKey(s, None, marker)),
+                },
+            }
+        }
+        let marker = first_marker.expect("should have a marker");
+        let headers = headers.unwrap_or_default();
+        let ret = Self {
+            comment,
+            delimiter,
+            double_quote,
+            escape,
+            headers,
+            terminator,
+            quote,
+        };
+        Ok((ret, marker))
+    }
+}
+
+#[cfg_attr(debug_assertions, derive(PartialEq))]
+#[derive(Debug)]
+struct FileProviderPreProcessed {
+    csv: CsvSettings,
+    auto_return: Option<EndpointProvidesSendOptions>,
+    // range 1-65535
+    buffer: Limit,
+    format: FileFormat,
+    partition: Option<PartitionPreProcessed>,
+    path: PreTemplate,
+    random: bool,
+    repeat: bool,
+    shuffle: bool,
+    unique: bool,
+}
+
+#[cfg_attr(debug_assertions, derive(PartialEq))]
+#[derive(Debug)]
+struct PartitionPreProcessed {
+    count: PreTemplate,
+    index: PreTemplate,
+}
+
+impl PartitionPreProcessed {
+    fn evaluate(&self, static_vars: &BTreeMap<String, json::Value>) -> Result<Partition, Error> {
+        let marker = self.count.0.marker;
+        let count = self
+            .count
+            .evaluate(static_vars, &mut RequiredProviders::new())?;
+        let count = count.parse().map_err(|_| {
+            Error::InvalidPartition(
+                format!("`count` must be at least 1, got `{}`", count),
+                marker,
+            )
+        })?;
+        let index = self
+            .index
+            .evaluate(static_vars, &mut RequiredProviders::new())?;
+        let index = index
+            .parse()
+            .ok()
+            .filter(|i| *i < NonZeroUsize::get(count))
+            .ok_or_else(|| {
+                let e = format!(
+                    "`index` must be from 0 to {}, got `{}`",
+                    count.get() - 1,
+                    index
+                );
+                Error::InvalidPartition(e, self.index.0.marker)
+            })?;
+        Ok(Partition { count, index })
+    }
+}
+
+impl FromYaml for PartitionPreProcessed {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let mut count = None;
+        let mut index = None;
+
+        let mut first_marker = None;
+        let mut saw_opening = false;
+        loop {
+            let (event, marker) = decoder.next()?;
+            if first_marker.is_none() {
+                first_marker = Some(marker);
+            }
+            match event {
+                YamlEvent::MappingStart => {
+                    if saw_opening {
+                        return Err(Error::YamlDeserialize(None, marker));
+                    } else {
+                        saw_opening = true;
+                    }
+                }
+                YamlEvent::SequenceStart => {
+                    return Err(Error::YamlDeserialize(None, marker));
+                }
+                YamlEvent::MappingEnd => {
+                    break;
+                }
+                YamlEvent::SequenceEnd => {
+                    unreachable!("shouldn't see sequence end");
+                }
+                YamlEvent::Scalar(s, ..) => match s.as_str() {
+                    "count" => {
+                        let (c, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        count = Some(c);
+                    }
+                    "index" => {
+                        let (i, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        index = Some(i);
+                    }
+                    _ => return Err(Error::UnrecognizedKey(s, None, marker)),
+                },
+            }
+        }
+        let marker = first_marker.expect("should have a marker");
+        let count = count.ok_or(Error::MissingYamlField("count", marker))?;
+        let index = index.ok_or(Error::MissingYamlField("index", marker))?;
+        Ok((Self { count, index }, marker))
+    }
+}
+
+impl FromYaml for FileProviderPreProcessed {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let mut csv = None;
+        let mut auto_return = None;
+        let mut buffer = None;
+        let mut format = None;
+        let mut partition = None;
+        let mut path = None;
+        let mut random = false;
+        let mut repeat = false;
+        let mut shuffle = false;
+        let mut unique = false;
+
+        let mut first_marker = None;
+        let mut saw_opening = false;
+        loop {
+            let (event, marker) = decoder.next()?;
+            if first_marker.is_none() {
+                first_marker = Some(marker);
+            }
+            match event {
+                YamlEvent::MappingStart => {
+                    if saw_opening {
+                        return Err(Error::YamlDeserialize(None, marker));
+                    } else {
+                        saw_opening = true;
+                    }
+                }
+                YamlEvent::SequenceStart => {
+                    return Err(Error::YamlDeserialize(None, marker));
+                }
+                YamlEvent::MappingEnd => {
+                    break;
+                }
+                YamlEvent::SequenceEnd => {
+                    unreachable!("shouldn't see sequence end");
+                }
+                YamlEvent::Scalar(s, ..) => match s.as_str() {
+                    "csv" => {
+                        let (c, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        csv = Some(c);
+                    }
+                    "auto_return" => {
+                        let (a, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        auto_return = Some(a);
+                    }
+                    "buffer" => {
+                        let (b, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        buffer = Some(b);
+                    }
+                    "format" => {
+                        let (f, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        format = Some(f);
+                    }
+                    "partition" => {
+                        let (p, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        partition = Some(p);
+                    }
+                    "path" => {
+                        let (s, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        let p = PreTemplate::new(s);
+                        path = Some(p);
+                    }
+                    "random" => {
+                        let (r, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        random = r;
+                    }
+                    "repeat" => {
+                        let (r, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        repeat = r;
+                    }
+                    "shuffle" => {
+                        let (r, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        shuffle = r;
+                    }
+                    "unique" => {
+                        let (u, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        unique = u;
+                    }
+                    _ => return Err(Error::UnrecognizedKey(s, None, marker)),
+                },
+            }
+        }
+        let marker = first_marker.expect("should have a marker");
+        let csv = csv.unwrap_or_default();
+        let buffer = buffer.unwrap_or_default();
+        let format = format.unwrap_or_default();
+        let path = path.ok_or(Error::MissingYamlField("path", marker))?;
+        let ret = Self {
+            csv,
+            auto_return,
+            buffer,
+            format,
+            partition,
+            path,
+            random,
+            repeat,
+            shuffle,
+            unique,
+        };
+        Ok((ret, marker))
+    }
+}
+
+#[cfg_attr(debug_assertions, derive(PartialEq))]
+#[derive(Debug)]
+struct SqliteProviderPreProcessed {
+    auto_return: Option<EndpointProvidesSendOptions>,
+    // range 1-65535
+    buffer: Limit,
+    path: PreTemplate,
+    query: String,
+    random: bool,
     repeat: bool,
+    shuffle: bool,
     unique: bool,
 }
 
-impl FromYaml for RangeProviderPreProcessed {
+impl FromYaml for SqliteProviderPreProcessed {
     fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
-        let mut saw_opening = false;
-
-        let mut start = 0;
-        let mut end = std::i64::MAX;
-        let mut step = NonZeroU16::new(1).expect("1 is non-zero");
+        let mut auto_return = None;
+        let mut buffer = None;
+        let mut path = None;
+        let mut query = None;
+        let mut random = false;
         let mut repeat = false;
+        let mut shuffle = false;
         let mut unique = false;
 
         let mut first_marker = None;
+        let mut saw_opening = false;
         loop {
             let (event, marker) = decoder.next()?;
             if first_marker.is_none() {
@@ -628,26 +1848,42 @@ impl FromYaml for RangeProviderPreProcessed {
                     unreachable!("shouldn't see sequence end");
                 }
                 YamlEvent::Scalar(s, ..) => match s.as_str() {
-                    "start" => {
-                        let (s, _) =
+                    "auto_return" => {
+                        let (a, _) =
                             FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        start = s;
+                        auto_return = Some(a);
                     }
-                    "end" => {
-                        let (e, _) =
+                    "buffer" => {
+                        let (b, _) =
                             FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        end = e;
+                        buffer = Some(b);
                     }
-                    "step" => {
+                    "path" => {
                         let (s, _) =
                             FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        step = s;
+                        let p = PreTemplate::new(s);
+                        path = Some(p);
+                    }
+                    "query" => {
+                        let (q, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        query = Some(q);
+                    }
+                    "random" => {
+                        let (r, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        random = r;
                     }
                     "repeat" => {
                         let (r, _) =
                             FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
                         repeat = r;
                     }
+                    "shuffle" => {
+                        let (r, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        shuffle = r;
+                    }
                     "unique" => {
                         let (u, _) =
                             FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
@@ -658,100 +1894,155 @@ impl FromYaml for RangeProviderPreProcessed {
             }
         }
         let marker = first_marker.expect("should have a marker");
+        let buffer = buffer.unwrap_or_default();
+        let path = path.ok_or(Error::MissingYamlField("path", marker))?;
+        let query = query.ok_or(Error::MissingYamlField("query", marker))?;
         let ret = Self {
-            start,
-            end,
-            step,
+            auto_return,
+            buffer,
+            path,
+            query,
+            random,
             repeat,
+            shuffle,
             unique,
         };
         Ok((ret, marker))
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
-pub enum FileFormat {
-    Csv,
-    Json,
-    Line,
+#[cfg_attr(debug_assertions, derive(PartialEq))]
+#[derive(Debug)]
+pub struct ResponseProviderPreProcessed {
+    auto_return: Option<EndpointProvidesSendOptions>,
+    buffer: Limit,
+    persist: Option<PreTemplate>,
+    ttl: Option<PreDuration>,
+    unique: bool,
 }
 
-impl FromYaml for FileFormat {
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResponseProvider {
+    pub auto_return: Option<EndpointProvidesSendOptions>,
+    pub buffer: Limit,
+    pub persist: Option<String>,
+    pub ttl: Option<Duration>,
+    pub unique: bool,
+}
+
+impl FromYaml for ResponseProviderPreProcessed {
     fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
-        let (event, marker) = decoder.next()?;
-        let format = match event.as_str() {
-            Some("csv") => FileFormat::Csv,
-            Some("json") => FileFormat::Json,
-            Some("line") => FileFormat::Line,
-            _ => return Err(Error::YamlDeserialize(None, marker)),
+        let mut auto_return = None;
+        let mut buffer = None;
+        let mut persist = None;
+        let mut ttl = None;
+        let mut unique = false;
+
+        let mut first_marker = None;
+        let mut saw_opening = false;
+        loop {
+            let (event, marker) = decoder.next()?;
+            if first_marker.is_none() {
+                first_marker = Some(marker);
+            }
+            match event {
+                YamlEvent::MappingStart => {
+                    if saw_opening {
+                        return Err(Error::YamlDeserialize(None, marker));
+                    } else {
+                        saw_opening = true;
+                    }
+                }
+                YamlEvent::SequenceStart => {
+                    return Err(Error::YamlDeserialize(None, marker));
+                }
+                YamlEvent::MappingEnd => {
+                    break;
+                }
+                YamlEvent::SequenceEnd => {
+                    unreachable!("shouldn't see sequence end");
+                }
+                YamlEvent::Scalar(s, ..) => match s.as_str() {
+                    "auto_return" => {
+                        let c =
+                            FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        auto_return = Some(c);
+                    }
+                    "buffer" => {
+                        let a =
+                            FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        buffer = Some(a);
+                    }
+                    "persist" => {
+                        let (s, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        persist = Some(PreTemplate::new(s));
+                    }
+                    "ttl" => {
+                        let (t, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        ttl = Some(t);
+                    }
+                    "unique" => {
+                        let u =
+                            FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        unique = u;
+                    }
+                    _ => return Err(Error::UnrecognizedKey(s, None, marker)),
+                },
+            }
+        }
+        let marker = first_marker.expect("should have a marker");
+        let buffer = buffer.unwrap_or_default();
+        let ret = Self {
+            auto_return,
+            buffer,
+            persist,
+            ttl,
+            unique,
         };
-        Ok((format, marker))
+        Ok((ret, marker))
     }
 }
 
-impl Default for FileFormat {
-    fn default() -> Self {
-        FileFormat::Line
-    }
+// what a `lookup` does when the key is not in the map provider
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum MapProviderMissing {
+    #[default]
+    Block,
+    Error,
 }
 
-#[derive(Clone, Debug, PartialEq)]
-pub enum CsvHeader {
-    Bool(bool),
-    String(String),
+impl MapProviderMissing {
+    pub fn is_block(self) -> bool {
+        matches!(self, MapProviderMissing::Block)
+    }
 }
 
-impl FromYaml for CsvHeader {
+impl FromYaml for MapProviderMissing {
     fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
         let (event, marker) = decoder.next()?;
-        match event.as_bool() {
-            Some(b) => Ok((CsvHeader::Bool(b), marker)),
-            None => event
-                .into_string()
-                .map(|s| (CsvHeader::String(s), marker))
-                .map_err(|_| Error::YamlDeserialize(None, marker)),
-        }
-    }
-}
-
-impl Default for CsvHeader {
-    fn default() -> Self {
-        CsvHeader::Bool(false)
-    }
-}
-
-fn from_yaml_char_u8<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> Result<u8, Error> {
-    let (event, marker) = decoder.next()?;
-    match event.as_x::<char>() {
-        Some(c) if c.is_ascii() => {
-            let mut b = [0; 1];
-            let _ = c.encode_utf8(&mut b);
-            Ok(b[0])
+        if let Ok(s) = event.into_string() {
+            let missing = match s.as_ref() {
+                "block" => MapProviderMissing::Block,
+                "error" => MapProviderMissing::Error,
+                _ => return Err(Error::YamlDeserialize(None, marker)),
+            };
+            Ok((missing, marker))
+        } else {
+            Err(Error::YamlDeserialize(None, marker))
         }
-        _ => Err(Error::YamlDeserialize(None, marker)),
     }
 }
 
 #[derive(Clone, Debug, Default, PartialEq)]
-pub struct CsvSettings {
-    pub comment: Option<u8>,
-    pub delimiter: Option<u8>,
-    pub double_quote: Option<bool>,
-    pub escape: Option<u8>,
-    pub headers: CsvHeader,
-    pub terminator: Option<u8>,
-    pub quote: Option<u8>,
+pub struct MapProvider {
+    pub missing: MapProviderMissing,
 }
 
-impl FromYaml for CsvSettings {
+impl FromYaml for MapProvider {
     fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
-        let mut comment = None;
-        let mut delimiter = None;
-        let mut double_quote = None;
-        let mut escape = None;
-        let mut headers = None;
-        let mut terminator = None;
-        let mut quote = None;
+        let mut missing = MapProviderMissing::default();
 
         let mut first_marker = None;
         let mut saw_opening = false;
@@ -778,79 +2069,116 @@ impl FromYaml for CsvSettings {
                     unreachable!("shouldn't see sequence end");
                 }
                 YamlEvent::Scalar(s, ..) => match s.as_str() {
-                    "comment" => {
-                        let c = from_yaml_char_u8(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        comment = Some(c);
-                    }
-                    "delimiter" => {
-                        let a = from_yaml_char_u8(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        delimiter = Some(a);
-                    }
-                    "double_quote" => {
-                        let (b, _) =
-                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        double_quote = Some(b);
-                    }
-                    "escape" => {
-                        let f = from_yaml_char_u8(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        escape = Some(f);
-                    }
-                    "headers" => {
-                        let (p, _) =
+                    "missing" => {
+                        let (m, _) =
                             FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        headers = Some(p);
-                    }
-                    "terminator" => {
-                        let r = from_yaml_char_u8(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        terminator = Some(r);
-                    }
-                    "quote" => {
-                        let r = from_yaml_char_u8(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        quote = Some(r);
+                        missing = m;
                     }
                     _ => return Err(Error::UnrecognizedKey(s, None, marker)),
                 },
             }
         }
         let marker = first_marker.expect("should have a marker");
-        let headers = headers.unwrap_or_default();
-        let ret = Self {
-            comment,
-            delimiter,
-            double_quote,
-            escape,
-            headers,
-            terminator,
-            quote,
-        };
-        Ok((ret, marker))
+        Ok((Self { missing }, marker))
     }
 }
 
 #[cfg_attr(debug_assertions, derive(PartialEq))]
 #[derive(Debug)]
-struct FileProviderPreProcessed {
-    csv: CsvSettings,
-    auto_return: Option<EndpointProvidesSendOptions>,
-    // range 1-65535
-    buffer: Limit,
-    format: FileFormat,
-    path: PreTemplate,
-    random: bool,
-    repeat: bool,
-    unique: bool,
+struct SequenceProviderPreProcessed {
+    start: Option<PreTemplate>,
+    end: i64,
+    step: NonZeroU16,
+    wrap: bool,
+    prefix: Option<String>,
+    suffix: Option<String>,
+    width: Option<usize>,
+    persist: Option<PreTemplate>,
+}
+
+impl SequenceProviderPreProcessed {
+    fn evaluate(
+        self,
+        static_vars: &BTreeMap<String, json::Value>,
+    ) -> Result<SequenceProvider, Error> {
+        let start = match self.start {
+            Some(start) => {
+                let marker = start.0.marker;
+                let start = start.evaluate(static_vars, &mut RequiredProviders::new())?;
+                let start = start.parse().map_err(|_| {
+                    let e = format!("`start` must be an integer, got `{}`", start);
+                    Error::InvalidSequence(e, marker)
+                })?;
+                if start > self.end {
+                    let e = format!("`start` must not be after `end`, got `{}`", start);
+                    return Err(Error::InvalidSequence(e, marker));
+                }
+                start
+            }
+            None => 0,
+        };
+        let persist = self
+            .persist
+            .map(|p| p.evaluate(static_vars, &mut RequiredProviders::new()))
+            .transpose()?;
+        Ok(SequenceProvider {
+            start,
+            end: self.end,
+            step: self.step.get(),
+            wrap: self.wrap,
+            prefix: self.prefix,
+            suffix: self.suffix,
+            width: self.width,
+            persist,
+        })
+    }
 }
 
-impl FromYaml for FileProviderPreProcessed {
+#[derive(Clone, Debug, PartialEq)]
+pub struct SequenceProvider {
+    pub start: i64,
+    pub end: i64,
+    pub step: u16,
+    pub wrap: bool,
+    pub prefix: Option<String>,
+    pub suffix: Option<String>,
+    pub width: Option<usize>,
+    pub persist: Option<String>,
+}
+
+impl SequenceProvider {
+    // the number which comes after `n`, or `None` when the sequence has ended
+    pub fn next(&self, n: i64) -> Option<i64> {
+        match n.checked_add(self.step.into()) {
+            Some(next) if next <= self.end => Some(next.max(self.start)),
+            _ if self.wrap => Some(self.start),
+            _ => None,
+        }
+    }
+
+    // a number is only turned into a string when some formatting is specified
+    pub fn format(&self, n: i64) -> json::Value {
+        if self.prefix.is_none() && self.suffix.is_none() && self.width.is_none() {
+            return n.into();
+        }
+        let prefix = self.prefix.as_deref().unwrap_or_default();
+        let suffix = self.suffix.as_deref().unwrap_or_default();
+        let width = self.width.unwrap_or_default();
+        format!("{}{:0width$}{}", prefix, n, suffix, width = width).into()
+    }
+}
+
+impl FromYaml for SequenceProviderPreProcessed {
     fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
-        let mut csv = None;
-        let mut auto_return = None;
-        let mut buffer = None;
-        let mut format = None;
-        let mut path = None;
-        let mut random = false;
-        let mut repeat = false;
-        let mut unique = false;
+        let mut start = None;
+        let mut end = i64::MAX;
+        let mut step = NonZeroU16::new(1).expect("1 is non-zero");
+        let mut wrap = false;
+        let mut prefix = None;
+        let mut suffix = None;
+        let mut width = None;
+        let mut persist = None;
+        let mut end_marker = None;
 
         let mut first_marker = None;
         let mut saw_opening = false;
@@ -877,81 +2205,137 @@ impl FromYaml for FileProviderPreProcessed {
                     unreachable!("shouldn't see sequence end");
                 }
                 YamlEvent::Scalar(s, ..) => match s.as_str() {
-                    "csv" => {
-                        let (c, _) =
+                    "start" => {
+                        let (s, _) =
                             FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        csv = Some(c);
+                        start = Some(PreTemplate::new(s));
                     }
-                    "auto_return" => {
-                        let (a, _) =
+                    "end" => {
+                        let (e, m) =
                             FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        auto_return = Some(a);
+                        end = e;
+                        end_marker = Some(m);
                     }
-                    "buffer" => {
-                        let (b, _) =
+                    "step" => {
+                        let (s, _) =
                             FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        buffer = Some(b);
+                        step = s;
                     }
-                    "format" => {
-                        let (f, _) =
+                    "wrap" => {
+                        let (w, _) =
                             FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        format = Some(f);
+                        wrap = w;
                     }
-                    "path" => {
-                        let (s, _) =
+                    "prefix" => {
+                        let (p, _) =
                             FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        let p = PreTemplate::new(s);
-                        path = Some(p);
+                        prefix = Some(p);
                     }
-                    "random" => {
-                        let (r, _) =
+                    "suffix" => {
+                        let (s, _) =
                             FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        random = r;
+                        suffix = Some(s);
                     }
-                    "repeat" => {
-                        let (r, _) =
+                    "width" => {
+                        let (w, _) =
                             FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        repeat = r;
+                        width = Some(w);
                     }
-                    "unique" => {
-                        let (u, _) =
+                    "persist" => {
+                        let (s, _) =
                             FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        unique = u;
+                        persist = Some(PreTemplate::new(s));
                     }
                     _ => return Err(Error::UnrecognizedKey(s, None, marker)),
                 },
             }
         }
         let marker = first_marker.expect("should have a marker");
-        let csv = csv.unwrap_or_default();
-        let buffer = buffer.unwrap_or_default();
-        let format = format.unwrap_or_default();
-        let path = path.ok_or(Error::MissingYamlField("path", marker))?;
+        // a given `start` is checked against `end` once its template is evaluated
+        if let (None, Some(end_marker)) = (&start, end_marker) {
+            if end < 0 {
+                let e = format!(
+                    "`end` must not be before the default `start` of 0, got `{}`",
+                    end
+                );
+                return Err(Error::InvalidSequence(e, end_marker));
+            }
+        }
         let ret = Self {
-            csv,
-            auto_return,
-            buffer,
-            format,
-            path,
-            random,
-            repeat,
-            unique,
+            start,
+            end,
+            step,
+            wrap,
+            prefix,
+            suffix,
+            width,
+            persist,
         };
         Ok((ret, marker))
     }
 }
 
+#[cfg_attr(debug_assertions, derive(PartialEq))]
+#[derive(Debug)]
+struct FilesProviderPreProcessed {
+    auto_return: Option<EndpointProvidesSendOptions>,
+    buffer: Limit,
+    glob: FileGlob,
+    path: PreTemplate,
+    recursive: bool,
+    repeat: bool,
+    shuffle: bool,
+    unique: bool,
+}
+
+// a pattern which the paths of a files provider's files must match, where the path is relative to
+// the provider's directory and uses `/` between directories
 #[derive(Clone, Debug, PartialEq)]
-pub struct ResponseProvider {
+pub struct FileGlob(glob::Pattern);
+
+impl FileGlob {
+    pub fn new(glob: &str) -> Result<Self, String> {
+        glob::Pattern::new(glob)
+            .map(FileGlob)
+            .map_err(|e| e.msg.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        self.0.matches(path)
+    }
+}
+
+impl Default for FileGlob {
+    fn default() -> Self {
+        FileGlob::new("*").expect("`*` is a valid glob")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilesProvider {
     pub auto_return: Option<EndpointProvidesSendOptions>,
     pub buffer: Limit,
+    pub glob: FileGlob,
+    pub path: String,
+    pub recursive: bool,
+    pub repeat: bool,
+    pub shuffle: bool,
     pub unique: bool,
 }
 
-impl FromYaml for ResponseProvider {
+impl FromYaml for FilesProviderPreProcessed {
     fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
         let mut auto_return = None;
         let mut buffer = None;
+        let mut glob = None;
+        let mut path = None;
+        let mut recursive = false;
+        let mut repeat = false;
+        let mut shuffle = false;
         let mut unique = false;
 
         let mut first_marker = None;
@@ -989,9 +2373,36 @@ impl FromYaml for ResponseProvider {
                             FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
                         buffer = Some(a);
                     }
+                    "glob" => {
+                        let (g, marker): (String, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        let g = FileGlob::new(&g)
+                            .map_err(|e| Error::InvalidGlob(format!("`{}`. {}", g, e), marker))?;
+                        glob = Some(g);
+                    }
+                    "path" => {
+                        let (p, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        path = Some(PreTemplate::new(p));
+                    }
+                    "recursive" => {
+                        let (r, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        recursive = r;
+                    }
+                    "repeat" => {
+                        let (r, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        repeat = r;
+                    }
+                    "shuffle" => {
+                        let (r, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        shuffle = r;
+                    }
                     "unique" => {
-                        let u =
-                            FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        let (u, _) =
+                            FromYaml::parse(decoder).map_err(map_yaml_deserialize_err(s))?;
                         unique = u;
                     }
                     _ => return Err(Error::UnrecognizedKey(s, None, marker)),
@@ -1000,9 +2411,16 @@ impl FromYaml for ResponseProvider {
         }
         let marker = first_marker.expect("should have a marker");
         let buffer = buffer.unwrap_or_default();
+        let glob = glob.unwrap_or_default();
+        let path = path.ok_or(Error::MissingYamlField("path", marker))?;
         let ret = Self {
             auto_return,
             buffer,
+            glob,
+            path,
+            recursive,
+            repeat,
+            shuffle,
             unique,
         };
         Ok((ret, marker))
@@ -1207,6 +2625,7 @@ impl FromYaml for LogsPreProcessed {
 
 #[derive(Debug)]
 struct EndpointPreProcessed {
+    arrival: Option<Arrival>,
     declare: BTreeMap<String, PreValueOrExpression>,
     headers: TupleVec<String, Nullable<PreTemplate>>,
     body: Option<Body>,
@@ -1220,14 +2639,18 @@ struct EndpointPreProcessed {
     logs: TupleVec<String, LogsPreProcessed>,
     max_parallel_requests: Option<NonZeroUsize>,
     no_auto_returns: bool,
+    rate_limit: Option<String>,
     request_timeout: Option<PreDuration>,
+    think_time: Option<PreThinkTime>,
+    warmup: Option<PreDuration>,
     marker: Marker,
 }
 
 #[cfg(debug_assertions)]
 impl PartialEq for EndpointPreProcessed {
     fn eq(&self, other: &Self) -> bool {
-        self.declare == other.declare
+        self.arrival == other.arrival
+            && self.declare == other.declare
             && self.headers == other.headers
             && self.body == other.body
             && self.load_pattern == other.load_pattern
@@ -1240,12 +2663,16 @@ impl PartialEq for EndpointPreProcessed {
             && self.logs == other.logs
             && self.max_parallel_requests == other.max_parallel_requests
             && self.no_auto_returns == other.no_auto_returns
+            && self.rate_limit == other.rate_limit
             && self.request_timeout == other.request_timeout
+            && self.think_time == other.think_time
+            && self.warmup == other.warmup
     }
 }
 
 impl FromYaml for EndpointPreProcessed {
     fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let mut arrival = None;
         let mut declare = None;
         let mut headers = None;
         let mut body = None;
@@ -1259,7 +2686,10 @@ impl FromYaml for EndpointPreProcessed {
         let mut logs = None;
         let mut max_parallel_requests = None;
         let mut no_auto_returns = None;
+        let mut rate_limit = None;
         let mut request_timeout = None;
+        let mut think_time = None;
+        let mut warmup = None;
 
         let mut first_marker = None;
         let mut saw_opening = false;
@@ -1286,6 +2716,12 @@ impl FromYaml for EndpointPreProcessed {
                     unreachable!("shouldn't see sequence end");
                 }
                 YamlEvent::Scalar(s, ..) => match s.as_str() {
+                    "arrival" => {
+                        let a =
+                            FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("EndpointPreProcessed.parse arrival: {:?}", a);
+                        arrival = Some(a);
+                    }
                     "declare" => {
                         let c =
                             FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
@@ -1365,12 +2801,30 @@ impl FromYaml for EndpointPreProcessed {
                         log::debug!("EndpointPreProcessed.parse no_auto_returns: {:?}", a);
                         no_auto_returns = Some(a);
                     }
+                    "rate_limit" => {
+                        let a =
+                            FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("EndpointPreProcessed.parse rate_limit: {:?}", a);
+                        rate_limit = Some(a);
+                    }
                     "request_timeout" => {
                         let a =
                             FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
                         log::debug!("EndpointPreProcessed.parse request_timeout: {:?}", a);
                         request_timeout = Some(a);
                     }
+                    "think_time" => {
+                        let a =
+                            FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("EndpointPreProcessed.parse think_time: {:?}", a);
+                        think_time = Some(a);
+                    }
+                    "warmup" => {
+                        let a =
+                            FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("EndpointPreProcessed.parse warmup: {:?}", a);
+                        warmup = Some(a);
+                    }
                     _ => return Err(Error::UnrecognizedKey(s, None, marker)),
                 },
             }
@@ -1386,6 +2840,7 @@ impl FromYaml for EndpointPreProcessed {
         let logs = logs.unwrap_or_default();
         let no_auto_returns = no_auto_returns.unwrap_or_default();
         let ret = Self {
+            arrival,
             declare,
             headers,
             body,
@@ -1399,13 +2854,102 @@ impl FromYaml for EndpointPreProcessed {
             logs,
             max_parallel_requests,
             no_auto_returns,
+            rate_limit,
             request_timeout,
+            think_time,
+            warmup,
             marker,
         };
         Ok((ret, marker))
     }
 }
 
+#[cfg_attr(debug_assertions, derive(PartialEq))]
+#[derive(Debug)]
+enum PreThinkTime {
+    Fixed(PreTemplate),
+    Random { min: PreTemplate, max: PreTemplate },
+}
+
+impl PreThinkTime {
+    fn evaluate(
+        self,
+        static_vars: &BTreeMap<String, json::Value>,
+        required_providers: &mut RequiredProviders,
+    ) -> Result<ThinkTime, Error> {
+        let mut as_template = |t: PreTemplate| {
+            let marker = t.0.marker;
+            let template = t.as_template(static_vars, required_providers)?;
+            // catch invalid durations now when they don't depend on providers
+            if template.is_simple() {
+                let dur = template.evaluate(Cow::Owned(json::Value::Null), None)?;
+                think_time_from_string2(dur, marker)?;
+            }
+            Ok::<_, Error>(template)
+        };
+        let think_time = match self {
+            PreThinkTime::Fixed(t) => ThinkTime::Fixed(as_template(t)?),
+            PreThinkTime::Random { min, max } => {
+                ThinkTime::Random(as_template(min)?, as_template(max)?)
+            }
+        };
+        Ok(think_time)
+    }
+}
+
+impl FromYaml for PreThinkTime {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let (event, marker) = decoder.peek()?;
+        if let YamlEvent::Scalar(..) = event {
+            let (t, marker) = FromYaml::parse(decoder)?;
+            return Ok((PreThinkTime::Fixed(t), marker));
+        }
+        let marker = *marker;
+        let mut min = None;
+        let mut max = None;
+        let mut saw_opening = false;
+        loop {
+            let (event, marker) = decoder.next()?;
+            match event {
+                YamlEvent::MappingStart => {
+                    if saw_opening {
+                        return Err(Error::YamlDeserialize(None, marker));
+                    } else {
+                        saw_opening = true;
+                    }
+                }
+                YamlEvent::SequenceStart => {
+                    return Err(Error::YamlDeserialize(None, marker));
+                }
+                YamlEvent::MappingEnd => {
+                    break;
+                }
+                YamlEvent::SequenceEnd => {
+                    unreachable!("shouldn't see sequence end");
+                }
+                YamlEvent::Scalar(s, ..) => match s.as_str() {
+                    "min" => {
+                        let v =
+                            FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("PreThinkTime.parse min: {:?}", v);
+                        min = Some(v);
+                    }
+                    "max" => {
+                        let v =
+                            FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("PreThinkTime.parse max: {:?}", v);
+                        max = Some(v);
+                    }
+                    _ => return Err(Error::UnrecognizedKey(s, Some("think_time".into()), marker)),
+                },
+            }
+        }
+        let min = min.ok_or(Error::MissingYamlField("min", marker))?;
+        let max = max.ok_or(Error::MissingYamlField("max", marker))?;
+        Ok((PreThinkTime::Random { min, max }, marker))
+    }
+}
+
 #[cfg_attr(debug_assertions, derive(PartialEq))]
 #[derive(Debug)]
 enum Body {
@@ -1659,20 +3203,114 @@ impl FromYaml for EndpointProvidesPreProcessed {
                     "where" => {
                         let v =
                             FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
-                        where_clause = Some(v);
+                        where_clause = Some(v);
+                    }
+                    _ => return Err(Error::UnrecognizedKey(s, None, marker)),
+                },
+            }
+        }
+        let marker = first_marker.expect("should have a marker");
+        let select = select.ok_or(Error::MissingYamlField("select", marker))?;
+        let for_each = for_each.unwrap_or_default();
+        let ret = Self {
+            for_each,
+            select,
+            send,
+            where_clause,
+        };
+        Ok((ret, marker))
+    }
+}
+
+#[derive(Debug)]
+struct ScenarioPreProcessed {
+    load_pattern: Option<PreLoadPattern>,
+    name: String,
+    peak_users: usize,
+    steps: Vec<EndpointPreProcessed>,
+    marker: Marker,
+}
+
+#[cfg(debug_assertions)]
+impl PartialEq for ScenarioPreProcessed {
+    fn eq(&self, other: &Self) -> bool {
+        self.load_pattern == other.load_pattern
+            && self.name == other.name
+            && self.peak_users == other.peak_users
+            && self.steps == other.steps
+    }
+}
+
+impl FromYaml for ScenarioPreProcessed {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let mut load_pattern = None;
+        let mut name = None;
+        let mut peak_users = None;
+        let mut steps = None;
+
+        let mut first_marker = None;
+        let mut saw_opening = false;
+        loop {
+            let (event, marker) = decoder.next()?;
+            if first_marker.is_none() {
+                first_marker = Some(marker);
+            }
+            match event {
+                YamlEvent::MappingStart => {
+                    if saw_opening {
+                        return Err(Error::YamlDeserialize(None, marker));
+                    } else {
+                        saw_opening = true;
+                    }
+                }
+                YamlEvent::SequenceStart => {
+                    return Err(Error::YamlDeserialize(None, marker));
+                }
+                YamlEvent::MappingEnd => {
+                    break;
+                }
+                YamlEvent::SequenceEnd => {
+                    unreachable!("shouldn't see sequence end");
+                }
+                YamlEvent::Scalar(s, ..) => match s.as_str() {
+                    "load_pattern" => {
+                        let a =
+                            FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("ScenarioPreProcessed.parse load_pattern: {:?}", a);
+                        load_pattern = Some(a);
+                    }
+                    "name" => {
+                        let a =
+                            FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("ScenarioPreProcessed.parse name: {:?}", a);
+                        name = Some(a);
+                    }
+                    "peak_users" => {
+                        let a =
+                            FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("ScenarioPreProcessed.parse peak_users: {:?}", a);
+                        peak_users = Some(a);
+                    }
+                    "steps" => {
+                        let a =
+                            FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("ScenarioPreProcessed.parse steps: {:?}", a);
+                        steps = Some(a);
                     }
-                    _ => return Err(Error::UnrecognizedKey(s, None, marker)),
+                    _ => return Err(Error::UnrecognizedKey(s, Some("scenario".into()), marker)),
                 },
             }
         }
         let marker = first_marker.expect("should have a marker");
-        let select = select.ok_or(Error::MissingYamlField("select", marker))?;
-        let for_each = for_each.unwrap_or_default();
+        let name = name.ok_or(Error::MissingYamlField("name", marker))?;
+        let peak_users = peak_users.ok_or(Error::MissingYamlField("peak_users", marker))?;
+        let steps = steps.ok_or(Error::MissingYamlField("steps", marker))?;
         let ret = Self {
-            for_each,
-            select,
-            send,
-            where_clause,
+            load_pattern,
+            name,
+            peak_users,
+            steps,
+            marker,
         };
         Ok((ret, marker))
     }
@@ -1785,9 +3423,13 @@ impl DefaultWithMarker for ClientConfigPreProcessed {
 }
 
 pub struct GeneralConfig {
+    pub arrival: Arrival,
+    pub arrival_seed: Option<u64>,
     pub auto_buffer_start_size: usize,
     pub bucket_size: Duration,
     pub log_provider_stats: bool,
+    pub rate_limits: BTreeMap<String, HitsPer>,
+    pub warmup: Option<Duration>,
     pub watch_transition_time: Option<Duration>,
     pub log_level: Option<LevelFilter>,
 }
@@ -1795,9 +3437,13 @@ pub struct GeneralConfig {
 #[cfg_attr(debug_assertions, derive(PartialEq))]
 #[derive(Debug)]
 struct GeneralConfigPreProcessed {
+    arrival: Arrival,
+    arrival_seed: Option<u64>,
     auto_buffer_start_size: usize,
     bucket_size: PreDuration,
     log_provider_stats: bool,
+    rate_limits: BTreeMap<String, PreHitsPer>,
+    warmup: Option<PreDuration>,
     watch_transition_time: Option<PreDuration>,
     pub log_level: Option<LevelFilter>,
 }
@@ -1805,9 +3451,13 @@ struct GeneralConfigPreProcessed {
 impl DefaultWithMarker for GeneralConfigPreProcessed {
     fn default(marker: Marker) -> Self {
         GeneralConfigPreProcessed {
+            arrival: Default::default(),
+            arrival_seed: None,
             auto_buffer_start_size: default_auto_buffer_start_size(),
             bucket_size: default_bucket_size(marker),
             log_provider_stats: default_log_provider_stats(),
+            rate_limits: Default::default(),
+            warmup: None,
             watch_transition_time: None,
             log_level: None,
         }
@@ -1816,9 +3466,13 @@ impl DefaultWithMarker for GeneralConfigPreProcessed {
 
 impl FromYaml for GeneralConfigPreProcessed {
     fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let mut arrival = Default::default();
+        let mut arrival_seed = None;
         let mut auto_buffer_start_size = default_auto_buffer_start_size();
         let mut bucket_size = None;
         let mut log_provider_stats = default_log_provider_stats();
+        let mut rate_limits = Default::default();
+        let mut warmup = None;
         let mut watch_transition_time = None;
         let mut log_level = None;
 
@@ -1848,6 +3502,16 @@ impl FromYaml for GeneralConfigPreProcessed {
                 }
                 YamlEvent::Scalar(s, ..) => {
                     match s.as_str() {
+                        "arrival" => {
+                            let a = FromYaml::parse_into(decoder)
+                                .map_err(map_yaml_deserialize_err(s))?;
+                            arrival = a;
+                        }
+                        "arrival_seed" => {
+                            let a = FromYaml::parse_into(decoder)
+                                .map_err(map_yaml_deserialize_err(s))?;
+                            arrival_seed = Some(a);
+                        }
                         "auto_buffer_start_size" => {
                             let c = FromYaml::parse_into(decoder)
                                 .map_err(map_yaml_deserialize_err(s))?;
@@ -1882,6 +3546,18 @@ impl FromYaml for GeneralConfigPreProcessed {
                                 }
                             };
                         }
+                        "rate_limits" => {
+                            let a = FromYaml::parse_into(decoder)
+                                .map_err(map_yaml_deserialize_err(s))?;
+                            debug!("rate_limits: {:?}", a);
+                            rate_limits = a;
+                        }
+                        "warmup" => {
+                            let a = FromYaml::parse_into(decoder)
+                                .map_err(map_yaml_deserialize_err(s))?;
+                            debug!("warmup: {:?}", a);
+                            warmup = Some(a);
+                        }
                         "watch_transition_time" => {
                             let b = FromYaml::parse_into(decoder)
                                 .map_err(map_yaml_deserialize_err(s))?;
@@ -1906,9 +3582,13 @@ impl FromYaml for GeneralConfigPreProcessed {
         let marker = first_marker.expect("should have a marker");
         let bucket_size = bucket_size.unwrap_or_else(|| default_bucket_size(marker));
         let ret = Self {
+            arrival,
+            arrival_seed,
             auto_buffer_start_size,
             bucket_size,
             log_provider_stats,
+            rate_limits,
+            warmup,
             watch_transition_time,
             log_level,
         };
@@ -1994,6 +3674,7 @@ struct LoadTestPreProcessed {
     load_pattern: Option<PreLoadPattern>,
     providers: BTreeMap<String, ProviderPreProcessed>,
     loggers: BTreeMap<String, LoggerPreProcessed>,
+    scenarios: Vec<ScenarioPreProcessed>,
     vars: BTreeMap<String, PreVar>,
 }
 
@@ -2005,6 +3686,7 @@ impl FromYaml for LoadTestPreProcessed {
         let mut load_pattern = None;
         let mut providers = None;
         let mut loggers = None;
+        let mut scenarios = None;
         let mut vars = None;
         let mut first_marker = None;
         let mut saw_opening = false;
@@ -2061,6 +3743,12 @@ impl FromYaml for LoadTestPreProcessed {
                         log::debug!("LoadTestPreProcessed.parse loggers: {:?}", v);
                         loggers = Some(v);
                     }
+                    "scenarios" => {
+                        let v =
+                            FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
+                        log::debug!("LoadTestPreProcessed.parse scenarios: {:?}", v);
+                        scenarios = Some(v);
+                    }
                     "vars" => {
                         let v =
                             FromYaml::parse_into(decoder).map_err(map_yaml_deserialize_err(s))?;
@@ -2073,7 +3761,13 @@ impl FromYaml for LoadTestPreProcessed {
         }
         let marker = first_marker.expect("should have a marker");
         let config = config.unwrap_or_else(|| DefaultWithMarker::default(marker));
-        let endpoints = endpoints.ok_or(Error::MissingYamlField("endpoints", marker))?;
+        // a test needs endpoints, scenarios or both
+        let scenarios: Vec<_> = scenarios.unwrap_or_default();
+        let endpoints = match endpoints {
+            Some(e) => e,
+            None if !scenarios.is_empty() => Vec::new(),
+            None => return Err(Error::MissingYamlField("endpoints", marker)),
+        };
         let providers = providers.unwrap_or_default();
         let loggers = loggers.unwrap_or_default();
         let vars = vars.unwrap_or_default();
@@ -2083,6 +3777,7 @@ impl FromYaml for LoadTestPreProcessed {
             load_pattern,
             providers,
             loggers,
+            scenarios,
             vars,
         };
         Ok((ret, marker))
@@ -2263,14 +3958,33 @@ pub fn duration_from_string(dur: String) -> Result<Duration, Error> {
     duration_from_string2(dur, marker)
 }
 
+// `think_time` also takes milliseconds, as pacing between requests is often sub-second. Other
+// durations stay in whole seconds
+pub fn think_time_from_string(dur: String) -> Result<Duration, Error> {
+    let marker = create_marker();
+    think_time_from_string2(dur, marker)
+}
+
 fn duration_from_string2(dur: String, marker: Marker) -> Result<Duration, Error> {
-    let base_re = r"(?i)(\d+)\s*(d|h|m|s|days?|hrs?|mins?|secs?|hours?|minutes?|seconds?)";
+    parse_duration(dur, marker, false)
+}
+
+fn think_time_from_string2(dur: String, marker: Marker) -> Result<Duration, Error> {
+    parse_duration(dur, marker, true)
+}
+
+fn parse_duration(dur: String, marker: Marker, allow_millis: bool) -> Result<Duration, Error> {
+    let base_re = if allow_millis {
+        r"(?i)(\d+)\s*(ms|millis(?:econds?)?|d|h|m|s|days?|hrs?|mins?|secs?|hours?|minutes?|seconds?)"
+    } else {
+        r"(?i)(\d+)\s*(d|h|m|s|days?|hrs?|mins?|secs?|hours?|minutes?|seconds?)"
+    };
     let sanity_re =
         Regex::new(&format!(r"^(?:{}\s*)+$", base_re)).expect("should be a valid regex");
     if !sanity_re.is_match(&dur) {
         return Err(Error::InvalidDuration(dur, marker));
     }
-    let mut total_secs = 0;
+    let mut total_millis = 0;
     let re = Regex::new(base_re).expect("should be a valid regex");
     for captures in re.captures_iter(&dur) {
         let n: u64 = captures
@@ -2279,7 +3993,14 @@ fn duration_from_string2(dur: String, marker: Marker) -> Result<Duration, Error>
             .as_str()
             .parse()
             .expect("should parse into u64 for duration");
-        let unit = &captures.get(2).expect("should have capture group").as_str()[0..1];
+        let unit = captures.get(2).expect("should have capture group").as_str();
+        let is_millis = unit.eq_ignore_ascii_case("ms")
+            || unit.len() > 4 && unit[0..5].eq_ignore_ascii_case("milli");
+        if is_millis {
+            total_millis += n;
+            continue;
+        }
+        let unit = &unit[0..1];
         let secs = if unit.eq_ignore_ascii_case("d") {
             n * 60 * 60 * 24 // days
         } else if unit.eq_ignore_ascii_case("h") {
@@ -2289,9 +4010,9 @@ fn duration_from_string2(dur: String, marker: Marker) -> Result<Duration, Error>
         } else {
             n // seconds
         };
-        total_secs += secs;
+        total_millis += secs * 1000;
     }
-    Ok(Duration::from_secs(total_secs))
+    Ok(Duration::from_millis(total_millis))
 }
 
 #[cfg_attr(debug_assertions, derive(PartialEq))]
@@ -2356,33 +4077,160 @@ impl PartialEq for PreLoadPattern {
 }
 
 impl PreLoadPattern {
-    fn evaluate(&self, static_vars: &BTreeMap<String, json::Value>) -> Result<LoadPattern, Error> {
+    // `read_traffic_file` gets the contents of a `from_file` traffic file
+    fn evaluate(
+        &self,
+        static_vars: &BTreeMap<String, json::Value>,
+        read_traffic_file: &dyn Fn(&str) -> io::Result<String>,
+    ) -> Result<LoadPattern, Error> {
         let mut builder: Option<LinearBuilder> = None;
+        let mut schedule = None;
+        // the time of day the next segment starts at, when there is a schedule
+        let mut clock = None;
         let mut last_end = 0f64;
-        for lppp in &self.0 {
-            match lppp {
-                LoadPatternPreProcessed::Linear(lbpp) => {
-                    let start = lbpp
-                        .from
-                        .as_ref()
-                        .map(|p| Ok::<_, Error>(p.evaluate(static_vars)? / 100f64))
-                        .unwrap_or_else(|| Ok(last_end))?;
+        let evaluate_from = |from: &Option<PrePercent>, last_end: f64| {
+            from.as_ref()
+                .map(|p| Ok::<_, Error>(p.evaluate(static_vars)? / 100f64))
+                .unwrap_or_else(|| Ok(last_end))
+        };
+        for (i, lppp) in self.0.iter().enumerate() {
+            let segments = match lppp {
+                // a schedule applies to the whole load_pattern so it must come first
+                LoadPatternPreProcessed::Schedule(sbpp) if i == 0 => {
+                    let s = sbpp.evaluate(static_vars)?;
+                    clock = Some(s.start);
+                    schedule = Some(s);
+                    continue;
+                }
+                LoadPatternPreProcessed::Schedule(_) => {
+                    return Err(Error::InvalidLoadPattern(self.1));
+                }
+                // each row of the file holds its rate until the next row
+                LoadPatternPreProcessed::FromFile(pre_template) => {
+                    let file = pre_template.evaluate(static_vars, &mut RequiredProviders::new())?;
+                    let invalid = |e: String| {
+                        Error::InvalidTrafficFile(file.clone(), e, pre_template.0.marker)
+                    };
+                    let contents = read_traffic_file(&file).map_err(|e| invalid(e.to_string()))?;
+                    parse_traffic_csv(&contents)
+                        .map_err(invalid)?
+                        .into_iter()
+                        .map(|(over, percent)| (percent, percent, (over, None), SegmentShape::Step))
+                        .collect()
+                }
+                LoadPatternPreProcessed::Linear(lbpp)
+                | LoadPatternPreProcessed::Exponential(lbpp) => {
+                    let start = evaluate_from(&lbpp.from, last_end)?;
                     let to = lbpp.to.evaluate(static_vars)?;
                     let end = to / 100f64;
-                    let over = lbpp.over.evaluate(static_vars)?;
-                    last_end = end;
-                    if let Some(ref mut lb) = builder {
-                        lb.append(start, end, over);
+                    let shape = if let LoadPatternPreProcessed::Linear(_) = lppp {
+                        SegmentShape::Linear
                     } else {
-                        builder = Some(LinearBuilder::new(start, end, over));
+                        SegmentShape::Exponential
+                    };
+                    let length = lbpp.length.evaluate(static_vars, clock, self.1)?;
+                    vec![(start, end, length, shape)]
+                }
+                LoadPatternPreProcessed::Step(sbpp) => {
+                    let end = sbpp.to.evaluate(static_vars)? / 100f64;
+                    let length = sbpp.length.evaluate(static_vars, clock, self.1)?;
+                    vec![(end, end, length, SegmentShape::Step)]
+                }
+                LoadPatternPreProcessed::Sine(sbpp) => {
+                    let start = evaluate_from(&sbpp.from, last_end)?;
+                    let end = sbpp.to.evaluate(static_vars)? / 100f64;
+                    let amplitude_percent = sbpp.amplitude.evaluate(static_vars)? / 100f64;
+                    let period = sbpp.period.evaluate(static_vars)?;
+                    if period == Duration::from_secs(0) {
+                        return Err(Error::InvalidLoadPattern(self.1));
+                    }
+                    let shape = SegmentShape::Sine {
+                        amplitude_percent,
+                        period,
+                    };
+                    let length = sbpp.length.evaluate(static_vars, clock, self.1)?;
+                    vec![(start, end, length, shape)]
+                }
+            };
+            for (start, end, (over, until), shape) in segments {
+                clock = clock
+                    .map(|c| (c + over).as_millis() % DAY.as_millis())
+                    .map(|millis| Duration::from_millis(millis as u64));
+                last_end = end;
+                let lb = match &mut builder {
+                    Some(lb) => {
+                        lb.append(start, end, over, shape);
+                        lb
                     }
+                    None => builder.insert(LinearBuilder::new(start, end, over, shape)),
+                };
+                if let Some(piece) = lb.pieces.last_mut() {
+                    piece.until = until;
                 }
             }
         }
-        builder
-            .ok_or(Error::InvalidLoadPattern(self.1))
-            .map(LoadPattern::Linear)
+        let mut builder = builder.ok_or(Error::InvalidLoadPattern(self.1))?;
+        builder.schedule = schedule;
+        Ok(LoadPattern::Linear(builder))
+    }
+}
+
+// Parses a traffic file into how long each row lasts and its rate as a fraction of the busiest
+// row, so `peak_load` is reached at the busiest point. Each line is `timestamp,rate` where the
+// timestamp is either seconds since the epoch or a date time such as `2021-06-01T09:00:00Z`.
+// The last row lasts as long as the one before it
+fn parse_traffic_csv(contents: &str) -> Result<Vec<(Duration, f64)>, String> {
+    let mut rows: Vec<(i64, f64)> = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |what| format!("invalid {} on line {}", what, i + 1);
+        let mut fields = line.split(',').map(|f| f.trim().trim_matches('"'));
+        let (timestamp, rate) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(t), Some(r), None) => (t, r),
+            _ => return Err(format!("expected `timestamp,rate` on line {}", i + 1)),
+        };
+        let rate = match rate.parse::<f64>() {
+            Ok(r) if r.is_finite() && r >= 0.0 => r,
+            // a header
+            Err(_) if rows.is_empty() && timestamp.parse::<f64>().is_err() => continue,
+            _ => return Err(invalid("rate")),
+        };
+        let millis = if let Ok(secs) = timestamp.parse::<f64>() {
+            (secs * 1000.0) as i64
+        } else if let Ok(dt) = DateTime::parse_from_rfc3339(timestamp) {
+            dt.timestamp_millis()
+        } else {
+            NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%dT%H:%M:%S")
+                .or_else(|_| NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S"))
+                .map_err(|_| invalid("timestamp"))?
+                .timestamp_millis()
+        };
+        if matches!(rows.last(), Some((previous, _)) if *previous >= millis) {
+            return Err(format!(
+                "timestamp on line {} is not after the previous row",
+                i + 1
+            ));
+        }
+        rows.push((millis, rate));
     }
+    if rows.len() < 2 {
+        return Err("expected at least two rows".into());
+    }
+    let peak = rows.iter().map(|(_, rate)| *rate).fold(0.0, f64::max);
+    if peak == 0.0 {
+        return Err("every rate is zero".into());
+    }
+    let mut lengths: Vec<_> = rows.windows(2).map(|w| w[1].0 - w[0].0).collect();
+    lengths.push(*lengths.last().expect("should have at least one length"));
+    let ret = rows
+        .iter()
+        .zip(lengths)
+        .map(|((_, rate), length)| (Duration::from_millis(length as u64), rate / peak))
+        .collect();
+    Ok(ret)
 }
 
 impl FromYaml for PreLoadPattern {
@@ -2401,23 +4249,38 @@ impl PreHitsPer {
         let string = self
             .0
             .evaluate(static_vars, &mut RequiredProviders::new())?;
-        let re = Regex::new(r"^(?i)(\d+(?:\.\d+)?)\s*hp([ms])$").expect("should be a valid regex");
-        let captures = re
-            .captures(&string)
-            .ok_or_else(|| Error::InvalidPeakLoad(string.clone(), (self.0).0.marker))?;
-        let n = captures
-            .get(1)
-            .expect("should have capture group")
-            .as_str()
-            .parse()
-            .expect("should be valid digits for HitsPer");
-        if captures.get(2).expect("should have capture group").as_str()[0..1]
-            .eq_ignore_ascii_case("m")
-        {
-            Ok(HitsPer::Minute(n))
-        } else {
-            Ok(HitsPer::Second(n))
-        }
+        hits_per_from_string2(string, (self.0).0.marker)
+    }
+}
+
+impl FromYaml for PreHitsPer {
+    fn parse<I: Iterator<Item = char>>(decoder: &mut YamlDecoder<I>) -> ParseResult<Self> {
+        let (t, marker) = FromYaml::parse(decoder)?;
+        Ok((Self(t), marker))
+    }
+}
+
+pub fn hits_per_from_string(hits_per: String) -> Result<HitsPer, Error> {
+    let marker = create_marker();
+    hits_per_from_string2(hits_per, marker)
+}
+
+fn hits_per_from_string2(string: String, marker: Marker) -> Result<HitsPer, Error> {
+    let re = Regex::new(r"^(?i)(\d+(?:\.\d+)?)\s*hp([ms])$").expect("should be a valid regex");
+    let captures = re
+        .captures(&string)
+        .ok_or_else(|| Error::InvalidPeakLoad(string.clone(), marker))?;
+    let n = captures
+        .get(1)
+        .expect("should have capture group")
+        .as_str()
+        .parse()
+        .expect("should be valid digits for HitsPer");
+    if captures.get(2).expect("should have capture group").as_str()[0..1].eq_ignore_ascii_case("m")
+    {
+        Ok(HitsPer::Minute(n))
+    } else {
+        Ok(HitsPer::Second(n))
     }
 }
 
@@ -2431,6 +4294,9 @@ pub struct LoadTest {
     pub endpoints: Vec<Endpoint>,
     pub providers: BTreeMap<String, Provider>,
     pub loggers: BTreeMap<String, Logger>,
+    pub scenarios: Vec<Scenario>,
+    // the files read by `from_file` load patterns, as written in the config
+    pub traffic_files: Vec<String>,
     vars: BTreeMap<String, json::Value>,
     load_test_errors: Vec<Error>,
 }
@@ -2442,9 +4308,32 @@ pub struct FileProvider {
     // range 1-65535
     pub buffer: Limit,
     pub format: FileFormat,
+    pub partition: Option<Partition>,
+    pub path: String,
+    pub random: bool,
+    pub repeat: bool,
+    pub shuffle: bool,
+    pub unique: bool,
+}
+
+// which of the records in a file a pewpew instance uses, so several instances can share a file
+// without using the same records
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Partition {
+    pub count: NonZeroUsize,
+    pub index: usize,
+}
+
+#[derive(Clone, Default, PartialEq)]
+pub struct SqliteProvider {
+    pub auto_return: Option<EndpointProvidesSendOptions>,
+    // range 1-65535
+    pub buffer: Limit,
     pub path: String,
+    pub query: String,
     pub random: bool,
     pub repeat: bool,
+    pub shuffle: bool,
     pub unique: bool,
 }
 
@@ -2498,6 +4387,7 @@ impl Logger {
 }
 
 pub struct Endpoint {
+    pub arrival: Option<Arrival>,
     pub body: BodyTemplate,
     pub declare: Vec<(String, ValueOrExpression)>,
     pub headers: Vec<(String, Template)>,
@@ -2510,10 +4400,105 @@ pub struct Endpoint {
     pub peak_load: Option<HitsPer>,
     pub provides: Vec<(String, Select)>,
     pub providers_to_stream: RequiredProviders,
+    pub rate_limit: Option<String>,
     pub required_providers: RequiredProviders,
     pub request_timeout: Option<Duration>,
     pub tags: BTreeMap<String, Template>,
+    pub think_time: Option<ThinkTime>,
     pub url: Template,
+    // how long from the start of the test this endpoint's stats are left out of the results
+    pub warmup: Option<Duration>,
+}
+
+// how long to wait before sending a request. Each template evaluates to a duration
+#[derive(Clone)]
+pub enum ThinkTime {
+    Fixed(Template),
+    // a random duration between the two
+    Random(Template, Template),
+}
+
+// a closed-model flow where each virtual user makes the requests in `steps` in order
+pub struct Scenario {
+    pub load_pattern: Option<LoadPattern>,
+    pub name: String,
+    pub peak_users: usize,
+    pub steps: Vec<Endpoint>,
+}
+
+impl Scenario {
+    fn from_preprocessed(
+        scenario: ScenarioPreProcessed,
+        first_endpoint_id: usize,
+        static_vars: &BTreeMap<String, json::Value>,
+        global_load_pattern: &Option<LoadPattern>,
+        global_headers: &[(String, (Template, RequiredProviders))],
+        config_path: &Path,
+        read_traffic_file: &dyn Fn(&str) -> io::Result<String>,
+    ) -> Result<Self, Error> {
+        let ScenarioPreProcessed {
+            load_pattern,
+            name,
+            peak_users,
+            steps,
+            ..
+        } = scenario;
+        let load_pattern = load_pattern
+            .map(|l| l.evaluate(static_vars, read_traffic_file))
+            .transpose()?
+            .or_else(|| global_load_pattern.clone());
+        let steps = steps
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut step)| {
+                // the pacing of a step comes from the virtual users, not from the step itself
+                let not_allowed = [
+                    ("arrival", step.arrival.is_some()),
+                    ("declare", !step.declare.is_empty()),
+                    ("load_pattern", step.load_pattern.is_some()),
+                    ("on_demand", step.on_demand),
+                    ("peak_load", step.peak_load.is_some()),
+                ];
+                if let Some((key, _)) = not_allowed.iter().find(|(_, set)| *set) {
+                    return Err(Error::UnrecognizedKey(
+                        key.to_string(),
+                        Some("steps".into()),
+                        step.marker,
+                    ));
+                }
+                // a virtual user should never wait on a full provider
+                for (_, value) in step.provides.0.iter_mut() {
+                    if value.send.is_none() {
+                        value.send = Some(EndpointProvidesSendOptions::IfNotFull);
+                    }
+                }
+                let marker = step.marker;
+                step.tags.insert(
+                    "scenario".into(),
+                    PreTemplate::new(WithMarker::new(name.clone(), marker)),
+                );
+                step.tags.insert(
+                    "step".into(),
+                    PreTemplate::new(WithMarker::new((i + 1).to_string(), marker)),
+                );
+                Endpoint::from_preprocessed(
+                    step,
+                    first_endpoint_id + i,
+                    static_vars,
+                    &None,
+                    global_headers,
+                    config_path,
+                    read_traffic_file,
+                )
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Scenario {
+            load_pattern,
+            name,
+            peak_users,
+            steps,
+        })
+    }
 }
 
 #[derive(Clone)]
@@ -2557,8 +4542,10 @@ impl Endpoint {
         global_load_pattern: &Option<LoadPattern>,
         global_headers: &[(String, (Template, RequiredProviders))],
         config_path: &Path,
+        read_traffic_file: &dyn Fn(&str) -> io::Result<String>,
     ) -> Result<Self, Error> {
         let EndpointPreProcessed {
+            arrival,
             declare,
             headers,
             body,
@@ -2571,8 +4558,11 @@ impl Endpoint {
             peak_load,
             provides,
             url,
+            rate_limit,
             request_timeout,
             mut tags,
+            think_time,
+            warmup,
             ..
         } = endpoint;
         let mut required_providers = RequiredProviders::new();
@@ -2617,7 +4607,7 @@ impl Endpoint {
             .collect::<Result<_, Error>>()?;
 
         let load_pattern = load_pattern
-            .map(|l| l.evaluate(static_vars))
+            .map(|l| l.evaluate(static_vars, read_traffic_file))
             .transpose()?
             .or_else(|| global_load_pattern.clone());
 
@@ -2645,6 +4635,10 @@ impl Endpoint {
             })
             .collect::<Result<_, Error>>()?;
 
+        let think_time = think_time
+            .map(|t| t.evaluate(static_vars, &mut required_providers))
+            .transpose()?;
+
         let body = body
             .map(|body| {
                 let value = match body {
@@ -2720,8 +4714,10 @@ impl Endpoint {
         let request_timeout = request_timeout
             .map(|d| d.evaluate(static_vars))
             .transpose()?;
+        let warmup = warmup.map(|d| d.evaluate(static_vars)).transpose()?;
 
         let mut endpoint = Endpoint {
+            arrival,
             declare,
             headers,
             body,
@@ -2734,10 +4730,13 @@ impl Endpoint {
             peak_load,
             provides,
             providers_to_stream,
+            rate_limit,
             request_timeout,
             required_providers,
             url,
             tags,
+            think_time,
+            warmup,
         };
 
         for (key, value) in logs.0 {
@@ -2779,10 +4778,14 @@ impl Endpoint {
 }
 
 impl LoadTest {
+    // `read_file` gets the contents of the other files the config reads while being parsed (the
+    // traffic files of `from_file` load patterns). The config crate never reads from the file
+    // system itself so it can be used from WebAssembly
     pub fn from_config(
         bytes: &[u8],
         config_path: &Path,
         env_vars: &BTreeMap<String, String>,
+        read_file: &dyn Fn(&Path) -> io::Result<String>,
     ) -> Result<Self, Error> {
         debug!(
             "config::LoadTest::from_config: {}",
@@ -2806,7 +4809,16 @@ impl LoadTest {
 
         let loggers = c.loggers;
         let providers = c.providers;
-        let global_load_pattern = c.load_pattern.map(|l| l.evaluate(&vars)).transpose()?;
+        // traffic files are relative to the config file
+        let traffic_files = RefCell::new(BTreeSet::new());
+        let read_traffic_file = |file: &str| {
+            traffic_files.borrow_mut().insert(file.to_string());
+            read_file(&config_path.with_file_name(file))
+        };
+        let global_load_pattern = c
+            .load_pattern
+            .map(|l| l.evaluate(&vars, &read_traffic_file))
+            .transpose()?;
         let global_headers: Vec<_> = c
             .config
             .client
@@ -2825,9 +4837,31 @@ impl LoadTest {
                 request_timeout: c.config.client.request_timeout.evaluate(&vars)?,
             },
             general: GeneralConfig {
+                arrival: c.config.general.arrival,
+                arrival_seed: c.config.general.arrival_seed,
                 auto_buffer_start_size: c.config.general.auto_buffer_start_size,
                 bucket_size: c.config.general.bucket_size.evaluate(&vars)?,
                 log_provider_stats: c.config.general.log_provider_stats,
+                rate_limits: c
+                    .config
+                    .general
+                    .rate_limits
+                    .iter()
+                    .map(|(name, pre_hits_per)| match pre_hits_per.evaluate(&vars)? {
+                        // a rate limit has to let some requests through
+                        HitsPer::Second(n) | HitsPer::Minute(n) if n <= 0.0 => {
+                            let WithMarker { inner, marker } = &(pre_hits_per.0).0;
+                            Err(Error::InvalidPeakLoad(inner.clone(), *marker))
+                        }
+                        hits_per => Ok((name.clone(), hits_per)),
+                    })
+                    .collect::<Result<_, Error>>()?,
+                warmup: c
+                    .config
+                    .general
+                    .warmup
+                    .map(|w| w.evaluate(&vars))
+                    .transpose()?,
                 watch_transition_time: c
                     .config
                     .general
@@ -2853,6 +4887,7 @@ impl LoadTest {
                     &global_load_pattern,
                     &global_headers,
                     config_path,
+                    &read_traffic_file,
                 )?;
 
                 // check for errors which would prevent a load test (but are ok for a try run)
@@ -2878,6 +4913,31 @@ impl LoadTest {
 
                 Ok(e)
             })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let mut step_markers = Vec::new();
+        let mut endpoint_id = endpoints.len();
+        let scenarios = c
+            .scenarios
+            .into_iter()
+            .map(|s| {
+                let marker = s.marker;
+                step_markers.extend(s.steps.iter().map(|step| step.marker));
+                let step_count = s.steps.len();
+                let s = Scenario::from_preprocessed(
+                    s,
+                    endpoint_id,
+                    &vars,
+                    &global_load_pattern,
+                    &global_headers,
+                    config_path,
+                    &read_traffic_file,
+                )?;
+                endpoint_id += step_count;
+                if s.load_pattern.is_none() {
+                    load_test_errors.push(Error::MissingLoadPattern(marker));
+                }
+                Ok(s)
+            })
             .collect::<Result<_, Error>>()?;
         let providers = providers
             .into_iter()
@@ -2889,27 +4949,103 @@ impl LoadTest {
                             auto_return,
                             buffer,
                             format,
+                            partition,
                             path,
                             random,
                             repeat,
+                            shuffle,
                             unique,
                         } = f;
+                        let partition = partition.map(|p| p.evaluate(&vars)).transpose()?;
                         let path = path.evaluate(&vars, &mut RequiredProviders::new())?;
                         let f = FileProvider {
                             csv,
                             auto_return,
                             buffer,
                             format,
+                            partition,
                             path,
                             random,
                             repeat,
+                            shuffle,
                             unique,
                         };
                         Provider::File(f)
                     }
                     ProviderPreProcessed::Range(r) => Provider::Range(r.into()),
-                    ProviderPreProcessed::Response(r) => Provider::Response(r),
+                    ProviderPreProcessed::Response(r) => {
+                        let ResponseProviderPreProcessed {
+                            auto_return,
+                            buffer,
+                            persist,
+                            ttl,
+                            unique,
+                        } = r;
+                        let persist = persist
+                            .map(|p| p.evaluate(&vars, &mut RequiredProviders::new()))
+                            .transpose()?;
+                        let ttl = ttl.map(|t| t.evaluate(&vars)).transpose()?;
+                        let r = ResponseProvider {
+                            auto_return,
+                            buffer,
+                            persist,
+                            ttl,
+                            unique,
+                        };
+                        Provider::Response(r)
+                    }
                     ProviderPreProcessed::List(l) => Provider::List(l),
+                    ProviderPreProcessed::Sqlite(s) => {
+                        let SqliteProviderPreProcessed {
+                            auto_return,
+                            buffer,
+                            path,
+                            query,
+                            random,
+                            repeat,
+                            shuffle,
+                            unique,
+                        } = s;
+                        let path = path.evaluate(&vars, &mut RequiredProviders::new())?;
+                        let s = SqliteProvider {
+                            auto_return,
+                            buffer,
+                            path,
+                            query,
+                            random,
+                            repeat,
+                            shuffle,
+                            unique,
+                        };
+                        Provider::Sqlite(s)
+                    }
+                    ProviderPreProcessed::Generate(g) => Provider::Generate(g),
+                    ProviderPreProcessed::Map(m) => Provider::Map(m),
+                    ProviderPreProcessed::Sequence(s) => Provider::Sequence(s.evaluate(&vars)?),
+                    ProviderPreProcessed::Files(f) => {
+                        let FilesProviderPreProcessed {
+                            auto_return,
+                            buffer,
+                            glob,
+                            path,
+                            recursive,
+                            repeat,
+                            shuffle,
+                            unique,
+                        } = f;
+                        let path = path.evaluate(&vars, &mut RequiredProviders::new())?;
+                        let f = FilesProvider {
+                            auto_return,
+                            buffer,
+                            glob,
+                            path,
+                            recursive,
+                            repeat,
+                            shuffle,
+                            unique,
+                        };
+                        Provider::Files(f)
+                    }
                 };
                 Ok((key, value))
             })
@@ -2920,6 +5056,8 @@ impl LoadTest {
             endpoints,
             providers,
             loggers: Default::default(),
+            scenarios,
+            traffic_files: traffic_files.into_inner().into_iter().collect(),
             vars,
             load_test_errors,
         };
@@ -2929,11 +5067,15 @@ impl LoadTest {
         }
 
         // validate each endpoint only references valid loggers and providers
-        for (e, marker) in loadtest.endpoints.iter().zip(endpoint_markers) {
+        let steps = loadtest.scenarios.iter().flat_map(|s| s.steps.iter());
+        let markers = endpoint_markers.into_iter().chain(step_markers);
+        for (e, marker) in loadtest.endpoints.iter().chain(steps).zip(markers) {
             loadtest.verify_loggers(e.logs.iter().map(|(l, _)| (l, &marker)))?;
+            loadtest.verify_rate_limit(&e.rate_limit, marker)?;
             let providers = e.provides.iter().map(|(k, _)| (k, &marker));
             let providers = e.required_providers.iter().chain(providers);
             loadtest.verify_providers(providers)?;
+            loadtest.verify_map_providers(e)?;
         }
 
         Ok(loadtest)
@@ -2943,10 +5085,21 @@ impl LoadTest {
         self.endpoints
             .iter()
             .filter_map(|e| e.load_pattern.as_ref().map(LoadPattern::duration))
+            .chain(
+                self.scenarios
+                    .iter()
+                    .filter_map(|s| s.load_pattern.as_ref().map(LoadPattern::duration)),
+            )
             .max()
             .unwrap_or_default()
     }
 
+    // every endpoint in the test, including the steps of each scenario
+    fn all_endpoints_mut(&mut self) -> impl Iterator<Item = &mut Endpoint> {
+        let steps = self.scenarios.iter_mut().flat_map(|s| s.steps.iter_mut());
+        self.endpoints.iter_mut().chain(steps)
+    }
+
     pub fn add_logger(&mut self, key: String, value: LoggerPreProcessed) -> Result<(), Error> {
         let mut required_providers = RequiredProviders::new();
         let (value, select) =
@@ -2954,7 +5107,7 @@ impl LoadTest {
         self.loggers.insert(key.clone(), value);
         self.verify_providers(required_providers.iter())?;
         if let Some(select) = select {
-            for endpoint in &mut self.endpoints {
+            for endpoint in self.all_endpoints_mut() {
                 endpoint.append_processed_logger(
                     key.clone(),
                     select.clone(),
@@ -2967,7 +5120,7 @@ impl LoadTest {
 
     pub fn clear_loggers(&mut self) {
         self.loggers.clear();
-        for endpoint in &mut self.endpoints {
+        for endpoint in self.all_endpoints_mut() {
             endpoint.logs.clear();
         }
     }
@@ -2981,6 +5134,15 @@ impl LoadTest {
             .map(|_| ())
     }
 
+    fn verify_rate_limit(&self, rate_limit: &Option<String>, marker: Marker) -> Result<(), Error> {
+        match rate_limit {
+            Some(name) if !self.config.general.rate_limits.contains_key(name) => {
+                Err(Error::UnknownRateLimit(name.clone(), marker))
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn verify_loggers<'a, I: Iterator<Item = (&'a String, &'a Marker)>>(
         &self,
         mut loggers: I,
@@ -3003,6 +5165,24 @@ impl LoadTest {
             Ok(())
         }
     }
+
+    // map providers are only accessed with `lookup`, which can only be used in a `declare`
+    fn verify_map_providers(&self, endpoint: &Endpoint) -> Result<(), Error> {
+        let is_map = |p: &String| matches!(self.providers.get(p), Some(Provider::Map(_)));
+        if let Some((p, marker)) = endpoint
+            .required_providers
+            .lookups()
+            .find(|(p, _)| !is_map(p))
+        {
+            Err(Error::NotAMapProvider(p.clone(), *marker))
+        } else if let Some((p, marker)) =
+            endpoint.providers_to_stream.iter().find(|(p, _)| is_map(p))
+        {
+            Err(Error::InvalidMapProviderUse(p.clone(), *marker))
+        } else {
+            Ok(())
+        }
+    }
 }
 
 pub(crate) fn json_value_to_string(v: Cow<'_, json::Value>) -> Cow<'_, String> {
@@ -3018,6 +5198,10 @@ mod tests {
     use super::*;
     use maplit::btreemap;
 
+    fn no_files(_: &str) -> io::Result<String> {
+        Err(io::ErrorKind::NotFound.into())
+    }
+
     fn check_all<T: FromYaml + std::fmt::Debug + PartialEq>(checks: Vec<(&str, Option<T>)>) {
         for (i, (s, n)) in checks.into_iter().enumerate() {
             let n2 = T::from_yaml_str(s);
@@ -3048,6 +5232,18 @@ mod tests {
         check_all(values);
     }
 
+    #[test]
+    fn from_yaml_arrival() {
+        let values = vec![
+            ("constant", Some(Arrival::Constant)),
+            ("poisson", Some(Arrival::Poisson)),
+            ("uniform", Some(Arrival::Uniform)),
+            ("Poisson", None),
+            ("7", None),
+        ];
+        check_all(values);
+    }
+
     #[test]
     fn from_yaml_limit() {
         let values = vec![
@@ -3082,6 +5278,8 @@ mod tests {
                     random: false,
                     repeat: true,
                     values: vec![json::json!("foo"), json::json!("bar")],
+                    weights: None,
+                    shuffle: false,
                     unique: false,
                 })),
             ),
@@ -3096,6 +5294,8 @@ mod tests {
                     random: true,
                     repeat: false,
                     values: vec![json::json!("foo"), json::json!("bar")],
+                    weights: None,
+                    shuffle: false,
                     unique: false,
                 })),
             ),
@@ -3111,9 +5311,59 @@ mod tests {
                     random: true,
                     repeat: false,
                     values: vec![json::json!("foo"), json::json!("bar")],
+                    weights: None,
+                    shuffle: false,
                     unique: true,
                 })),
             ),
+            (
+                "
+                weighted:
+                    - value: small
+                      weight: 80
+                    - value: { items: 50 }
+                      weight: 0.5",
+                Some(ListProvider::WithOptions(ListWithOptions {
+                    random: false,
+                    repeat: true,
+                    values: vec![json::json!("small"), json::json!({ "items": 50 })],
+                    weights: Some(vec![80.0, 0.5]),
+                    shuffle: false,
+                    unique: false,
+                })),
+            ),
+            (
+                "
+                weighted:
+                    - value: small
+                      weight: 0",
+                None,
+            ),
+            (
+                "
+                weighted:
+                    - value: small",
+                None,
+            ),
+            ("weighted: []", None),
+            (
+                "
+                weighted:
+                    - value: small
+                      weight: 1e308
+                    - value: big
+                      weight: 1e308",
+                None,
+            ),
+            (
+                "
+                values:
+                    - foo
+                weighted:
+                    - value: small
+                      weight: 1",
+                None,
+            ),
             (
                 "
                 - foo
@@ -3140,6 +5390,161 @@ mod tests {
         check_all(values);
     }
 
+    #[test]
+    fn partition_evaluate() {
+        let static_vars = btreemap! {
+            "instance".to_string() => json::json!(2),
+            "instances".to_string() => json::json!("4"),
+        };
+        let partition = PartitionPreProcessed::from_yaml_str(
+            "
+            index: ${instance}
+            count: ${instances}",
+        )
+        .unwrap()
+        .evaluate(&static_vars)
+        .unwrap();
+        let count = NonZeroUsize::new(4).unwrap();
+        assert_eq!(partition, Partition { count, index: 2 });
+
+        let invalid = vec![
+            "{ index: 4, count: 4 }",
+            "{ index: 0, count: 0 }",
+            "{ index: -1, count: 2 }",
+        ];
+        for yaml in invalid {
+            let partition = PartitionPreProcessed::from_yaml_str(yaml)
+                .unwrap()
+                .evaluate(&static_vars);
+            assert!(
+                matches!(partition, Err(Error::InvalidPartition(..))),
+                "{}",
+                yaml
+            );
+        }
+    }
+
+    #[test]
+    fn sequence_provider() {
+        let static_vars = btreemap! {
+            "start".to_string() => json::json!("98"),
+        };
+        let sp = SequenceProviderPreProcessed::from_yaml_str(
+            "
+            start: ${start}
+            end: 100
+            wrap: true
+            prefix: A
+            suffix: Z
+            width: 4",
+        )
+        .unwrap()
+        .evaluate(&static_vars)
+        .unwrap();
+        let values: Vec<_> = iter::successors(Some(sp.start), |n| sp.next(*n))
+            .take(4)
+            .map(|n| sp.format(n))
+            .collect();
+        assert_eq!(values, vec!["A0098Z", "A0099Z", "A0100Z", "A0098Z"]);
+
+        let sp = SequenceProviderPreProcessed::from_yaml_str("{ end: 4, step: 3 }")
+            .unwrap()
+            .evaluate(&static_vars)
+            .unwrap();
+        let values: Vec<_> = iter::successors(Some(sp.start), |n| sp.next(*n))
+            .map(|n| sp.format(n))
+            .collect();
+        assert_eq!(values, vec![json::json!(0), json::json!(3)]);
+
+        let sp = SequenceProviderPreProcessed::from_yaml_str("{ end: -5 }");
+        assert!(matches!(sp, Err(Error::InvalidSequence(..))));
+
+        let sp = SequenceProviderPreProcessed::from_yaml_str("{ start: -8, end: -5 }")
+            .unwrap()
+            .evaluate(&static_vars)
+            .unwrap();
+        assert_eq!(sp.start, -8);
+
+        let invalid = vec!["{ start: foo }", "{ start: 5, end: 4 }"];
+        for yaml in invalid {
+            let sp = SequenceProviderPreProcessed::from_yaml_str(yaml)
+                .unwrap()
+                .evaluate(&static_vars);
+            assert!(matches!(sp, Err(Error::InvalidSequence(..))), "{}", yaml);
+        }
+    }
+
+    #[test]
+    fn shuffled_list_provider() {
+        let list = ListProvider::from_yaml_str(
+            "
+            shuffle: true
+            values: [1, 2, 3, 4, 5]",
+        )
+        .unwrap();
+        let values: Vec<_> = list.into_iter().take(50).collect();
+        for pass in values.chunks(5) {
+            let mut pass: Vec<_> = pass.iter().map(|v| v.as_u64().unwrap()).collect();
+            pass.sort_unstable();
+            assert_eq!(pass, vec![1, 2, 3, 4, 5]);
+        }
+
+        let invalid = ListProvider::from_yaml_str(
+            "
+            shuffle: true
+            weighted:
+                - value: 1
+                  weight: 1",
+        );
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn weighted_list_provider() {
+        let list = ListProvider::from_yaml_str(
+            "
+            weighted:
+                - value: small
+                  weight: 80
+                - value: medium
+                  weight: 15
+                - value: huge
+                  weight: 5",
+        )
+        .unwrap();
+
+        let mut counts = BTreeMap::new();
+        for v in list.clone().into_iter().take(10_000) {
+            *counts
+                .entry(v.as_str().unwrap().to_string())
+                .or_insert(0i32) += 1;
+        }
+        for &(value, expect) in &[("small", 8000), ("medium", 1500), ("huge", 500)] {
+            let count = counts[value];
+            assert!(
+                (count - expect).abs() < 300,
+                "{} was picked {} times",
+                value,
+                count
+            );
+        }
+
+        let mut list = list;
+        if let ListProvider::WithOptions(l) = &mut list {
+            l.repeat = false;
+        }
+        let mut values: Vec<_> = list.into_iter().collect();
+        values.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+        assert_eq!(
+            values,
+            vec![
+                json::json!("huge"),
+                json::json!("medium"),
+                json::json!("small")
+            ]
+        );
+    }
+
     #[test]
     fn from_yaml_load_pattern_pre_processed() {
         let values = vec![
@@ -3152,7 +5557,7 @@ mod tests {
                 Some(LoadPatternPreProcessed::Linear(LinearBuilderPreProcessed {
                     from: None,
                     to: PrePercent(create_template("10%")),
-                    over: PreDuration(create_template("9h")),
+                    length: PreSegmentLength::Over(PreDuration(create_template("9h"))),
                 })),
             ),
             (
@@ -3164,7 +5569,7 @@ mod tests {
                 Some(LoadPatternPreProcessed::Linear(LinearBuilderPreProcessed {
                     from: Some(PrePercent(create_template("50%"))),
                     to: PrePercent(create_template("10%")),
-                    over: PreDuration(create_template("9h")),
+                    length: PreSegmentLength::Over(PreDuration(create_template("9h"))),
                 })),
             ),
             (
@@ -3176,11 +5581,431 @@ mod tests {
                     foo: 123",
                 None,
             ),
+            (
+                "
+                step:
+                    to: 10%
+                    over: 9h",
+                Some(LoadPatternPreProcessed::Step(StepBuilderPreProcessed {
+                    to: PrePercent(create_template("10%")),
+                    length: PreSegmentLength::Over(PreDuration(create_template("9h"))),
+                })),
+            ),
+            (
+                "
+                step:
+                    from: 50%
+                    to: 10%
+                    over: 9h",
+                None,
+            ),
+            (
+                "
+                exponential:
+                    from: 1%
+                    to: 100%
+                    over: 10m",
+                Some(LoadPatternPreProcessed::Exponential(
+                    LinearBuilderPreProcessed {
+                        from: Some(PrePercent(create_template("1%"))),
+                        to: PrePercent(create_template("100%")),
+                        length: PreSegmentLength::Over(PreDuration(create_template("10m"))),
+                    },
+                )),
+            ),
+            (
+                "
+                sine:
+                    to: 75%
+                    amplitude: 25%
+                    period: 10m
+                    over: 1h",
+                Some(LoadPatternPreProcessed::Sine(SineBuilderPreProcessed {
+                    from: None,
+                    to: PrePercent(create_template("75%")),
+                    amplitude: PrePercent(create_template("25%")),
+                    period: PreDuration(create_template("10m")),
+                    length: PreSegmentLength::Over(PreDuration(create_template("1h"))),
+                })),
+            ),
+            (
+                "
+                sine:
+                    to: 75%
+                    over: 1h",
+                None,
+            ),
             ("-96", None),
         ];
         check_all(values);
     }
 
+    #[test]
+    fn load_pattern_evaluate_shapes() {
+        let load_pattern = PreLoadPattern::from_yaml_str(
+            "
+            - linear:
+                to: 50%
+                over: 1m
+            - step:
+                to: 80%
+                over: 2m
+            - exponential:
+                to: 160%
+                over: 3m
+            - sine:
+                amplitude: 10%
+                to: 100%
+                period: 30s
+                over: 4m",
+        )
+        .expect("should be valid yaml")
+        .evaluate(&Default::default(), &no_files)
+        .expect("should be a valid load_pattern");
+
+        let lb = load_pattern.builder();
+        assert_eq!(lb.duration(), Duration::from_secs(600));
+        let pieces: Vec<_> = lb
+            .pieces
+            .into_iter()
+            .map(|p| {
+                (
+                    p.start_percent,
+                    p.end_percent,
+                    p.duration.as_secs(),
+                    p.shape,
+                )
+            })
+            .collect();
+        let expect = vec![
+            (0.0, 0.5, 60, SegmentShape::Linear),
+            (0.8, 0.8, 120, SegmentShape::Step),
+            (0.8, 1.6, 180, SegmentShape::Exponential),
+            (
+                1.6,
+                1.0,
+                240,
+                SegmentShape::Sine {
+                    amplitude_percent: 0.1,
+                    period: Duration::from_secs(30),
+                },
+            ),
+        ];
+        assert_eq!(pieces, expect);
+
+        let zero_period = PreLoadPattern::from_yaml_str(
+            "
+            - sine:
+                amplitude: 10%
+                to: 100%
+                period: 0s
+                over: 4m",
+        )
+        .expect("should be valid yaml")
+        .evaluate(&Default::default(), &no_files);
+        assert!(zero_period.is_err());
+    }
+
+    #[test]
+    fn load_pattern_evaluate_schedule() {
+        let load_pattern = PreLoadPattern::from_yaml_str(
+            "
+            - schedule:
+                start: 06:00
+                timezone: -07:00
+            - linear:
+                to: 50%
+                until: 09:00
+            - step:
+                to: 150%
+                over: 3h
+            - linear:
+                to: 10%
+                until: 02:30",
+        )
+        .expect("should be valid yaml")
+        .evaluate(&Default::default(), &no_files)
+        .expect("should be a valid load_pattern");
+
+        let lb = load_pattern.builder();
+        assert_eq!(
+            lb.schedule,
+            Some(Schedule {
+                start: Duration::from_secs(6 * 60 * 60),
+                timezone: Timezone::Offset(-7 * 60 * 60),
+            })
+        );
+        let durations: Vec<_> = lb
+            .pieces
+            .iter()
+            .map(|p| p.duration.as_secs() / 60)
+            .collect();
+        // the last segment runs past midnight
+        assert_eq!(durations, vec![180, 180, 14 * 60 + 30]);
+
+        let invalid = vec![
+            // `until` needs a schedule
+            "
+            - linear:
+                to: 50%
+                until: 09:00",
+            // the schedule has to come first
+            "
+            - linear:
+                to: 50%
+                over: 1h
+            - schedule:
+                start: 06:00",
+            "
+            - schedule:
+                start: 25:00
+            - linear:
+                to: 50%
+                over: 1h",
+            "
+            - schedule:
+                timezone: America/Denver
+            - linear:
+                to: 50%
+                over: 1h",
+            // a segment can't last until the time it starts
+            "
+            - schedule:
+                start: 06:00
+            - linear:
+                to: 50%
+                until: 06:00",
+        ];
+        for yaml in invalid {
+            let load_pattern = PreLoadPattern::from_yaml_str(yaml)
+                .expect("should be valid yaml")
+                .evaluate(&Default::default(), &no_files);
+            assert!(load_pattern.is_err(), "{}", yaml);
+        }
+
+        // the error names whichever of `over` and `until` came second
+        for (yaml, key) in [
+            ("over: 1h\n until: 09:00", "until"),
+            ("until: 09:00\n over: 1h", "over"),
+        ] {
+            let yaml = format!(
+                "
+                - linear:
+                    to: 50%
+                    {}",
+                yaml.replace("\n ", "\n                    ")
+            );
+            let both = PreLoadPattern::from_yaml_str(&yaml);
+            assert!(
+                matches!(&both, Err(Error::YamlDeserialize(Some(k), _)) if k == key),
+                "{}",
+                yaml
+            );
+        }
+    }
+
+    #[test]
+    fn load_pattern_align_to_clock() {
+        let scheduled = |timezone| {
+            PreLoadPattern::from_yaml_str(&format!(
+                "
+                - schedule:
+                    start: 09:00
+                    timezone: {}
+                - linear:
+                    to: 100%
+                    over: 3h",
+                timezone
+            ))
+            .expect("should be valid yaml")
+            .evaluate(&Default::default(), &no_files)
+            .expect("should be a valid load_pattern")
+        };
+        let hours = |h: u64| Duration::from_secs(h * 60 * 60);
+        // 10:00 UTC on the day after the epoch
+        let now = hours(24 + 10);
+
+        let mut load_pattern = scheduled("UTC");
+        assert_eq!(load_pattern.align_to_clock(now, &|_| 0), Some(hours(1)));
+        assert_eq!(load_pattern.duration(), hours(3));
+
+        // the local timezone is 1 hour ahead so it is 11:00 there
+        let mut load_pattern = scheduled("local");
+        assert_eq!(
+            load_pattern.align_to_clock(now, &|_| 60 * 60),
+            Some(hours(2))
+        );
+
+        // it is 05:00 in UTC-05:00 so the pattern waits 4 hours at 0% before starting
+        let mut load_pattern = scheduled("UTC-05:00");
+        assert_eq!(load_pattern.align_to_clock(now, &|_| 0), Some(hours(0)));
+        assert_eq!(load_pattern.duration(), hours(7));
+        let first = &load_pattern.builder().pieces[0];
+        assert_eq!((first.end_percent, first.duration), (0.0, hours(4)));
+
+        let mut unscheduled = PreLoadPattern::from_yaml_str(
+            "
+            - linear:
+                to: 100%
+                over: 3h",
+        )
+        .expect("should be valid yaml")
+        .evaluate(&Default::default(), &no_files)
+        .expect("should be a valid load_pattern");
+        assert_eq!(unscheduled.align_to_clock(now, &|_| 0), None);
+    }
+
+    #[test]
+    fn load_pattern_align_to_clock_across_daylight_saving() {
+        let scheduled = |segment: (&str, &str)| {
+            PreLoadPattern::from_yaml_str(&format!(
+                "
+                - schedule:
+                    start: {}
+                    timezone: local
+                - linear:
+                    to: 100%
+                    {}",
+                segment.0, segment.1
+            ))
+            .expect("should be valid yaml")
+            .evaluate(&Default::default(), &no_files)
+            .expect("should be a valid load_pattern")
+        };
+        let hours = |h: u64| Duration::from_secs(h * 60 * 60);
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        // the clocks go forward an hour at 02:00 on the day after the epoch
+        let change = hours(24 + 2).as_secs() as i64;
+        let local_offset = |secs: i64| if secs < change { 0 } else { 60 * 60 };
+
+        // 01:00 until 03:00 is only an hour when the clocks skip 02:00
+        let mut load_pattern = scheduled(("01:00", "until: 03:00"));
+        let now = hours(24 + 1) + minutes(30);
+        assert_eq!(
+            load_pattern.align_to_clock(now, &local_offset),
+            Some(minutes(30))
+        );
+        assert_eq!(load_pattern.duration(), hours(1));
+
+        // having finished for the day, the next 09:00 is an hour sooner than a day later
+        let mut load_pattern = scheduled(("09:00", "over: 1h"));
+        let now = hours(10) + minutes(30);
+        assert_eq!(
+            load_pattern.align_to_clock(now, &local_offset),
+            Some(hours(0))
+        );
+        let first = &load_pattern.builder().pieces[0];
+        assert_eq!(first.duration, hours(21) + minutes(30));
+    }
+
+    #[test]
+    fn parse_traffic_csv_rows() {
+        let traffic = parse_traffic_csv(
+            "timestamp,rate
+            2021-06-01T09:00:00Z,50
+            2021-06-01T09:01:00Z,200
+
+            2021-06-01T09:02:00Z,100",
+        )
+        .expect("should be a valid traffic file");
+        let expect = vec![
+            (Duration::from_secs(60), 0.25),
+            (Duration::from_secs(60), 1.0),
+            (Duration::from_secs(60), 0.5),
+        ];
+        assert_eq!(traffic, expect);
+
+        let traffic = parse_traffic_csv("1622538000,0\n1622538030,10\n1622538090,5")
+            .expect("should be a valid traffic file");
+        let expect = vec![
+            (Duration::from_secs(30), 0.0),
+            (Duration::from_secs(60), 1.0),
+            (Duration::from_secs(60), 0.5),
+        ];
+        assert_eq!(traffic, expect);
+
+        let invalid = vec![
+            "1622538000,10",
+            "1622538000,0\n1622538060,0",
+            "1622538060,10\n1622538000,10",
+            "1622538000,10\nyesterday,10",
+            "1622538000,10\n1622538060,-5",
+            "1622538000,10,3\n1622538060,5",
+        ];
+        for contents in invalid {
+            assert!(parse_traffic_csv(contents).is_err(), "{}", contents);
+        }
+    }
+
+    #[test]
+    fn load_pattern_evaluate_from_file() {
+        let dir = tempfile::tempdir().expect("should create a temp dir");
+        std::fs::write(
+            dir.path().join("traffic.csv"),
+            "1622538000,100\n1622538060,400\n1622538120,200",
+        )
+        .expect("should write traffic file");
+        let read_traffic_file = |file: &str| std::fs::read_to_string(dir.path().join(file));
+        let load_pattern = PreLoadPattern::from_yaml_str(
+            "
+            - linear:
+                to: 25%
+                over: 5m
+            - from_file: traffic.csv
+            - linear:
+                to: 0%
+                over: 1m",
+        )
+        .expect("should be valid yaml")
+        .evaluate(&Default::default(), &read_traffic_file)
+        .expect("should be a valid load_pattern");
+
+        let lb = load_pattern.builder();
+        assert_eq!(lb.duration(), Duration::from_secs(9 * 60));
+        let pieces: Vec<_> = lb
+            .pieces
+            .into_iter()
+            .map(|p| (p.start_percent, p.end_percent, p.duration.as_secs()))
+            .collect();
+        let expect = vec![
+            (0.0, 0.25, 300),
+            (0.25, 0.25, 60),
+            (1.0, 1.0, 60),
+            (0.5, 0.5, 60),
+            (0.5, 0.0, 60),
+        ];
+        assert_eq!(pieces, expect);
+
+        let missing = PreLoadPattern::from_yaml_str("- from_file: does-not-exist.csv")
+            .expect("should be valid yaml")
+            .evaluate(&Default::default(), &read_traffic_file);
+        assert!(matches!(missing, Err(Error::InvalidTrafficFile(..))));
+    }
+
+    #[test]
+    fn load_test_reads_traffic_files() {
+        let yaml = b"
+            load_pattern:
+              - from_file: traffic.csv
+            endpoints:
+              - url: http://localhost
+                peak_load: 1hps
+        ";
+        let read_file = |path: &Path| {
+            assert_eq!(path, Path::new("tests/traffic.csv"));
+            Ok("1622538000,1\n1622538060,2".to_string())
+        };
+        let load_test = LoadTest::from_config(
+            yaml,
+            Path::new("tests/loadtest.yaml"),
+            &Default::default(),
+            &read_file,
+        )
+        .expect("should be a valid config");
+        assert_eq!(load_test.traffic_files, vec!["traffic.csv"]);
+        assert_eq!(load_test.get_duration(), Duration::from_secs(120));
+    }
+
     #[test]
     fn from_yaml_provider_pre_processed() {
         let values = vec![
@@ -3194,12 +6019,45 @@ mod tests {
                     auto_return: None,
                     buffer: Default::default(),
                     format: Default::default(),
+                    partition: None,
+                    path: create_template("foo.bar"),
+                    random: false,
+                    repeat: false,
+                    shuffle: false,
+                    unique: false,
+                })),
+            ),
+            (
+                "
+                file:
+                    path: foo.bar
+                    partition:
+                        index: 1
+                        count: 4",
+                Some(ProviderPreProcessed::File(FileProviderPreProcessed {
+                    csv: Default::default(),
+                    auto_return: None,
+                    buffer: Default::default(),
+                    format: Default::default(),
+                    partition: Some(PartitionPreProcessed {
+                        count: create_template("4"),
+                        index: create_template("1"),
+                    }),
                     path: create_template("foo.bar"),
                     random: false,
                     repeat: false,
+                    shuffle: false,
                     unique: false,
                 })),
             ),
+            (
+                "
+                file:
+                    path: foo.bar
+                    partition:
+                        index: 1",
+                None,
+            ),
             (
                 "range: {}",
                 Some(ProviderPreProcessed::Range(RangeProviderPreProcessed {
@@ -3212,12 +6070,104 @@ mod tests {
             ),
             (
                 "response: {}",
-                Some(ProviderPreProcessed::Response(ResponseProvider {
+                Some(ProviderPreProcessed::Response(
+                    ResponseProviderPreProcessed {
+                        auto_return: None,
+                        buffer: Default::default(),
+                        persist: None,
+                        ttl: None,
+                        unique: false,
+                    },
+                )),
+            ),
+            (
+                "
+                response:
+                    persist: sessions.json
+                    ttl: 15m",
+                Some(ProviderPreProcessed::Response(
+                    ResponseProviderPreProcessed {
+                        auto_return: None,
+                        buffer: Default::default(),
+                        persist: Some(create_template("sessions.json")),
+                        ttl: Some(PreDuration(create_template("15m"))),
+                        unique: false,
+                    },
+                )),
+            ),
+            (
+                "map: {}",
+                Some(ProviderPreProcessed::Map(MapProvider {
+                    missing: MapProviderMissing::Block,
+                })),
+            ),
+            (
+                "
+                map:
+                    missing: error",
+                Some(ProviderPreProcessed::Map(MapProvider {
+                    missing: MapProviderMissing::Error,
+                })),
+            ),
+            (
+                "
+                map:
+                    missing: skip",
+                None,
+            ),
+            (
+                "
+                files:
+                    path: images
+                    glob: '*.png'
+                    recursive: true
+                    repeat: true",
+                Some(ProviderPreProcessed::Files(FilesProviderPreProcessed {
                     auto_return: None,
                     buffer: Default::default(),
+                    glob: FileGlob::new("*.png").unwrap(),
+                    path: create_template("images"),
+                    recursive: true,
+                    repeat: true,
+                    shuffle: false,
                     unique: false,
                 })),
             ),
+            (
+                "
+                files:
+                    glob: '*.png'",
+                None,
+            ),
+            (
+                "
+                files:
+                    path: images
+                    glob: '[a'",
+                None,
+            ),
+            (
+                "
+                sequence:
+                    start: ${start}
+                    end: 999
+                    wrap: true
+                    prefix: ORD-
+                    width: 6
+                    persist: order_id.txt",
+                Some(ProviderPreProcessed::Sequence(
+                    SequenceProviderPreProcessed {
+                        start: Some(create_template("${start}")),
+                        end: 999,
+                        step: NonZeroU16::new(1).unwrap(),
+                        wrap: true,
+                        prefix: Some("ORD-".into()),
+                        suffix: None,
+                        width: Some(6),
+                        persist: Some(create_template("order_id.txt")),
+                    },
+                )),
+            ),
             (
                 "
                 list:
@@ -3226,12 +6176,36 @@ mod tests {
                     vec![json::json!(1)],
                 ))),
             ),
+            (
+                "
+                sqlite:
+                    path: fixtures.db
+                    query: SELECT id, name FROM users
+                    repeat: true",
+                Some(ProviderPreProcessed::Sqlite(SqliteProviderPreProcessed {
+                    auto_return: None,
+                    buffer: Default::default(),
+                    path: create_template("fixtures.db"),
+                    query: "SELECT id, name FROM users".into(),
+                    random: false,
+                    repeat: true,
+                    shuffle: false,
+                    unique: false,
+                })),
+            ),
+            (
+                "
+                sqlite:
+                    path: fixtures.db",
+                None,
+            ),
         ];
         check_all(values);
     }
 
     fn create_endpoint_pre_processed(url: &str) -> EndpointPreProcessed {
         EndpointPreProcessed {
+            arrival: None,
             declare: Default::default(),
             headers: Default::default(),
             body: None,
@@ -3245,7 +6219,10 @@ mod tests {
             logs: Default::default(),
             no_auto_returns: false,
             max_parallel_requests: None,
+            rate_limit: None,
             request_timeout: None,
+            think_time: None,
+            warmup: None,
             marker: create_marker(),
         }
     }
@@ -3275,6 +6252,7 @@ mod tests {
             ("asdf", None),
             (
                 "
+                arrival: poisson
                 declare:
                     foo: bar
                 headers:
@@ -3303,8 +6281,12 @@ mod tests {
                     foo:
                         select: 1
                 no_auto_returns: true
-                request_timeout: 15s",
+                rate_limit: search
+                request_timeout: 15s
+                think_time: 2s
+                warmup: 1m",
                 Some(EndpointPreProcessed {
+                    arrival: Some(Arrival::Poisson),
                     declare: btreemap! {
                         "foo".to_string() => PreValueOrExpression(create_with_marker("bar".to_string()))
                     },
@@ -3318,7 +6300,7 @@ mod tests {
                         vec![LoadPatternPreProcessed::Linear(LinearBuilderPreProcessed {
                             from: None,
                             to: PrePercent(create_template("100%")),
-                            over: PreDuration(create_template("10m")),
+                            length: PreSegmentLength::Over(PreDuration(create_template("10m"))),
                         })],
                         create_marker(),
                     )),
@@ -3371,7 +6353,10 @@ mod tests {
                     .into(),
                     no_auto_returns: true,
                     max_parallel_requests: Some(NonZeroUsize::new(3).unwrap()),
+                    rate_limit: Some("search".into()),
                     request_timeout: Some(PreDuration(create_template("15s"))),
+                    think_time: Some(PreThinkTime::Fixed(create_template("2s"))),
+                    warmup: Some(PreDuration(create_template("1m"))),
                     marker: create_marker(),
                 }),
             ),
@@ -3384,6 +6369,73 @@ mod tests {
         check_all(values);
     }
 
+    #[test]
+    fn from_yaml_think_time() {
+        let values = vec![
+            ("1s", Some(PreThinkTime::Fixed(create_template("1s")))),
+            (
+                "${random(1, 5)}s",
+                Some(PreThinkTime::Fixed(create_template("${random(1, 5)}s"))),
+            ),
+            (
+                "
+                min: 500ms
+                max: 2s",
+                Some(PreThinkTime::Random {
+                    min: create_template("500ms"),
+                    max: create_template("2s"),
+                }),
+            ),
+            ("min: 500ms", None),
+            ("max: 2s", None),
+            ("min: 1s\nmax: 2s\nfoo: bar", None),
+            ("[1s, 2s]", None),
+        ];
+        check_all(values);
+    }
+
+    #[test]
+    fn durations() {
+        let checks = vec![
+            ("1s", Some(Duration::from_secs(1))),
+            ("2m 3s", Some(Duration::from_secs(123))),
+            ("1h", Some(Duration::from_secs(3600))),
+            ("5 mins", Some(Duration::from_secs(300))),
+            ("250ms", None),
+            ("1.5s", None),
+            ("asdf", None),
+        ];
+        for (s, expect) in checks {
+            assert_eq!(
+                duration_from_string(s.into()).ok(),
+                expect,
+                "duration `{}`",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn think_time_durations() {
+        let checks = vec![
+            ("1s", Some(Duration::from_secs(1))),
+            ("2m 3s", Some(Duration::from_secs(123))),
+            ("250ms", Some(Duration::from_millis(250))),
+            ("1s 500 millis", Some(Duration::from_millis(1500))),
+            ("3 milliseconds", Some(Duration::from_millis(3))),
+            ("1.5s", None),
+            ("asdf", None),
+        ];
+        for (s, expect) in checks {
+            assert_eq!(
+                think_time_from_string(s.into()).ok(),
+                expect,
+                "think_time `{}`",
+                s
+            );
+        }
+    }
+
     #[test]
     fn from_yaml_body() {
         let values = vec![
@@ -3514,6 +6566,30 @@ mod tests {
                 "{}",
                 Some(GeneralConfigPreProcessed::default(create_marker())),
             ),
+            (
+                "
+                arrival: uniform
+                arrival_seed: 42",
+                Some(GeneralConfigPreProcessed {
+                    arrival: Arrival::Uniform,
+                    arrival_seed: Some(42),
+                    ..DefaultWithMarker::default(create_marker())
+                }),
+            ),
+            (
+                "
+                rate_limits:
+                    search: 200hps
+                warmup: 30s",
+                Some(GeneralConfigPreProcessed {
+                    rate_limits: btreemap! {
+                        "search".to_string() => PreHitsPer(create_template("200hps")),
+                    },
+                    warmup: Some(PreDuration(create_template("30s"))),
+                    ..DefaultWithMarker::default(create_marker())
+                }),
+            ),
+            ("arrival: random", None),
         ];
         check_all(values);
     }
@@ -3530,15 +6606,230 @@ mod tests {
                     providers: Default::default(),
                     load_pattern: None,
                     loggers: Default::default(),
+                    scenarios: Default::default(),
                     vars: Default::default(),
                     endpoints: vec![create_endpoint_pre_processed("http://localhost:8080")],
                 }),
             ),
+            (
+                "scenarios:
+                    - name: browse
+                      peak_users: 1
+                      steps:
+                        - url: http://localhost:8080",
+                Some(LoadTestPreProcessed {
+                    config: DefaultWithMarker::default(create_marker()),
+                    providers: Default::default(),
+                    load_pattern: None,
+                    loggers: Default::default(),
+                    scenarios: vec![ScenarioPreProcessed {
+                        load_pattern: None,
+                        name: "browse".into(),
+                        peak_users: 1,
+                        steps: vec![create_endpoint_pre_processed("http://localhost:8080")],
+                        marker: create_marker(),
+                    }],
+                    vars: Default::default(),
+                    endpoints: Vec::new(),
+                }),
+            ),
             ("config: {}", None),
         ];
         check_all(values);
     }
 
+    #[test]
+    fn from_yaml_scenario_pre_processed() {
+        let values = vec![
+            ("asdf", None),
+            ("name: browse", None),
+            ("name: browse\npeak_users: 10", None),
+            (
+                "name: browse\npeak_users: 10\nsteps:\n  - url: http://localhost\nfoo: bar",
+                None,
+            ),
+            (
+                "
+                name: checkout
+                peak_users: 10
+                load_pattern:
+                  - linear:
+                      to: 100%
+                      over: 1m
+                steps:
+                  - url: http://localhost:8080
+                  - url: http://localhost:8081",
+                Some(ScenarioPreProcessed {
+                    load_pattern: Some(PreLoadPattern(
+                        vec![LoadPatternPreProcessed::Linear(LinearBuilderPreProcessed {
+                            from: None,
+                            to: PrePercent(create_template("100%")),
+                            length: PreSegmentLength::Over(PreDuration(create_template("1m"))),
+                        })],
+                        create_marker(),
+                    )),
+                    name: "checkout".into(),
+                    peak_users: 10,
+                    steps: vec![
+                        create_endpoint_pre_processed("http://localhost:8080"),
+                        create_endpoint_pre_processed("http://localhost:8081"),
+                    ],
+                    marker: create_marker(),
+                }),
+            ),
+        ];
+        check_all(values);
+    }
+
+    #[test]
+    fn load_test_with_scenarios() {
+        let yaml = br#"
+            load_pattern:
+              - linear:
+                  to: 100%
+                  over: 5m
+            endpoints:
+              - url: http://localhost:8080
+                peak_load: 1hps
+            scenarios:
+              - name: checkout
+                peak_users: 5
+                load_pattern:
+                  - linear:
+                      to: 100%
+                      over: 10m
+                steps:
+                  - url: http://localhost:8080/login
+                  - url: http://localhost:8080/cart
+              - name: browse
+                peak_users: 2
+                steps:
+                  - url: http://localhost:8080/
+        "#;
+        let load_test = LoadTest::from_config(yaml, Path::new(""), &Default::default(), &|_| {
+            unreachable!("no files are read")
+        })
+        .expect("should be a valid config");
+        load_test
+            .ok_for_loadtest()
+            .expect("should be ok for a load test");
+        assert_eq!(load_test.get_duration(), Duration::from_secs(600));
+        assert_eq!(load_test.scenarios.len(), 2);
+        let checkout = &load_test.scenarios[0];
+        assert_eq!(checkout.name, "checkout");
+        assert_eq!(checkout.peak_users, 5);
+        let tags: Vec<_> = checkout
+            .steps
+            .iter()
+            .map(|s| {
+                let tag = |k: &str| s.tags[k].evaluate_with_star();
+                (tag("_id"), tag("scenario"), tag("step"))
+            })
+            .collect();
+        let expect = vec![
+            ("1".to_string(), "checkout".to_string(), "1".to_string()),
+            ("2".to_string(), "checkout".to_string(), "2".to_string()),
+        ];
+        assert_eq!(tags, expect);
+        // falls back to the global load_pattern
+        let browse = &load_test.scenarios[1];
+        assert_eq!(
+            browse.load_pattern.as_ref().map(LoadPattern::duration),
+            Some(Duration::from_secs(300))
+        );
+
+        let yaml = br#"
+            scenarios:
+              - name: checkout
+                peak_users: 5
+                steps:
+                  - url: http://localhost:8080/login
+                    peak_load: 1hps
+        "#;
+        assert!(
+            LoadTest::from_config(yaml, Path::new(""), &Default::default(), &|_| {
+                unreachable!("no files are read")
+            })
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn load_test_with_map_provider() {
+        let yaml = br#"
+            providers:
+              tokens:
+                map: {}
+              users:
+                list:
+                  - 1
+                  - 2
+            endpoints:
+              - url: http://localhost:8080/login/${users}
+                provides:
+                  tokens:
+                    select:
+                      key: users
+                      value: response.body.token
+              - declare:
+                  user: users
+                  token: lookup(tokens, users)
+                url: http://localhost:8080/${user}?token=${token}
+        "#;
+        let load_test = LoadTest::from_config(yaml, Path::new(""), &Default::default(), &|_| {
+            unreachable!("no files are read")
+        })
+        .expect("should be a valid config");
+        assert!(load_test.endpoints[1]
+            .required_providers
+            .lookups()
+            .any(|(p, _)| p == "tokens"));
+
+        let invalid = [
+            // lookup outside of a declare
+            br#"
+            providers:
+              tokens:
+                map: {}
+            endpoints:
+              - url: http://localhost:8080/${lookup(tokens, 1)}
+                peak_load: 1hps
+            "#
+            .as_ref(),
+            // map provider used as a regular provider
+            br#"
+            providers:
+              tokens:
+                map: {}
+            endpoints:
+              - url: http://localhost:8080/${tokens}
+                peak_load: 1hps
+            "#,
+            // lookup of a provider which isn't a map
+            br#"
+            providers:
+              users:
+                list:
+                  - 1
+            endpoints:
+              - declare:
+                  token: lookup(users, 1)
+                url: http://localhost:8080/${token}
+                peak_load: 1hps
+            "#,
+        ];
+        for (i, yaml) in invalid.iter().enumerate() {
+            assert!(
+                LoadTest::from_config(yaml, Path::new(""), &Default::default(), &|_| {
+                    unreachable!("no files are read")
+                })
+                .is_err(),
+                "failed at index {}",
+                i
+            );
+        }
+    }
+
     #[test]
     fn from_yaml_config_pre_processed() {
         let values = vec![
@@ -1,6 +1,7 @@
 use crate::expression_functions::{
-    Collect, Encode, Entries, Epoch, If, Join, JsonPath, Match, MinMax, Pad, Random, Range, Repeat,
-    Replace,
+    AddDuration, Collect, Collection, Decode, Encode, Entries, Epoch, FormatDate, Hash, Hmac, If,
+    Join, JsonPath, JwtDecode, JwtEncode, Lookup, Match, MinMax, Now, Pad, ParseDate, Random,
+    Range, Repeat, Replace, Uuid,
 };
 use crate::{
     create_marker, json_value_to_string, EndpointProvidesPreProcessed, EndpointProvidesSendOptions,
@@ -10,7 +11,7 @@ use crate::{
 use crate::error::{self, CreatingExpressionError, ExecutingExpressionError};
 
 use ether::{Either, Either3, EitherExt};
-use futures::{stream, Stream, StreamExt, TryStreamExt};
+use futures::{future::BoxFuture, stream, Stream, StreamExt, TryStreamExt};
 use itertools::Itertools;
 use pest::{
     iterators::{Pair, Pairs},
@@ -35,14 +36,25 @@ type ProviderStreamStream<Ar> = Box<
         + 'static,
 >;
 
+/// a function which gets the value stored under a key in a map provider. Resolves to `None` when
+/// the key is missing and the provider does not wait for it
+pub type ProviderLookup =
+    Arc<dyn Fn(String) -> BoxFuture<'static, Option<json::Value>> + Send + Sync>;
+
 pub trait ProviderStream<Ar: Clone + Send + Unpin + 'static> {
     #[allow(clippy::wrong_self_convention)]
     fn into_stream(&self) -> ProviderStreamStream<Ar>;
+
+    /// only map providers support `lookup`
+    fn lookup(&self) -> Option<ProviderLookup> {
+        None
+    }
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct RequiredProviders {
     inner: BTreeMap<String, Marker>,
+    lookups: BTreeMap<String, Marker>,
     special: u16,
     where_special: u16,
     is_where: bool,
@@ -52,6 +64,7 @@ impl RequiredProviders {
     pub fn new() -> Self {
         RequiredProviders {
             inner: BTreeMap::new(),
+            lookups: BTreeMap::new(),
             special: 0,
             where_special: 0,
             is_where: false,
@@ -91,6 +104,11 @@ impl RequiredProviders {
         }
     }
 
+    // the provider is the target of a `lookup` so it must be a map provider
+    pub(super) fn insert_lookup(&mut self, s: String, marker: Marker) {
+        self.lookups.insert(s, marker);
+    }
+
     pub(super) fn remove(&mut self, s: &str) {
         self.inner.remove(s);
     }
@@ -99,6 +117,7 @@ impl RequiredProviders {
         self.special |= other.special;
         self.where_special |= other.special;
         self.inner.extend(other.inner);
+        self.lookups.extend(other.lookups);
     }
 
     pub fn into_inner(self) -> BTreeMap<String, Marker> {
@@ -128,53 +147,110 @@ impl RequiredProviders {
     pub fn iter(&self) -> impl Iterator<Item = (&String, &Marker)> {
         self.inner.iter()
     }
+
+    pub fn lookups(&self) -> impl Iterator<Item = (&String, &Marker)> {
+        self.lookups.iter()
+    }
 }
 
 #[derive(Clone, Debug)]
 pub(super) enum FunctionCall {
+    AddDuration(AddDuration),
     Collect(Collect),
+    Collection(Box<Collection>),
+    Decode(Decode),
     Encode(Encode),
     Entries(Entries),
     Epoch(Epoch),
+    FormatDate(Box<FormatDate>),
+    Hash(Hash),
+    Hmac(Box<Hmac>),
     If(Box<If>),
     Join(Join),
     JsonPath(JsonPath),
+    JwtDecode(JwtDecode),
+    JwtEncode(Box<JwtEncode>),
+    Lookup(Box<Lookup>),
     Match(Box<Match>),
     MinMax(MinMax),
+    Now(Now),
     Pad(Pad),
+    ParseDate(Box<ParseDate>),
     Random(Random),
     Range(Box<Range>),
     Repeat(Repeat),
     Replace(Box<Replace>),
+    Uuid(Uuid),
 }
 
 impl FunctionCall {
     fn new(
         ident: &str,
         args: Vec<ValueOrExpression>,
+        lambda: Option<Lambda>,
         providers: &mut RequiredProviders,
         static_vars: &BTreeMap<String, json::Value>,
         marker: Marker,
     ) -> Result<Either<Self, json::Value>, CreatingExpressionError> {
+        let collection = |function, args| {
+            Collection::new(function, args, lambda.clone(), marker)
+                .map(|c| c.map_a(|c| FunctionCall::Collection(c.into())))
+        };
         let r = match ident {
+            "add_duration" => AddDuration::new(args, marker)?.map_a(FunctionCall::AddDuration),
+            "contains" => collection("contains", args)?,
+            "filter" => collection("filter", args)?,
+            "flatten" => collection("flatten", args)?,
+            "keys" => collection("keys", args)?,
+            "len" => collection("len", args)?,
+            "map" => collection("map", args)?,
+            "merge" => collection("merge", args)?,
+            "slice" => collection("slice", args)?,
+            "sort_by" => collection("sort_by", args)?,
+            "unique" => collection("unique", args)?,
+            "values" => collection("values", args)?,
+            _ if lambda.is_some() => {
+                return Err(CreatingExpressionError::InvalidLambda(
+                    format!("`{}` does not take a lambda", ident),
+                    marker,
+                ))
+            }
             "collect" => Either::A(FunctionCall::Collect(Collect::new(args, marker)?)),
+            "decode" => Decode::new(args, marker)?.map_a(FunctionCall::Decode),
             "encode" => Encode::new(args, marker)?.map_a(FunctionCall::Encode),
             "end_pad" => Pad::new(false, args, marker)?.map_a(FunctionCall::Pad),
             "entries" => Either::A(FunctionCall::Entries(Entries::new(args, marker)?)),
             "epoch" => Either::A(FunctionCall::Epoch(Epoch::new(args, marker)?)),
+            "format_date" => {
+                FormatDate::new(args, marker)?.map_a(|f| FunctionCall::FormatDate(f.into()))
+            }
+            "hash" => Hash::new(args, marker)?.map_a(FunctionCall::Hash),
+            "hmac" => Hmac::new(args, marker)?.map_a(|h| FunctionCall::Hmac(h.into())),
             "if" => If::new(args, marker)?.map_a(|a| FunctionCall::If(a.into())),
             "join" => Join::new(args, marker)?.map_a(FunctionCall::Join),
             "json_path" => {
                 JsonPath::new(args, providers, static_vars, marker)?.map_a(FunctionCall::JsonPath)
             }
+            "jwt_decode" => JwtDecode::new(args, marker)?.map_a(FunctionCall::JwtDecode),
+            "jwt_encode" => {
+                JwtEncode::new(args, marker)?.map_a(|j| FunctionCall::JwtEncode(j.into()))
+            }
+            "lookup" => Either::A(FunctionCall::Lookup(
+                Lookup::new(args, providers, marker)?.into(),
+            )),
             "match" => Match::new(args, marker)?.map_a(|m| FunctionCall::Match(m.into())),
             "max" => MinMax::new(false, args)?.map_a(FunctionCall::MinMax),
             "min" => MinMax::new(true, args)?.map_a(FunctionCall::MinMax),
+            "now" => Either::A(FunctionCall::Now(Now::new(args, marker)?)),
+            "parse_date" => {
+                ParseDate::new(args, marker)?.map_a(|p| FunctionCall::ParseDate(p.into()))
+            }
             "start_pad" => Pad::new(true, args, marker)?.map_a(FunctionCall::Pad),
             "random" => Either::A(FunctionCall::Random(Random::new(args, marker)?)),
             "range" => Either::A(FunctionCall::Range(Range::new(args, marker)?.into())),
             "repeat" => Either::A(FunctionCall::Repeat(Repeat::new(args, marker)?)),
             "replace" => Replace::new(args, marker)?.map_a(|r| FunctionCall::Replace(r.into())),
+            "uuid" => Either::A(FunctionCall::Uuid(Uuid::new(args, marker)?)),
             _ => {
                 return Err(CreatingExpressionError::UnknownFunction(
                     ident.into(),
@@ -192,20 +268,32 @@ impl FunctionCall {
         for_each: Option<&[Cow<'a, json::Value>]>,
     ) -> Result<Cow<'a, json::Value>, ExecutingExpressionError> {
         match self {
+            FunctionCall::AddDuration(a) => a.evaluate(d, no_recoverable_error, for_each),
             FunctionCall::Collect(c) => c.evaluate(d, no_recoverable_error, for_each),
+            FunctionCall::Collection(c) => c.evaluate(d, no_recoverable_error, for_each),
+            FunctionCall::Decode(dec) => dec.evaluate(d, no_recoverable_error, for_each),
             FunctionCall::Encode(e) => e.evaluate(d, no_recoverable_error, for_each),
             FunctionCall::Entries(e) => e.evaluate(d, no_recoverable_error, for_each),
             FunctionCall::Epoch(e) => e.evaluate(),
+            FunctionCall::FormatDate(f) => f.evaluate(d, no_recoverable_error, for_each),
+            FunctionCall::Hash(h) => h.evaluate(d, no_recoverable_error, for_each),
+            FunctionCall::Hmac(h) => h.evaluate(d, no_recoverable_error, for_each),
             FunctionCall::If(i) => i.evaluate(d, no_recoverable_error, for_each),
             FunctionCall::Join(j) => j.evaluate(d, no_recoverable_error, for_each),
             FunctionCall::JsonPath(j) => Ok(j.evaluate(d)),
+            FunctionCall::JwtDecode(j) => j.evaluate(d, no_recoverable_error, for_each),
+            FunctionCall::JwtEncode(j) => j.evaluate(d, no_recoverable_error, for_each),
+            FunctionCall::Lookup(l) => l.evaluate(),
             FunctionCall::Match(m) => m.evaluate(d, no_recoverable_error, for_each),
             FunctionCall::MinMax(m) => m.evaluate(d, no_recoverable_error, for_each),
+            FunctionCall::Now(n) => Ok(n.evaluate()),
             FunctionCall::Pad(p) => p.evaluate(d, no_recoverable_error, for_each),
+            FunctionCall::ParseDate(p) => p.evaluate(d, no_recoverable_error, for_each),
             FunctionCall::Range(r) => r.evaluate(d, no_recoverable_error, for_each),
             FunctionCall::Random(r) => Ok(r.evaluate()),
             FunctionCall::Repeat(r) => Ok(r.evaluate()),
             FunctionCall::Replace(r) => r.evaluate(d, no_recoverable_error, for_each),
+            FunctionCall::Uuid(u) => Ok(u.evaluate()),
         }
     }
 
@@ -263,12 +351,46 @@ impl FunctionCall {
                     no_recoverable_error,
                     for_each,
                 )?)),
-                FunctionCall::Repeat(r) => Either3::C(Either3::C(Either::A(r.evaluate_as_iter()))),
-                FunctionCall::Replace(r) => Either3::C(Either3::C(Either::B(r.evaluate_as_iter(
+                FunctionCall::Lookup(l) => {
+                    Either3::C(Either3::C(Either3::A(iter::once(l.evaluate()?))))
+                }
+                FunctionCall::Hash(h) => Either3::C(Either3::C(Either3::A(h.evaluate_as_iter(
                     d,
                     no_recoverable_error,
                     for_each,
                 )?))),
+                FunctionCall::Hmac(h) => Either3::C(Either3::C(Either3::A(h.evaluate_as_iter(
+                    d,
+                    no_recoverable_error,
+                    for_each,
+                )?))),
+                FunctionCall::JwtDecode(j) => Either3::C(Either3::C(Either3::A(
+                    j.evaluate_as_iter(d, no_recoverable_error, for_each)?,
+                ))),
+                FunctionCall::Collection(c) => Either3::C(Either3::C(Either3::A(
+                    c.evaluate_as_iter(d, no_recoverable_error, for_each)?,
+                ))),
+                FunctionCall::Decode(dec) => Either3::C(Either3::C(Either3::A(
+                    dec.evaluate_as_iter(d, no_recoverable_error, for_each)?,
+                ))),
+                FunctionCall::JwtEncode(j) => Either3::C(Either3::C(Either3::A(
+                    j.evaluate_as_iter(d, no_recoverable_error, for_each)?,
+                ))),
+                FunctionCall::AddDuration(a) => Either3::C(Either3::C(Either3::A(
+                    a.evaluate_as_iter(d, no_recoverable_error, for_each)?,
+                ))),
+                FunctionCall::FormatDate(f) => Either3::C(Either3::C(Either3::A(
+                    f.evaluate_as_iter(d, no_recoverable_error, for_each)?,
+                ))),
+                FunctionCall::Now(n) => Either3::C(Either3::C(Either3::A(n.evaluate_as_iter()))),
+                FunctionCall::ParseDate(p) => Either3::C(Either3::C(Either3::A(
+                    p.evaluate_as_iter(d, no_recoverable_error, for_each)?,
+                ))),
+                FunctionCall::Uuid(u) => Either3::C(Either3::C(Either3::A(u.evaluate_as_iter()))),
+                FunctionCall::Repeat(r) => Either3::C(Either3::C(Either3::B(r.evaluate_as_iter()))),
+                FunctionCall::Replace(r) => Either3::C(Either3::C(Either3::C(
+                    r.evaluate_as_iter(d, no_recoverable_error, for_each)?,
+                ))),
             };
         Ok(r)
     }
@@ -282,20 +404,32 @@ impl FunctionCall {
         no_recoverable_error: bool,
     ) -> impl Stream<Item = Result<(json::Value, Vec<Ar>), ExecutingExpressionError>> + Send {
         match self {
+            FunctionCall::AddDuration(a) => a.into_stream(providers, no_recoverable_error).boxed(),
             FunctionCall::Collect(c) => c.into_stream(providers, no_recoverable_error).boxed(),
+            FunctionCall::Collection(c) => c.into_stream(providers, no_recoverable_error).boxed(),
+            FunctionCall::Decode(d) => d.into_stream(providers, no_recoverable_error).boxed(),
             FunctionCall::Encode(e) => e.into_stream(providers, no_recoverable_error).boxed(),
             FunctionCall::Entries(e) => e.into_stream(providers, no_recoverable_error).boxed(),
             FunctionCall::Epoch(e) => e.into_stream().boxed(),
+            FunctionCall::FormatDate(f) => f.into_stream(providers, no_recoverable_error).boxed(),
+            FunctionCall::Hash(h) => h.into_stream(providers, no_recoverable_error).boxed(),
+            FunctionCall::Hmac(h) => h.into_stream(providers, no_recoverable_error).boxed(),
             FunctionCall::If(i) => i.into_stream(providers, no_recoverable_error).boxed(),
             FunctionCall::Join(j) => j.into_stream(providers, no_recoverable_error).boxed(),
             FunctionCall::JsonPath(j) => j.into_stream(providers).boxed(),
+            FunctionCall::JwtDecode(j) => j.into_stream(providers, no_recoverable_error).boxed(),
+            FunctionCall::JwtEncode(j) => j.into_stream(providers, no_recoverable_error).boxed(),
+            FunctionCall::Lookup(l) => l.into_stream(providers, no_recoverable_error).boxed(),
             FunctionCall::Match(m) => m.into_stream(providers, no_recoverable_error).boxed(),
             FunctionCall::MinMax(m) => m.into_stream(providers, no_recoverable_error).boxed(),
+            FunctionCall::Now(n) => n.into_stream().boxed(),
             FunctionCall::Pad(p) => p.into_stream(providers, no_recoverable_error).boxed(),
+            FunctionCall::ParseDate(p) => p.into_stream(providers, no_recoverable_error).boxed(),
             FunctionCall::Random(r) => r.into_stream().boxed(),
             FunctionCall::Range(r) => r.into_stream(providers, no_recoverable_error).boxed(),
             FunctionCall::Repeat(r) => r.into_stream().boxed(),
             FunctionCall::Replace(r) => r.into_stream(providers, no_recoverable_error).boxed(),
+            FunctionCall::Uuid(u) => u.into_stream().boxed(),
         }
     }
 }
@@ -617,6 +751,23 @@ impl ValueOrExpression {
         ValueOrExpression::from_expression(e)
     }
 
+    // if this is only the name of a provider (or variable), get the name
+    pub(super) fn as_ident(&self) -> Option<&str> {
+        let path = match self {
+            ValueOrExpression::Value(Value::Path(path)) => path,
+            ValueOrExpression::Expression(Expression {
+                not: None,
+                lhs: ExpressionLhs::Value(Value::Path(path)),
+                op: None,
+            }) => path,
+            _ => return None,
+        };
+        match (&path.start, path.rest.is_empty()) {
+            (PathStart::Ident(s), true) => Some(s),
+            _ => None,
+        }
+    }
+
     fn from_expression(e: Expression) -> Result<Self, CreatingExpressionError> {
         let voe = match e.simplify_to_json()? {
             Either::A(v) => ValueOrExpression::Value(Value::Json(v)),
@@ -677,6 +828,28 @@ impl ValueOrExpression {
     }
 }
 
+/// the `item => body` argument taken by functions such as `filter` and `map`. The body is evaluated
+/// once for each item, and the parameter is the only name it can reference
+#[derive(Clone, Debug)]
+pub(super) struct Lambda {
+    param: String,
+    body: ValueOrExpression,
+}
+
+impl Lambda {
+    pub(super) fn call(
+        &self,
+        item: json::Value,
+        no_recoverable_error: bool,
+    ) -> Result<json::Value, ExecutingExpressionError> {
+        let mut d = json::Map::new();
+        d.insert(self.param.clone(), item);
+        self.body
+            .evaluate(Cow::Owned(d.into()), no_recoverable_error, None)
+            .map(Cow::into_owned)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum Value {
     Path(Box<Path>),
@@ -1663,22 +1836,41 @@ fn parse_function_call(
     );
     let mut ident = None;
     let mut args = Vec::new();
+    let mut lambda = None;
     for pair in pair.into_inner() {
         match pair.as_rule() {
             Rule::function_ident => {
                 ident = Some(pair.as_str());
             }
+            Rule::function_arg if lambda.is_some() => {
+                return Err(CreatingExpressionError::InvalidLambda(
+                    "a lambda must be the last argument".into(),
+                    marker,
+                ));
+            }
             Rule::function_arg => {
-                args.push(
-                    parse_expression(
-                        pair.into_inner(),
-                        providers,
-                        static_vars,
-                        no_recoverable_error,
-                        marker,
-                    )
-                    .and_then(ValueOrExpression::from_expression)?,
-                );
+                let mut inner = pair.into_inner();
+                match inner.peek().map(|p| p.as_rule()) {
+                    Some(Rule::lambda) => {
+                        let pair = inner.next().expect("should have a lambda");
+                        lambda = Some(parse_lambda(
+                            pair,
+                            static_vars,
+                            no_recoverable_error,
+                            marker,
+                        )?);
+                    }
+                    _ => args.push(
+                        parse_expression(
+                            inner,
+                            providers,
+                            static_vars,
+                            no_recoverable_error,
+                            marker,
+                        )
+                        .and_then(ValueOrExpression::from_expression)?,
+                    ),
+                }
             }
             r => {
                 unreachable!("Unexpected rule for function call, `{:?}`", r);
@@ -1688,12 +1880,56 @@ fn parse_function_call(
     FunctionCall::new(
         ident.expect("expected to have a function identifier"),
         args,
+        lambda,
         providers,
         static_vars,
         marker,
     )
 }
 
+fn parse_lambda(
+    pair: Pair<'_, Rule>,
+    static_vars: &BTreeMap<String, json::Value>,
+    no_recoverable_error: bool,
+    marker: Marker,
+) -> Result<Lambda, CreatingExpressionError> {
+    log::trace!(
+        "parse_lambda pair: {}, no_recoverable_error: {}, marker: {:?}",
+        pair,
+        no_recoverable_error,
+        marker
+    );
+    let mut inner = pair.into_inner();
+    let param = inner
+        .next()
+        .expect("expected a lambda to have a parameter")
+        .as_str()
+        .to_string();
+    let body = inner.next().expect("expected a lambda to have a body");
+    // the parameter shadows any var with the same name
+    let mut static_vars = static_vars.clone();
+    static_vars.remove(&param);
+    let mut providers = RequiredProviders::new();
+    let body = parse_expression(
+        body.into_inner(),
+        &mut providers,
+        &static_vars,
+        no_recoverable_error,
+        marker,
+    )
+    .and_then(ValueOrExpression::from_expression)?;
+    let only_param = providers.special == 0
+        && providers.lookups.is_empty()
+        && providers.inner.keys().all(|p| *p == param);
+    if !only_param {
+        return Err(CreatingExpressionError::InvalidLambda(
+            format!("the body can only reference the parameter `{}`", param),
+            marker,
+        ));
+    }
+    Ok(Lambda { param, body })
+}
+
 fn parse_indexed_property(
     pair: Pair<'_, Rule>,
     providers: &mut RequiredProviders,
@@ -2169,6 +2405,7 @@ mod tests {
             ),
             (json::json!(r#"stats.rtt"#), None, vec![], STATS),
             (json::json!("join(b.e, '-')"), None, vec!["b"], 0),
+            (json::json!("map(c, d => d.e)"), None, vec!["c"], 0),
             (
                 json::json!({"z": 42, "dees": r#"json_path("c.*.d")"#, "x": "foo"}),
                 None,
@@ -2338,6 +2575,7 @@ mod tests {
             (r#""foo-bar""#, json::json!("foo-bar")),
             ("'foo-bar'", json::json!("foo-bar")),
             ("join(b.e, '-')", json::json!("5-6-7-8")),
+            ("map(c2, c => c.d * 3)", json::json!([3, 6, 9])),
         ];
 
         let mut required_providers = RequiredProviders::new();
@@ -2358,6 +2596,72 @@ mod tests {
         }
     }
 
+    #[test]
+    fn lambdas() {
+        let data = json::json!({
+            "items": [
+                { "id": 4, "status": "active" },
+                { "id": 2, "status": "inactive" },
+                { "id": 3, "status": "active" },
+                { "id": 1, "status": "active" },
+            ],
+            "x": 10,
+        });
+
+        // (select json, expected out data)
+        let check_table = vec![
+            (
+                r#"filter(items, x => x.status == "active")"#,
+                json::json!([
+                    { "id": 4, "status": "active" },
+                    { "id": 3, "status": "active" },
+                    { "id": 1, "status": "active" },
+                ]),
+            ),
+            ("map(items, item => item.id * 2)", json::json!([8, 4, 6, 2])),
+            ("sort_by(items, x => x.id)[0].id", json::json!(1)),
+            (
+                r#"slice(sort_by(map(filter(items, x => x.status == "active"), x => x.id), x => x), 0, 2)"#,
+                json::json!([1, 3]),
+            ),
+            (
+                r#"map(items, x => `${x.id}-${x.status}`)[1]"#,
+                json::json!("2-inactive"),
+            ),
+            ("len(filter(items, x => x.id > 3))", json::json!(1)),
+            (
+                "join(map(filter(items, x => x.id % 2 == 0), x => x.id), ',')",
+                json::json!("4,2"),
+            ),
+        ];
+
+        for (i, (select, expect)) in check_table.into_iter().enumerate() {
+            let data = data.clone();
+            let select = Select::simple(select, Block, None, None, None);
+            check_results(select, data, &[expect], i);
+        }
+
+        let invalid = vec![
+            "filter(items, x => y.status)",
+            "map(items, x => response.status)",
+            "filter(x => x, items)",
+            "join(items, x => x)",
+            "len(items, x => x)",
+            "filter(items)",
+        ];
+        let static_vars = BTreeMap::new();
+        for expr in invalid {
+            let r = ValueOrExpression::new(
+                expr,
+                &mut RequiredProviders::new(),
+                &static_vars,
+                false,
+                create_marker(),
+            );
+            assert!(r.is_err(), "{}", expr);
+        }
+    }
+
     #[test]
     fn r#where() {
         let data = json::json!({
@@ -5,9 +5,14 @@ use futures::{
 };
 
 use ether::EitherExt;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use std::{
     collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 
@@ -57,6 +62,237 @@ impl LinearSegment {
     }
 }
 
+#[cfg_attr(debug_assertions, derive(Debug, PartialEq))]
+#[derive(Clone)]
+// holds the rate for the entire segment
+struct StepSegment {
+    hps: f64,
+    duration: Duration,
+}
+
+impl StepSegment {
+    fn new(hps: f64, duration: Duration) -> Self {
+        StepSegment { hps, duration }
+    }
+
+    fn get_hps_at(&self, _time: Duration) -> f64 {
+        match self.hps.is_finite() {
+            true => self.hps,
+            false => 0.0,
+        }
+    }
+}
+
+#[cfg_attr(debug_assertions, derive(Debug, PartialEq))]
+#[derive(Clone)]
+// for this curve
+// x = number of seconds elapsed
+// y = number of hits per second
+// y = start * e^(k * x)
+struct ExponentialSegment {
+    start: f64,
+    k: f64,
+    duration: Duration,
+}
+
+impl ExponentialSegment {
+    fn new(start_hps: f64, end_hps: f64, duration: Duration) -> Self {
+        let seconds = duration.as_secs_f64();
+        // an exponential curve never reaches zero, so a zero end point is raised to one hit
+        // over the duration of the segment (unless scaling from 0 to 0)
+        let min_y = seconds.recip();
+        let (start, end) = if start_hps <= 0.0 && end_hps <= 0.0 {
+            (0.0, 0.0)
+        } else {
+            (start_hps.max(min_y), end_hps.max(min_y))
+        };
+        let k = if start == 0.0 {
+            0.0
+        } else {
+            (end / start).ln() / seconds
+        };
+
+        ExponentialSegment { start, k, duration }
+    }
+
+    fn get_hps_at(&self, time: Duration) -> f64 {
+        let x = time.as_secs_f64();
+        let y = self.start * (self.k * x).exp();
+        match y.is_finite() {
+            true => y,
+            false => 0.0,
+        }
+    }
+}
+
+#[cfg_attr(debug_assertions, derive(Debug, PartialEq))]
+#[derive(Clone)]
+// for this curve
+// x = number of seconds elapsed
+// y = number of hits per second
+// y = m * x + b + amplitude * sin(2 * pi * (x + phase) / period)
+struct SineSegment {
+    m: f64,
+    b: f64,
+    amplitude: f64,
+    period: f64,
+    phase: f64,
+    min_y: f64,
+    duration: Duration,
+}
+
+impl SineSegment {
+    fn new(
+        start_hps: f64,
+        end_hps: f64,
+        amplitude_hps: f64,
+        period: Duration,
+        duration: Duration,
+    ) -> Self {
+        let m = (end_hps - start_hps) / duration.as_secs_f64();
+        let period = period.as_secs_f64();
+        // when the wave dips to (or below) zero, keep sending at least one hit per period
+        // so the segment doesn't stall through the trough
+        let min_y = if start_hps <= 0.0 && end_hps <= 0.0 && amplitude_hps == 0.0 {
+            0.0
+        } else {
+            period.recip()
+        };
+
+        SineSegment {
+            m,
+            b: start_hps,
+            amplitude: amplitude_hps,
+            period,
+            phase: 0.0,
+            min_y,
+            duration,
+        }
+    }
+
+    fn get_hps_at(&self, time: Duration) -> f64 {
+        let x = time.as_secs_f64();
+        let wave = (2.0 * std::f64::consts::PI * (x + self.phase) / self.period).sin();
+        let mut y = self.m * x + self.b + self.amplitude * wave;
+        if y < self.min_y {
+            y = self.min_y;
+        }
+        match y.is_finite() {
+            true => y,
+            false => 0.0,
+        }
+    }
+}
+
+// a handle which sets the rate of a controlled segment while the stream is running
+#[derive(Clone, Debug)]
+pub struct RateControl(Arc<AtomicU64>);
+
+impl RateControl {
+    pub fn new(rate: PerX) -> Self {
+        RateControl(Arc::new(AtomicU64::new(rate.as_per_second().to_bits())))
+    }
+
+    pub fn set(&self, rate: PerX) {
+        self.0
+            .store(rate.as_per_second().to_bits(), Ordering::Relaxed);
+    }
+
+    // the current rate in hits per second
+    pub fn hits_per_second(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(debug_assertions)]
+impl PartialEq for RateControl {
+    fn eq(&self, rhs: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &rhs.0)
+    }
+}
+
+#[cfg_attr(debug_assertions, derive(Debug, PartialEq))]
+#[derive(Clone)]
+// the rate is read from a `RateControl` each time the next hit is scheduled
+struct ControlledSegment {
+    control: RateControl,
+    duration: Duration,
+}
+
+impl ControlledSegment {
+    fn get_hps_at(&self, _time: Duration) -> f64 {
+        let hps = self.control.hits_per_second();
+        match hps.is_finite() {
+            true => hps,
+            false => 0.0,
+        }
+    }
+}
+
+#[cfg_attr(debug_assertions, derive(Debug, PartialEq))]
+#[derive(Clone)]
+enum Segment {
+    Linear(LinearSegment),
+    Step(StepSegment),
+    Exponential(ExponentialSegment),
+    Sine(SineSegment),
+    Controlled(ControlledSegment),
+}
+
+impl Segment {
+    fn duration(&self) -> Duration {
+        match self {
+            Segment::Linear(s) => s.duration,
+            Segment::Step(s) => s.duration,
+            Segment::Exponential(s) => s.duration,
+            Segment::Sine(s) => s.duration,
+            Segment::Controlled(s) => s.duration,
+        }
+    }
+
+    fn get_hps_at(&self, time: Duration) -> f64 {
+        match self {
+            Segment::Linear(s) => s.get_hps_at(time),
+            Segment::Step(s) => s.get_hps_at(time),
+            Segment::Exponential(s) => s.get_hps_at(time),
+            Segment::Sine(s) => s.get_hps_at(time),
+            Segment::Controlled(s) => s.get_hps_at(time),
+        }
+    }
+
+    // creates a segment which picks up at `time` and follows the same curve for the
+    // remainder of this segment
+    fn remainder(&self, time: Duration) -> Segment {
+        let duration = self.duration() - time;
+        match self {
+            Segment::Linear(s) => {
+                let start_hps = s.get_hps_at(time);
+                let end_hps = s.get_hps_at(s.duration);
+                Segment::Linear(LinearSegment::new(start_hps, end_hps, duration))
+            }
+            Segment::Step(s) => Segment::Step(StepSegment::new(s.hps, duration)),
+            Segment::Exponential(s) => Segment::Exponential(ExponentialSegment {
+                start: s.get_hps_at(time),
+                k: s.k,
+                duration,
+            }),
+            Segment::Sine(s) => {
+                let x = time.as_secs_f64();
+                Segment::Sine(SineSegment {
+                    b: s.m * x + s.b,
+                    phase: s.phase + x,
+                    duration,
+                    ..s.clone()
+                })
+            }
+            Segment::Controlled(s) => Segment::Controlled(ControlledSegment {
+                control: s.control.clone(),
+                duration,
+            }),
+        }
+    }
+}
+
 // stored as per minute
 pub struct PerX(f64);
 
@@ -74,16 +310,59 @@ impl PerX {
     }
 }
 
+// how the time between hits is distributed around the target hits per second
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Arrival {
+    // evenly spaced hits
+    #[default]
+    Constant,
+    // exponentially distributed wait times, as seen from many independent clients
+    Poisson,
+    // wait times spread evenly between 0 and double the constant wait time
+    Uniform,
+}
+
+struct ArrivalSampler {
+    arrival: Arrival,
+    rng: StdRng,
+}
+
+impl ArrivalSampler {
+    fn new(arrival: Arrival, seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        ArrivalSampler { arrival, rng }
+    }
+
+    // the amount of time to wait until the next hit for the given hits per second
+    fn wait_time(&mut self, target_hits_per_second: f64) -> Duration {
+        let mean = target_hits_per_second.recip();
+        let secs = match self.arrival {
+            Arrival::Constant => mean,
+            Arrival::Poisson => {
+                // `gen` is in the range [0, 1) so `1 - u` is never zero
+                let u: f64 = self.rng.gen();
+                -(1.0 - u).ln() * mean
+            }
+            Arrival::Uniform => self.rng.gen::<f64>() * 2.0 * mean,
+        };
+        Duration::from_secs_f64(secs)
+    }
+}
+
 // each ModInterval segment is evaluated independenty at [t0, tDuration]
 // `x_offset` helps to keep track of the progression within the entire ModInterval
 struct ModIntervalStreamState {
     end_time: Instant,
-    current_segment: LinearSegment,
-    segments: VecDeque<LinearSegment>,
+    current_segment: Segment,
+    segments: VecDeque<Segment>,
     start_time: Instant,
     x_offset: Duration,
     next_start: Instant,
     following_start: Option<Instant>,
+    arrival: ArrivalSampler,
 }
 
 impl ModIntervalStreamState {
@@ -91,13 +370,13 @@ impl ModIntervalStreamState {
         let mut wait_time = time - self.start_time - self.x_offset;
 
         // when we've reached the end of the current segment, get the next one
-        if wait_time >= self.current_segment.duration {
+        if wait_time >= self.current_segment.duration() {
             let segment = match self.segments.pop_front() {
                 Some(s) => s,
                 None => return None,
             };
-            wait_time -= self.current_segment.duration;
-            self.x_offset += self.current_segment.duration;
+            wait_time -= self.current_segment.duration();
+            self.x_offset += self.current_segment.duration();
             self.current_segment = segment;
         }
 
@@ -111,11 +390,11 @@ impl ModIntervalStreamState {
                 return None;
             } else {
                 // there are more segments, return remaining time for this segment
-                self.current_segment.duration - wait_time
+                self.current_segment.duration() - wait_time
             }
         } else {
             // convert from hits per second to the amount of time we should wait
-            Duration::from_secs_f64(target_hits_per_second.recip())
+            self.arrival.wait_time(target_hits_per_second)
         };
         let ret = time + wait_time;
         if ret <= self.end_time {
@@ -129,8 +408,10 @@ impl ModIntervalStreamState {
 #[cfg_attr(debug_assertions, derive(Debug, PartialEq))]
 #[derive(Clone)]
 pub struct ModInterval {
-    segments: VecDeque<LinearSegment>,
+    segments: VecDeque<Segment>,
     duration: Duration,
+    arrival: Arrival,
+    seed: Option<u64>,
 }
 
 impl ModInterval {
@@ -139,9 +420,18 @@ impl ModInterval {
         ModInterval {
             segments: VecDeque::new(),
             duration: Default::default(),
+            arrival: Default::default(),
+            seed: None,
         }
     }
 
+    // sets how hits are spread around the target rate. When a `seed` is provided the same
+    // wait times are generated on every run
+    pub fn set_arrival(&mut self, arrival: Arrival, seed: Option<u64>) {
+        self.arrival = arrival;
+        self.seed = seed;
+    }
+
     pub fn transition_from(&mut self, mut old: Self, at: Duration, mut over: Duration) {
         // if either mod_interval is shorter than the `at` point, return
         if old.duration < at || self.duration < at {
@@ -155,14 +445,14 @@ impl ModInterval {
         fn find_segment(
             mod_interval: &mut ModInterval,
             time: Duration,
-        ) -> (usize, &mut LinearSegment, Duration) {
+        ) -> (usize, &mut Segment, Duration) {
             let mut x_offset = Default::default();
             let last_i = mod_interval.segments.len() - 1;
             for (i, segment) in mod_interval.segments.iter_mut().enumerate() {
-                if segment.duration + x_offset > time || i == last_i {
+                if segment.duration() + x_offset > time || i == last_i {
                     return (i, segment, x_offset);
                 }
-                x_offset += segment.duration;
+                x_offset += segment.duration();
             }
             unreachable!("segment should be long enough");
         }
@@ -179,22 +469,20 @@ impl ModInterval {
         // adjust the segment following transition to be the correct size
         let segment_x = (at + over) - x_offset;
         let transition_end_hps = post_transition_segment.get_hps_at(segment_x);
-        let after_transition_segment_duration = post_transition_segment.duration - segment_x;
+        let after_transition_segment_duration = post_transition_segment.duration() - segment_x;
         if after_transition_segment_duration == Default::default() {
             self.segments.pop_back();
         } else {
-            let post_transition_segment_end_hps =
-                post_transition_segment.get_hps_at(post_transition_segment.duration);
-            *post_transition_segment = LinearSegment::new(
-                transition_end_hps,
-                post_transition_segment_end_hps,
-                after_transition_segment_duration,
-            );
+            *post_transition_segment = post_transition_segment.remainder(segment_x);
         }
 
         // remove segments upto where the transition goes
         self.segments.drain(..i);
-        let transition_segment = LinearSegment::new(transition_start_hps, transition_end_hps, over);
+        let transition_segment = Segment::Linear(LinearSegment::new(
+            transition_start_hps,
+            transition_end_hps,
+            over,
+        ));
 
         // add the transition
         self.segments.push_front(transition_segment);
@@ -204,11 +492,68 @@ impl ModInterval {
     }
 
     pub fn append_segment(&mut self, start: PerX, duration: Duration, end: PerX) {
-        self.duration += duration;
-
         let start_hps = start.as_per_second();
         let end_hps = end.as_per_second();
         let segment = LinearSegment::new(start_hps, end_hps, duration);
+        self.push_segment(Segment::Linear(segment));
+    }
+
+    pub fn append_step_segment(&mut self, rate: PerX, duration: Duration) {
+        let segment = StepSegment::new(rate.as_per_second(), duration);
+        self.push_segment(Segment::Step(segment));
+    }
+
+    pub fn append_exponential_segment(&mut self, start: PerX, duration: Duration, end: PerX) {
+        let start_hps = start.as_per_second();
+        let end_hps = end.as_per_second();
+        let segment = ExponentialSegment::new(start_hps, end_hps, duration);
+        self.push_segment(Segment::Exponential(segment));
+    }
+
+    pub fn append_sine_segment(
+        &mut self,
+        start: PerX,
+        duration: Duration,
+        end: PerX,
+        amplitude: PerX,
+        period: Duration,
+    ) {
+        let segment = SineSegment::new(
+            start.as_per_second(),
+            end.as_per_second(),
+            amplitude.as_per_second(),
+            period,
+            duration,
+        );
+        self.push_segment(Segment::Sine(segment));
+    }
+
+    // appends a segment whose rate follows the returned `RateControl`. A rate of zero ends the
+    // stream, so the controller should keep the rate above zero until it is done
+    pub fn append_controlled_segment(&mut self, start: PerX, duration: Duration) -> RateControl {
+        let control = RateControl::new(start);
+        let segment = ControlledSegment {
+            control: control.clone(),
+            duration,
+        };
+        self.push_segment(Segment::Controlled(segment));
+        control
+    }
+
+    // the target hits per second at the given time, or `None` once the segments have ended
+    pub fn hits_per_second_at(&self, time: Duration) -> Option<f64> {
+        let mut x_offset = Duration::default();
+        for segment in &self.segments {
+            if time < x_offset + segment.duration() {
+                return Some(segment.get_hps_at(time - x_offset));
+            }
+            x_offset += segment.duration();
+        }
+        None
+    }
+
+    fn push_segment(&mut self, segment: Segment) {
+        self.duration += segment.duration();
         self.segments.push_back(segment);
     }
 
@@ -219,6 +564,7 @@ impl ModInterval {
         let mut state = None;
         let mut segments = self.segments;
         let duration = self.duration;
+        let (arrival, seed) = (self.arrival, self.seed);
         stream::unfold((), move |_| {
             let now = time::now();
             if state.is_none() {
@@ -237,6 +583,7 @@ impl ModInterval {
                     x_offset: Default::default(),
                     next_start: now,
                     following_start: None,
+                    arrival: ArrivalSampler::new(arrival, seed),
                 };
                 s.following_start = s.calculate_next_start(now);
                 state = Some(s);
@@ -614,4 +961,228 @@ mod tests {
 
         assert_eq!(new_mod_interval, expect_mod_interval);
     }
+
+    #[test]
+    fn step_segment_holds_rate() {
+        let mut mod_interval = ModInterval::new();
+        mod_interval.append_step_segment(PerX::second(10.0), Duration::from_secs(30));
+        assert_eq!(mod_interval.duration, Duration::from_secs(30));
+
+        let segment = &mod_interval.segments[0];
+        for secs in [0, 15, 30].iter() {
+            let hps = segment.get_hps_at(Duration::from_secs(*secs));
+            assert!((hps - 10.0).abs() < f64::EPSILON, "hps was {}", hps);
+        }
+    }
+
+    #[test]
+    fn exponential_segment_curve() {
+        let mut mod_interval = ModInterval::new();
+        mod_interval.append_exponential_segment(
+            PerX::second(1.0),
+            Duration::from_secs(30),
+            PerX::second(100.0),
+        );
+
+        let segment = &mod_interval.segments[0];
+        let checks = [(0, 1.0), (15, 10.0), (30, 100.0)];
+        for (secs, expect) in checks.iter() {
+            let hps = segment.get_hps_at(Duration::from_secs(*secs));
+            assert!((hps - expect).abs() < 1e-9, "at {}s hps was {}", secs, hps);
+        }
+
+        // scaling from zero starts at one hit over the duration of the segment
+        let segment = ExponentialSegment::new(0.0, 10.0, Duration::from_secs(10));
+        assert!((segment.get_hps_at(Duration::from_secs(0)) - 0.1).abs() < 1e-9);
+        assert!((segment.get_hps_at(Duration::from_secs(10)) - 10.0).abs() < 1e-9);
+
+        let segment = ExponentialSegment::new(0.0, 0.0, Duration::from_secs(10));
+        assert_eq!(segment.get_hps_at(Duration::from_secs(5)), 0.0);
+    }
+
+    #[test]
+    fn sine_segment_curve() {
+        let mut mod_interval = ModInterval::new();
+        mod_interval.append_sine_segment(
+            PerX::second(20.0),
+            Duration::from_secs(60),
+            PerX::second(20.0),
+            PerX::second(10.0),
+            Duration::from_secs(20),
+        );
+
+        let segment = &mod_interval.segments[0];
+        let checks = [(0, 20.0), (5, 30.0), (10, 20.0), (15, 10.0), (20, 20.0)];
+        for (secs, expect) in checks.iter() {
+            let hps = segment.get_hps_at(Duration::from_secs(*secs));
+            assert!((hps - expect).abs() < 1e-9, "at {}s hps was {}", secs, hps);
+        }
+
+        // the trough of the wave never drops below one hit per period
+        let segment = SineSegment::new(
+            5.0,
+            5.0,
+            10.0,
+            Duration::from_secs(20),
+            Duration::from_secs(60),
+        );
+        let hps = segment.get_hps_at(Duration::from_secs(15));
+        assert!((hps - 0.05).abs() < 1e-9, "hps was {}", hps);
+    }
+
+    #[test]
+    fn remainder_follows_curve() {
+        let segments = [
+            Segment::Exponential(ExponentialSegment::new(1.0, 100.0, Duration::from_secs(30))),
+            Segment::Sine(SineSegment::new(
+                10.0,
+                40.0,
+                5.0,
+                Duration::from_secs(7),
+                Duration::from_secs(30),
+            )),
+            Segment::Step(StepSegment::new(3.0, Duration::from_secs(30))),
+        ];
+        for (i, segment) in segments.iter().enumerate() {
+            let at = Duration::from_secs(12);
+            let remainder = segment.remainder(at);
+            assert_eq!(remainder.duration(), Duration::from_secs(18));
+            for secs in 0..=18 {
+                let offset = Duration::from_secs(secs);
+                let left = remainder.get_hps_at(offset);
+                let right = segment.get_hps_at(at + offset);
+                assert!(
+                    (left - right).abs() < 1e-9,
+                    "segment at index {} differed at {}s. left: {} right: {}",
+                    i,
+                    secs,
+                    left,
+                    right
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn transition_into_step_works() {
+        let mut old_mod_interval = ModInterval::new();
+        old_mod_interval.append_segment(
+            PerX::second(0.0),
+            Duration::from_secs(60),
+            PerX::second(30.0),
+        );
+
+        let mut new_mod_interval = ModInterval::new();
+        new_mod_interval.append_step_segment(PerX::second(10.0), Duration::from_secs(30));
+        new_mod_interval.append_step_segment(PerX::second(50.0), Duration::from_secs(30));
+
+        new_mod_interval.transition_from(
+            old_mod_interval,
+            Duration::from_secs(30),
+            Duration::from_secs(15),
+        );
+
+        let mut expect_mod_interval = ModInterval::new();
+        expect_mod_interval.append_segment(
+            PerX::second(15.0),
+            Duration::from_secs(15),
+            PerX::second(50.0),
+        );
+        expect_mod_interval.append_step_segment(PerX::second(50.0), Duration::from_secs(15));
+
+        assert_eq!(new_mod_interval, expect_mod_interval);
+    }
+
+    #[test]
+    fn hits_per_second_at_follows_segments() {
+        let mut mi = ModInterval::new();
+        mi.append_segment(
+            PerX::second(0.0),
+            Duration::from_secs(10),
+            PerX::second(10.0),
+        );
+        mi.append_step_segment(PerX::second(4.0), Duration::from_secs(10));
+
+        let rate_at = |secs| mi.hits_per_second_at(Duration::from_secs(secs));
+        // linear segments never quite reach zero
+        assert!(rate_at(0).unwrap() < 1.0);
+        assert_eq!(rate_at(5), Some(5.0));
+        assert_eq!(rate_at(15), Some(4.0));
+        assert_eq!(rate_at(20), None);
+    }
+
+    #[test]
+    fn controlled_segment_follows_rate_control() {
+        let mut mi = ModInterval::new();
+        let control = mi.append_controlled_segment(PerX::second(10.0), Duration::from_secs(60));
+
+        let rate_at = |secs| mi.hits_per_second_at(Duration::from_secs(secs));
+        assert_eq!(rate_at(0), Some(10.0));
+        control.set(PerX::minute(30.0));
+        assert_eq!(rate_at(30), Some(0.5));
+        assert_eq!(rate_at(60), None);
+
+        control.set(PerX::second(20.0));
+        let waits: Vec<_> = block_on_stream(Box::pin(mi.into_stream(None)))
+            .take(5)
+            .filter_map(|(instant, next_instant)| next_instant.map(|i| i - instant))
+            .collect();
+        assert_eq!(waits, vec![Duration::from_millis(50); 5]);
+    }
+
+    fn collect_waits(arrival: Arrival, seed: u64) -> Vec<Duration> {
+        let mut mod_interval = ModInterval::new();
+        mod_interval.append_step_segment(PerX::second(10.0), Duration::from_secs(60));
+        mod_interval.set_arrival(arrival, Some(seed));
+        let stream = Box::pin(mod_interval.into_stream(None));
+        block_on_stream(stream)
+            .filter_map(|(instant, next_instant)| next_instant.map(|i| i - instant))
+            .collect()
+    }
+
+    #[test]
+    fn constant_arrival_is_evenly_spaced() {
+        let waits = collect_waits(Arrival::Constant, 1);
+        assert!(!waits.is_empty());
+        for wait in waits {
+            assert_eq!(wait, Duration::from_millis(100));
+        }
+    }
+
+    #[test]
+    fn random_arrivals_are_reproducible() {
+        for arrival in [Arrival::Poisson, Arrival::Uniform].iter() {
+            let waits = collect_waits(*arrival, 42);
+            assert_eq!(waits, collect_waits(*arrival, 42), "{:?}", arrival);
+            assert_ne!(waits, collect_waits(*arrival, 43), "{:?}", arrival);
+        }
+    }
+
+    #[test]
+    fn random_arrivals_average_to_target_rate() {
+        for arrival in [Arrival::Poisson, Arrival::Uniform].iter() {
+            let mut sampler = ArrivalSampler::new(*arrival, Some(7));
+            let samples = 100_000;
+            let mut max = Duration::from_secs(0);
+            let total: f64 = (0..samples)
+                .map(|_| {
+                    let wait = sampler.wait_time(10.0);
+                    max = max.max(wait);
+                    wait.as_secs_f64()
+                })
+                .sum();
+            let mean = total / samples as f64;
+            assert!(
+                (mean - 0.1).abs() < 0.002,
+                "{:?} mean was {}",
+                arrival,
+                mean
+            );
+            if let Arrival::Uniform = arrival {
+                assert!(max <= Duration::from_millis(200));
+            } else {
+                assert!(max > Duration::from_millis(200));
+            }
+        }
+    }
 }
@@ -1,12 +1,12 @@
 use std::{convert::TryInto, fs::create_dir_all, io, path::PathBuf, time::UNIX_EPOCH};
 
-use clap::{crate_version, App, AppSettings, Arg};
-use config::duration_from_string;
+use clap::{crate_version, App, AppSettings, Arg, ArgMatches};
+use config::{duration_from_string, hits_per_from_string, HitsPer};
 use futures::channel::mpsc as futures_channel;
 use log::{debug, info};
 use pewpew::{
-    create_run, ExecConfig, RunConfig, RunOutputFormat, StatsFileFormat, TryConfig, TryFilter,
-    TryRunFormat,
+    create_run, ExecConfig, RunConfig, RunOutputFormat, SearchConfig, StatsFileFormat, TryConfig,
+    TryFilter, TryRunFormat,
 };
 use regex::Regex;
 use tokio::runtime;
@@ -24,6 +24,23 @@ fn main() {
     }
     let filter_reg = Regex::new("^(.*?)(!=|=)(.*)").expect("is a valid regex");
     let filter_reg2 = filter_reg.clone();
+    let filter_reg3 = filter_reg.clone();
+    let duration_validator = |s: &str| match duration_from_string(s.into()) {
+        Ok(_) => Ok(()),
+        Err(_) => Err("must be a duration, e.g. `30s`".to_string()),
+    };
+    let rate_validator = |s: &str| match hits_per_from_string(s.into()) {
+        Ok(_) => Ok(()),
+        Err(_) => Err("must be a rate, e.g. `10hps` or `600hpm`".to_string()),
+    };
+    let number_validator = |s: &str| match s.parse::<f64>() {
+        Ok(n) if n > 0.0 => Ok(()),
+        _ => Err("must be a positive number".to_string()),
+    };
+    let percent_validator = |s: &str| match s.parse::<f64>() {
+        Ok(n) if n > 0.0 && n <= 100.0 => Ok(()),
+        _ => Err("must be a number greater than 0 and at most 100".to_string()),
+    };
     let matches = App::new("pewpew")
         .about("The HTTP load test tool https://familysearch.github.io/pewpew")
         .version(crate_version!())
@@ -50,6 +67,7 @@ fn main() {
                     .long("stats-file")
                     .help("Specify the filename for the stats file")
                     .value_name("STATS_FILE")
+                    .allow_invalid_utf8(true)
             )
             .arg(
                 Arg::new("start-at")
@@ -71,6 +89,7 @@ fn main() {
                     .number_of_values(1)
                     .help("Directory to store results and logs")
                     .value_name("DIRECTORY")
+                    .allow_invalid_utf8(true)
             )
             .arg(
                 Arg::new("stats-file-format")
@@ -151,6 +170,132 @@ fn main() {
                     .required(true),
             )
         )
+        .subcommand(App::new("search")
+            .about("Searches for the highest rate an endpoint can sustain while meeting an SLO")
+            .arg(
+                Arg::new("output-format")
+                    .short('f')
+                    .long("output-format")
+                    .help("Formatting for stats printed to stderr")
+                    .value_name("FORMAT")
+                    .possible_value("human")
+                    .possible_value("json")
+                    .default_value("human")
+            )
+            .arg(
+                Arg::new("stats-file")
+                    .short('o')
+                    .long("stats-file")
+                    .help("Specify the filename for the stats file")
+                    .value_name("STATS_FILE")
+                    .allow_invalid_utf8(true)
+            )
+            .arg(
+                Arg::new("results-directory")
+                    .short('d')
+                    .long("results-directory")
+                    .number_of_values(1)
+                    .help("Directory to store results and logs")
+                    .value_name("DIRECTORY")
+                    .allow_invalid_utf8(true)
+            )
+            .arg(
+                Arg::new("include")
+                    .short('i')
+                    .long("include")
+                    .long_help(r#"Filter which endpoint's rate is searched. Filters work based on an endpoint's tags. Filters are specified in the format "key=value" where "*" is a wildcard. Exactly one endpoint with a peak_load must match the filters"#)
+                    .multiple_occurrences(true)
+                    .number_of_values(1)
+                    .required(true)
+                    .validator(move |s| {
+                        if filter_reg3.is_match(s) {
+                            Ok(())
+                        } else {
+                            Err("include filters must be in the format `tag=value` or `tag!=value`".to_string())
+                        }
+                    })
+                    .value_name("INCLUDE")
+            )
+            .arg(
+                Arg::new("latency")
+                    .short('l')
+                    .long("latency")
+                    .help("The highest response time allowed at the percentile")
+                    .value_name("DURATION")
+                    .required(true)
+                    .validator(duration_validator)
+            )
+            .arg(
+                Arg::new("percentile")
+                    .short('p')
+                    .long("percentile")
+                    .help("The response time percentile checked against the latency")
+                    .value_name("PERCENTILE")
+                    .default_value("99")
+                    .validator(percent_validator)
+            )
+            .arg(
+                Arg::new("error-rate")
+                    .short('e')
+                    .long("error-rate")
+                    .help("The highest percent of requests allowed to fail")
+                    .value_name("PERCENT")
+                    .default_value("1")
+                    .validator(percent_validator)
+            )
+            .arg(
+                Arg::new("start-rate")
+                    .long("start-rate")
+                    .help("The rate the search starts at")
+                    .value_name("RATE")
+                    .default_value("1hps")
+                    .validator(rate_validator)
+            )
+            .arg(
+                Arg::new("max-rate")
+                    .long("max-rate")
+                    .help("The highest rate the search will try")
+                    .value_name("RATE")
+                    .validator(rate_validator)
+            )
+            .arg(
+                Arg::new("step-duration")
+                    .long("step-duration")
+                    .help("How long each rate is measured")
+                    .value_name("DURATION")
+                    .default_value("30s")
+                    .validator(duration_validator)
+            )
+            .arg(
+                Arg::new("settle-time")
+                    .long("settle-time")
+                    .help("How long to wait after changing the rate before measuring")
+                    .value_name("DURATION")
+                    .default_value("5s")
+                    .validator(duration_validator)
+            )
+            .arg(
+                Arg::new("precision")
+                    .long("precision")
+                    .help("Stop once the highest passing and lowest failing rates are within this percent")
+                    .value_name("PERCENT")
+                    .default_value("5")
+                    .validator(number_validator)
+            )
+            .arg(
+                Arg::new("max-duration")
+                    .long("max-duration")
+                    .help("The longest the search will run")
+                    .value_name("DURATION")
+                    .default_value("1h")
+                    .validator(duration_validator)
+            )
+            .arg(
+                Arg::new("CONFIG")
+                    .help("Load test config file to use")
+                    .required(true),
+            )
+        )
         .get_matches();
 
     let (ctrl_c_tx, ctrlc_channel) = futures_channel::unbounded();
@@ -160,65 +305,11 @@ fn main() {
     });
 
     let cli_config = if let Some(matches) = matches.subcommand_matches("run") {
-        let config_file: PathBuf = matches
-            .value_of("CONFIG")
-            .expect("should have CONFIG param")
-            .into();
-        let results_dir = matches.value_of_os("results-directory").map(|d| {
-            create_dir_all(d).unwrap();
-            PathBuf::from(d)
-        });
-        let output_format = TryInto::try_into(
-            matches
-                .value_of("output-format")
-                .expect("should have output_format cli arg"),
-        )
-        .expect("output_format cli arg unrecognized");
-        match output_format {
-            RunOutputFormat::Json => {
-                json_env_logger::init();
-                json_env_logger::panic_hook();
-            }
-            _ => env_logger::init(),
-        }
-        info!("log::max_level() = {}", log::max_level());
-        let stats_file = matches
-            .value_of_os("stats-file")
-            .map(PathBuf::from)
-            .unwrap_or_else(|| {
-                let start_sec = UNIX_EPOCH
-                    .elapsed()
-                    .map(|d| d.as_secs())
-                    .unwrap_or_default();
-                let test_name = config_file.file_stem().and_then(std::ffi::OsStr::to_str);
-                let file = if let Some(test_name) = test_name {
-                    format!("stats-{}-{}.json", test_name, start_sec)
-                } else {
-                    format!("stats-{}.json", start_sec)
-                };
-                PathBuf::from(file)
-            });
-        let stats_file = if let Some(results_dir) = &results_dir {
-            let mut file = results_dir.clone();
-            file.push(stats_file);
-            file
-        } else {
-            stats_file
-        };
-        let stats_file_format = StatsFileFormat::Json;
-        let watch_config_file = matches.is_present("watch");
-        let start_at = matches
+        let mut run_config = create_run_config(matches);
+        run_config.watch_config_file = matches.is_present("watch");
+        run_config.start_at = matches
             .value_of("start-at")
             .map(|s| duration_from_string(s.to_string()).expect("start_at should match pattern"));
-        let run_config = RunConfig {
-            config_file,
-            output_format,
-            results_dir,
-            start_at,
-            stats_file,
-            stats_file_format,
-            watch_config_file,
-        };
         debug!("{{\"run_config\":{}}}", run_config);
         ExecConfig::Run(run_config)
     } else if let Some(matches) = matches.subcommand_matches("try") {
@@ -235,33 +326,9 @@ fn main() {
             }
             _ => None,
         };
-        let filters = matches.values_of("include").map(|v| {
-            v.map(|s| {
-                let captures = filter_reg
-                    .captures(s)
-                    .expect("include cli arg should match regex");
-                let left = captures
-                    .get(1)
-                    .expect("include arg should match regex")
-                    .as_str()
-                    .to_string();
-                let right = captures
-                    .get(3)
-                    .expect("include arg should match regex")
-                    .as_str()
-                    .to_string();
-                let comparator = captures
-                    .get(2)
-                    .expect("include arg should match regex")
-                    .as_str();
-                match comparator {
-                    "=" => TryFilter::Eq(left, right),
-                    "!=" => TryFilter::Ne(left, right),
-                    _ => unreachable!(),
-                }
-            })
-            .collect()
-        });
+        let filters = matches
+            .values_of("include")
+            .map(|v| parse_filters(v, &filter_reg));
         let format: TryRunFormat = matches
             .value_of("format")
             .and_then(|f| f.try_into().ok())
@@ -285,6 +352,51 @@ fn main() {
         };
         debug!("{{\"try_config\":{}}}", try_config);
         ExecConfig::Try(try_config)
+    } else if let Some(matches) = matches.subcommand_matches("search") {
+        let run_config = create_run_config(matches);
+        let filters = parse_filters(
+            matches
+                .values_of("include")
+                .expect("should have include cli arg"),
+            &filter_reg,
+        );
+        let duration_arg = |name| {
+            let s = matches
+                .value_of(name)
+                .expect("should have duration cli arg");
+            duration_from_string(s.to_string()).expect("duration cli arg should match pattern")
+        };
+        let number_arg = |name| {
+            matches
+                .value_of(name)
+                .and_then(|s| s.parse().ok())
+                .expect("should have number cli arg")
+        };
+        let rate_arg = |name| {
+            matches.value_of(name).map(|s| {
+                match hits_per_from_string(s.to_string())
+                    .expect("rate cli arg should match pattern")
+                {
+                    HitsPer::Second(n) => n as f64,
+                    HitsPer::Minute(n) => n as f64 / 60.0,
+                }
+            })
+        };
+        let search_config = SearchConfig {
+            run_config,
+            filters,
+            latency: duration_arg("latency"),
+            percentile: number_arg("percentile"),
+            error_rate: number_arg("error-rate"),
+            start_rate: rate_arg("start-rate").expect("should have start-rate cli arg"),
+            max_rate: rate_arg("max-rate"),
+            step_duration: duration_arg("step-duration"),
+            settle_time: duration_arg("settle-time"),
+            precision: number_arg("precision"),
+            max_duration: duration_arg("max-duration"),
+        };
+        debug!("{{\"search_config\":{}}}", search_config);
+        ExecConfig::Search(search_config)
     } else {
         unreachable!();
     };
@@ -308,3 +420,91 @@ fn main() {
         std::process::exit(1)
     }
 }
+
+// creates the config shared by the `run` and `search` subcommands and sets up logging
+fn create_run_config(matches: &ArgMatches) -> RunConfig {
+    let config_file: PathBuf = matches
+        .value_of("CONFIG")
+        .expect("should have CONFIG param")
+        .into();
+    let results_dir = matches.value_of_os("results-directory").map(|d| {
+        create_dir_all(d).unwrap();
+        PathBuf::from(d)
+    });
+    let output_format = TryInto::try_into(
+        matches
+            .value_of("output-format")
+            .expect("should have output_format cli arg"),
+    )
+    .expect("output_format cli arg unrecognized");
+    match output_format {
+        RunOutputFormat::Json => {
+            json_env_logger::init();
+            json_env_logger::panic_hook();
+        }
+        _ => env_logger::init(),
+    }
+    info!("log::max_level() = {}", log::max_level());
+    let stats_file = matches
+        .value_of_os("stats-file")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            let start_sec = UNIX_EPOCH
+                .elapsed()
+                .map(|d| d.as_secs())
+                .unwrap_or_default();
+            let test_name = config_file.file_stem().and_then(std::ffi::OsStr::to_str);
+            let file = if let Some(test_name) = test_name {
+                format!("stats-{}-{}.json", test_name, start_sec)
+            } else {
+                format!("stats-{}.json", start_sec)
+            };
+            PathBuf::from(file)
+        });
+    let stats_file = if let Some(results_dir) = &results_dir {
+        let mut file = results_dir.clone();
+        file.push(stats_file);
+        file
+    } else {
+        stats_file
+    };
+    RunConfig {
+        config_file,
+        output_format,
+        results_dir,
+        start_at: None,
+        stats_file,
+        stats_file_format: StatsFileFormat::Json,
+        watch_config_file: false,
+    }
+}
+
+// parses `--include` filters in the format `key=value` or `key!=value`
+fn parse_filters<'a, I: Iterator<Item = &'a str>>(values: I, filter_reg: &Regex) -> Vec<TryFilter> {
+    values
+        .map(|s| {
+            let captures = filter_reg
+                .captures(s)
+                .expect("include cli arg should match regex");
+            let left = captures
+                .get(1)
+                .expect("include arg should match regex")
+                .as_str()
+                .to_string();
+            let right = captures
+                .get(3)
+                .expect("include arg should match regex")
+                .as_str()
+                .to_string();
+            let comparator = captures
+                .get(2)
+                .expect("include arg should match regex")
+                .as_str();
+            match comparator {
+                "=" => TryFilter::Eq(left, right),
+                "!=" => TryFilter::Ne(left, right),
+                _ => unreachable!(),
+            }
+        })
+        .collect()
+}
@@ -9,6 +9,7 @@ pub enum RecoverableError {
     BodyErr(Arc<dyn StdError + Send + Sync>),
     ConnectionErr(SystemTime, Arc<dyn StdError + Send + Sync>),
     ExecutingExpression(Box<config::ExecutingExpressionError>),
+    InvalidThinkTime(String),
     Timeout(SystemTime),
 }
 
@@ -22,6 +23,7 @@ impl RecoverableError {
             ExecutingExpression(..) => 3,
             Timeout(_) => 4,
             ProviderDelay(_) => 5,
+            InvalidThinkTime(_) => 6,
         }
     }
 }
@@ -32,6 +34,7 @@ impl fmt::Display for RecoverableError {
             BodyErr(e) => write!(f, "body error: {}", e),
             ConnectionErr(_, e) => write!(f, "connection error: `{}`", e),
             ExecutingExpression(e) => e.fmt(f),
+            InvalidThinkTime(d) => write!(f, "invalid duration `{}` for think_time", d),
             ProviderDelay(p) => write!(f, "endpoint was delayed waiting for provider `{}`", p),
             Timeout(..) => write!(f, "request timed out"),
         }
@@ -47,6 +50,7 @@ pub enum TestError {
     Config(Box<config::Error>),
     FileReading(String, Arc<std::io::Error>),
     InvalidConfigFilePath(PathBuf),
+    InvalidSearchTarget(usize),
     InvalidUrl(String),
     Recoverable(RecoverableError),
     RequestBuilderErr(Arc<HttpError>),
@@ -73,6 +77,11 @@ impl fmt::Display for TestError {
             InvalidConfigFilePath(p) => {
                 write!(f, "could not find config file at path `{}`", p.display())
             }
+            InvalidSearchTarget(n) => write!(
+                f,
+                "search filters must match exactly one endpoint with a `peak_load`, but matched {}",
+                n
+            ),
             InvalidUrl(u) => write!(f, "invalid url `{}`", u),
             Recoverable(r) => write!(f, "recoverable error: {}", r),
             RequestBuilderErr(e) => write!(f, "error creating request: {}", e),
@@ -103,7 +112,11 @@ impl StdError for TestError {
 impl From<config::Error> for TestError {
     fn from(ce: config::Error) -> Self {
         if let config::Error::ExpressionErr(config::CreatingExpressionError::Executing(
-            e @ config::ExecutingExpressionError::IndexingIntoJson(..),
+            e @ (config::ExecutingExpressionError::DecodingFailed(..)
+            | config::ExecutingExpressionError::IndexingIntoJson(..)
+            | config::ExecutingExpressionError::InvalidDate(..)
+            | config::ExecutingExpressionError::InvalidJwt(..)
+            | config::ExecutingExpressionError::MissingMapKey(..)),
         )) = ce
         {
             Recoverable(ExecutingExpression(e.into()))
@@ -121,7 +134,7 @@ impl From<config::CreatingExpressionError> for TestError {
 
 impl From<config::ExecutingExpressionError> for TestError {
     fn from(e: config::ExecutingExpressionError) -> Self {
-        Config(Box::new(e.into()))
+        config::Error::from(e).into()
     }
 }
 
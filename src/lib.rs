@@ -7,13 +7,15 @@ mod error;
 mod line_writer;
 mod providers;
 mod request;
+mod search;
 mod stats;
 mod util;
 
 use crate::error::TestError;
 use crate::stats::{create_stats_channel, create_try_run_stats_channel, StatsMessage};
 
-use ether::Either;
+use chrono::{Local, TimeZone};
+use ether::{Either, EitherExt};
 use futures::{
     channel::mpsc::{
         Sender as FCSender, UnboundedReceiver as FCUnboundedReceiver,
@@ -30,7 +32,7 @@ use hyper_tls::HttpsConnector;
 use itertools::Itertools;
 use line_writer::{blocking_writer, MsgType};
 use log::{debug, error, info, warn};
-use mod_interval::{ModInterval, PerX};
+use mod_interval::{Arrival, ModInterval, PerX};
 use native_tls::TlsConnector;
 use serde::Serialize;
 use serde_json as json;
@@ -52,7 +54,7 @@ use std::{
     pin::Pin,
     sync::Arc,
     task::Poll,
-    time::{Duration, Instant},
+    time::{Duration, Instant, UNIX_EPOCH},
 };
 
 struct Endpoints {
@@ -246,10 +248,36 @@ impl fmt::Display for TryConfig {
     }
 }
 
+#[derive(Clone, Serialize)]
+pub struct SearchConfig {
+    pub run_config: RunConfig,
+    pub filters: Vec<TryFilter>,
+    // the SLO each rate is checked against
+    pub latency: Duration,
+    pub percentile: f64,
+    pub error_rate: f64,
+    // rates are in hits per second
+    pub start_rate: f64,
+    pub max_rate: Option<f64>,
+    pub step_duration: Duration,
+    pub settle_time: Duration,
+    // the search stops once the highest passing and lowest failing rates are within this
+    // percent of each other
+    pub precision: f64,
+    pub max_duration: Duration,
+}
+
+impl fmt::Display for SearchConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", serde_json::to_string(&self).unwrap_or_default())
+    }
+}
+
 #[derive(Serialize)]
 pub enum ExecConfig {
     Run(RunConfig),
     Try(TryConfig),
+    Search(SearchConfig),
 }
 
 impl fmt::Display for ExecConfig {
@@ -263,6 +291,7 @@ impl ExecConfig {
         match self {
             ExecConfig::Run(r) => &r.config_file,
             ExecConfig::Try(t) => &t.config_file,
+            ExecConfig::Search(s) => &s.run_config.config_file,
         }
     }
 
@@ -270,6 +299,7 @@ impl ExecConfig {
         match self {
             ExecConfig::Run(r) => r.output_format,
             ExecConfig::Try(_) => RunOutputFormat::Human,
+            ExecConfig::Search(s) => s.run_config.output_format,
         }
     }
 }
@@ -348,13 +378,25 @@ async fn _create_run(
     log::trace!("env_vars={:?}", env_vars.clone());
     let output_format = exec_config.get_output_format();
     let config_file_path = exec_config.get_config_file().clone();
-    let mut config =
-        config::LoadTest::from_config(&config_bytes, exec_config.get_config_file(), &env_vars)?;
+    let mut config = config::LoadTest::from_config(
+        &config_bytes,
+        exec_config.get_config_file(),
+        &env_vars,
+        &|path| std::fs::read_to_string(path),
+    )?;
     debug!("config::LoadTest::from_config finished");
+    // a search runs like a full load test with one endpoint's rate driven by the search
+    let (exec_config, search_config) = match exec_config {
+        ExecConfig::Search(s) => (ExecConfig::Run(s.run_config.clone()), Some(s)),
+        exec_config => (exec_config, None),
+    };
+    // the providers of a full run, kept so any `persist`ed providers can be saved when it ends
+    let mut run_providers = None;
     let test_runner = match exec_config {
         ExecConfig::Try(t) => {
             create_try_run_future(config, t, test_ended_tx.clone(), stdout, stderr).map(Either::A)
         }
+        ExecConfig::Search(_) => unreachable!("search should be converted to a run"),
         ExecConfig::Run(r) => {
             let config_providers = mem::take(&mut config.providers);
             // build and register the providers
@@ -374,6 +416,7 @@ async fn _create_run(
             )?;
 
             let providers = Arc::new(providers);
+            run_providers = Some(providers.clone());
 
             if r.watch_config_file {
                 create_config_watcher(
@@ -394,6 +437,7 @@ async fn _create_run(
             create_load_test_future(
                 config,
                 r,
+                search_config,
                 test_ended_tx,
                 providers,
                 stats_tx,
@@ -410,7 +454,10 @@ async fn _create_run(
             let mut test_result = Ok(TestEndReason::Completed);
             while let Some(v) = test_ended_rx.next().await {
                 match v {
-                    Ok(Ok(TestEndReason::ConfigUpdate(_))) => continue,
+                    Ok(Ok(TestEndReason::ConfigUpdate(providers))) => {
+                        run_providers = Some(providers);
+                        continue;
+                    }
                     Ok(v) => {
                         test_result = v;
                     }
@@ -418,6 +465,14 @@ async fn _create_run(
                 };
                 break;
             }
+            if let Some(providers) = run_providers {
+                for provider in providers.values() {
+                    provider.wait_for_auto_returns().await;
+                    if let Err(e) = provider.persist_values() {
+                        test_result = Err(e);
+                    }
+                }
+            }
             test_result
         }
         Err(e) => Err(e),
@@ -582,7 +637,12 @@ fn create_config_watcher(
                 continue;
             }
 
-            let config = config::LoadTest::from_config(&config_bytes, &config_file_path, &env_vars);
+            let config = config::LoadTest::from_config(
+                &config_bytes,
+                &config_file_path,
+                &env_vars,
+                &|path| std::fs::read_to_string(path),
+            );
             let mut config = match config {
                 Ok(m) => m,
                 Err(e) => {
@@ -604,6 +664,27 @@ fn create_config_watcher(
 
             let config_providers = mem::take(&mut config.providers);
 
+            // save the values of any `persist`ed providers which are about to be replaced so the
+            // new providers start with them. The current test is still running, so values held by
+            // its requests are returned to the old provider rather than saved
+            for (name, p) in &previous_config_providers {
+                if config_providers.get(name) == Some(p) {
+                    continue;
+                }
+                if let Some(Err(e)) = previous_providers.get(name).map(|p| p.persist_values()) {
+                    let msg = match output_format {
+                        RunOutputFormat::Human => {
+                            format!("\n{} {}\n", Paint::yellow("Could not persist provider"), e)
+                        }
+                        RunOutputFormat::Json => {
+                            let json = json::json!({"type": "warn", "msg": format!("{} {}", "could not persist provider", e)});
+                            format!("{}\n", json)
+                        }
+                    };
+                    let _ = block_on(stderr.send(MsgType::Other(msg)));
+                }
+            }
+
             // build and register the providers
             let providers = get_providers_from_config(
                 &config_providers,
@@ -659,6 +740,7 @@ fn create_config_watcher(
             let f = create_load_test_future(
                 config,
                 run_config,
+                None,
                 test_ended_tx.clone(),
                 providers,
                 stats_tx.clone(),
@@ -691,6 +773,55 @@ fn create_config_watcher(
     });
 }
 
+// creates a function which checks whether a set of tags matches any of the filters. When there
+// are no filters everything matches
+fn create_filter_fn(filters: Vec<TryFilter>) -> impl Fn(&BTreeMap<String, String>) -> bool {
+    let filters: Vec<_> = filters
+        .into_iter()
+        .map(|try_filter| {
+            let (is_eq, key, right) = match try_filter {
+                TryFilter::Eq(key, right) => (true, key, right),
+                TryFilter::Ne(key, right) => (false, key, right),
+            };
+            let right = right.split('*').map(regex::escape).join(".*?");
+            let right = format!("^{}$", right);
+            (
+                is_eq,
+                key,
+                regex::Regex::new(&right).expect("filter should be a valid regex"),
+            )
+        })
+        .collect();
+    move |tags: &BTreeMap<String, String>| -> bool {
+        filters.is_empty()
+            || filters.iter().any(|(is_eq, key, regex)| {
+                let check = tags
+                    .get(key)
+                    .map(|left| regex.is_match(left))
+                    .unwrap_or(false);
+                if *is_eq {
+                    check
+                } else {
+                    !check
+                }
+            })
+    }
+}
+
+// evaluates the tags which don't reference any providers
+fn get_static_tags(
+    tags: &BTreeMap<String, config::Template>,
+) -> Result<BTreeMap<String, String>, TestError> {
+    tags.iter()
+        .filter(|(_, v)| v.is_simple())
+        .map(|(k, v)| {
+            v.evaluate(Cow::Owned(json::Value::Null), None)
+                .map(|v| (k.clone(), v))
+                .map_err(Into::into)
+        })
+        .collect()
+}
+
 fn create_try_run_future(
     mut config: config::LoadTest,
     try_config: TryConfig,
@@ -750,38 +881,7 @@ fn create_try_run_future(
     )?;
 
     // setup "filters" which decide which endpoints are included in this try run
-    let filters: Vec<_> = try_config
-        .filters
-        .unwrap_or_default()
-        .into_iter()
-        .map(|try_filter| {
-            let (is_eq, key, right) = match try_filter {
-                TryFilter::Eq(key, right) => (true, key, right),
-                TryFilter::Ne(key, right) => (false, key, right),
-            };
-            let right = right.split('*').map(regex::escape).join(".*?");
-            let right = format!("^{}$", right);
-            (
-                is_eq,
-                key,
-                regex::Regex::new(&right).expect("filter should be a valid regex"),
-            )
-        })
-        .collect();
-    let filter_fn = move |tags: &BTreeMap<String, String>| -> bool {
-        filters.is_empty()
-            || filters.iter().any(|(is_eq, key, regex)| {
-                let check = tags
-                    .get(key)
-                    .map(|left| regex.is_match(left))
-                    .unwrap_or(false);
-                if *is_eq {
-                    check
-                } else {
-                    !check
-                }
-            })
-    };
+    let filter_fn = create_filter_fn(try_config.filters.unwrap_or_default());
 
     // create the loggers
     let loggers = get_loggers_from_config(
@@ -812,20 +912,7 @@ fn create_try_run_future(
             .collect::<BTreeSet<_>>();
         endpoint.on_demand = true;
 
-        let static_tags = endpoint
-            .tags
-            .iter()
-            .filter_map(|(k, v)| {
-                if v.is_simple() {
-                    let r = v
-                        .evaluate(Cow::Owned(json::Value::Null), None)
-                        .map(|v| (k.clone(), v));
-                    Some(r)
-                } else {
-                    None
-                }
-            })
-            .collect::<Result<_, _>>()?;
+        let static_tags = get_static_tags(&endpoint.tags)?;
 
         let builder = request::EndpointBuilder::new(endpoint, None);
         endpoints.append(static_tags, builder, provides_set, required_providers);
@@ -844,9 +931,35 @@ fn create_try_run_future(
         loggers,
         providers: providers.into(),
         stats_tx,
+        rate_limiters: Default::default(),
     };
 
-    let endpoint_calls = endpoints.build(filter_fn, &mut builder_ctx, &response_providers)?;
+    // a scenario is included when any of its steps pass the filters, and makes a single pass
+    let scenarios = config
+        .scenarios
+        .into_iter()
+        .map(|scenario| {
+            let mut included = false;
+            for step in &scenario.steps {
+                included |= filter_fn(&get_static_tags(&step.tags)?);
+            }
+            Ok::<_, TestError>((included, scenario))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut endpoint_calls: Vec<_> = endpoints
+        .build(filter_fn, &mut builder_ctx, &response_providers)?
+        .into_iter()
+        .map(EitherExt::a)
+        .collect();
+    for (included, scenario) in scenarios {
+        if included {
+            let scenario = request::ScenarioBuilder::new(scenario, None, None)
+                .build(&mut builder_ctx)
+                .into_future();
+            endpoint_calls.push(scenario.boxed().b());
+        }
+    }
 
     let mut test_ended_rx = BroadcastStream::new(test_ended_tx.subscribe());
     let mut left = try_join_all(endpoint_calls).map(move |r| {
@@ -863,9 +976,11 @@ fn create_try_run_future(
     Ok(f)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn create_load_test_future(
-    config: config::LoadTest,
+    mut config: config::LoadTest,
     run_config: RunConfig,
+    search_config: Option<SearchConfig>,
     test_ended_tx: broadcast::Sender<Result<TestEndReason, TestError>>,
     providers: Arc<BTreeMap<String, providers::Provider>>,
     stats_tx: FCUnboundedSender<StatsMessage>,
@@ -875,9 +990,68 @@ fn create_load_test_future(
     debug!("create_load_test_future start");
     config.ok_for_loadtest()?;
 
-    let mut duration = config.get_duration();
-    if let Some(t) = run_config.start_at {
-        duration = duration.checked_sub(t).unwrap_or_default();
+    // load_patterns with a schedule start wherever the clock is (which keeps them aligned across
+    // restarts), while the others start at `start_at`
+    let since_epoch = UNIX_EPOCH.elapsed().unwrap_or_default();
+    // the offset can change during the test, such as for daylight saving
+    let local_offset = |secs| Local.timestamp(secs, 0).offset().local_minus_utc();
+    let mut duration = Duration::default();
+    let mut align = |load_pattern: &mut Option<config::LoadPattern>| {
+        let load_pattern = load_pattern.as_mut()?;
+        let start_at = load_pattern
+            .align_to_clock(since_epoch, &local_offset)
+            .or(run_config.start_at);
+        let remaining = load_pattern
+            .duration()
+            .checked_sub(start_at.unwrap_or_default())
+            .unwrap_or_default();
+        duration = duration.max(remaining);
+        start_at
+    };
+    let endpoint_start_ats: Vec<_> = config
+        .endpoints
+        .iter_mut()
+        .map(|e| align(&mut e.load_pattern))
+        .collect();
+    let scenario_start_ats: Vec<_> = config
+        .scenarios
+        .iter_mut()
+        .map(|s| align(&mut s.load_pattern))
+        .collect();
+
+    // the warmup is measured from the start of the test, so when the test is restarted part way
+    // through (or the config is reloaded) only what is left of it applies
+    let elapsed = run_config.start_at.unwrap_or_default();
+    let global_warmup = config.config.general.warmup;
+    let steps = config.scenarios.iter_mut().flat_map(|s| s.steps.iter_mut());
+    for endpoint in config.endpoints.iter_mut().chain(steps) {
+        endpoint.warmup = endpoint
+            .warmup
+            .or(global_warmup)
+            .and_then(|w| w.checked_sub(elapsed))
+            .filter(|w| *w > Duration::default());
+    }
+
+    // find the endpoint whose rate is driven by the search
+    let search_target = match &search_config {
+        Some(search_config) => {
+            let filter_fn = create_filter_fn(search_config.filters.clone());
+            let mut matches = Vec::new();
+            for (i, endpoint) in config.endpoints.iter().enumerate() {
+                if endpoint.peak_load.is_some() && filter_fn(&get_static_tags(&endpoint.tags)?) {
+                    matches.push(i);
+                }
+            }
+            match matches.as_slice() {
+                [i] => Some(*i),
+                _ => return Err(TestError::InvalidSearchTarget(matches.len())),
+            }
+        }
+        None => None,
+    };
+    let mut rate_control = None;
+    if let Some(search_config) = &search_config {
+        duration = search_config.max_duration;
     }
 
     let config_config = config.config;
@@ -891,12 +1065,17 @@ fn create_load_test_future(
         &stderr,
     )?;
 
+    let arrival_seed = config_config.general.arrival_seed;
+    let global_arrival = config_config.general.arrival;
+
     // create the endpoints
     #[allow(clippy::needless_collect)]
     let builders: Vec<_> = config
         .endpoints
         .into_iter()
-        .map(|mut endpoint| {
+        .zip(endpoint_start_ats)
+        .enumerate()
+        .map(|(i, (mut endpoint, mut start_at))| {
             let mut mod_interval: Option<
                 Pin<Box<dyn Stream<Item = (Instant, Option<Instant>)> + Send>>,
             > = None;
@@ -904,30 +1083,55 @@ fn create_load_test_future(
             if let (Some(peak_load), Some(load_pattern)) =
                 (endpoint.peak_load.as_ref(), endpoint.load_pattern.take())
             {
-                let mut mod_interval2 = ModInterval::new();
-                let pieces = match load_pattern {
-                    config::LoadPattern::Linear(l) => l.pieces,
+                let mut mod_interval2 = match (&search_config, search_target) {
+                    // the searched endpoint follows the rate set by the search instead of its
+                    // load_pattern
+                    (Some(search_config), Some(target)) if target == i => {
+                        start_at = None;
+                        let mut mod_interval = ModInterval::new();
+                        let control = mod_interval.append_controlled_segment(
+                            PerX::second(search_config.start_rate),
+                            search_config.max_duration,
+                        );
+                        rate_control = Some(control);
+                        mod_interval
+                    }
+                    _ => load_pattern_to_mod_interval(load_pattern, |percent| match peak_load {
+                        config::HitsPer::Minute(m) => PerX::minute(percent * *m as f64),
+                        config::HitsPer::Second(s) => PerX::second(percent * *s as f64),
+                    }),
                 };
-                for piece in pieces {
-                    let (start, end) = match peak_load {
-                        config::HitsPer::Minute(m) => (
-                            PerX::minute(piece.start_percent * *m as f64),
-                            PerX::minute(piece.end_percent * *m as f64),
-                        ),
-                        config::HitsPer::Second(s) => (
-                            PerX::second(piece.start_percent * *s as f64),
-                            PerX::second(piece.end_percent * *s as f64),
-                        ),
-                    };
-                    mod_interval2.append_segment(start, piece.duration, end);
-                }
-                mod_interval = Some(Box::pin(mod_interval2.into_stream(run_config.start_at)));
+                let arrival = match endpoint.arrival.unwrap_or(global_arrival) {
+                    config::Arrival::Constant => Arrival::Constant,
+                    config::Arrival::Poisson => Arrival::Poisson,
+                    config::Arrival::Uniform => Arrival::Uniform,
+                };
+                // give each endpoint its own sequence so they don't fire in lock step
+                let seed = arrival_seed.map(|s| s.wrapping_add(i as u64));
+                mod_interval2.set_arrival(arrival, seed);
+                mod_interval = Some(Box::pin(mod_interval2.into_stream(start_at)));
             }
 
             request::EndpointBuilder::new(endpoint, mod_interval)
         })
         .collect();
 
+    // create the scenarios, where the "hits per second" of the load_pattern is the number of users
+    let scenario_builders: Vec<_> = config
+        .scenarios
+        .into_iter()
+        .zip(scenario_start_ats)
+        .map(|(mut scenario, start_at)| {
+            let peak_users = scenario.peak_users as f64;
+            let users = scenario.load_pattern.take().map(|load_pattern| {
+                load_pattern_to_mod_interval(load_pattern, |percent| {
+                    PerX::second(percent * peak_users)
+                })
+            });
+            request::ScenarioBuilder::new(scenario, users, start_at)
+        })
+        .collect();
+
     let client = create_http_client(config_config.client.keepalive)?;
 
     let mut builder_ctx = request::BuilderContext {
@@ -937,13 +1141,32 @@ fn create_load_test_future(
         loggers,
         providers,
         stats_tx: stats_tx.clone(),
+        rate_limiters: Default::default(),
     };
 
-    let endpoint_calls = builders
+    let endpoint_calls: Vec<_> = builders
+        .into_iter()
+        .map(|builder| builder.build(&mut builder_ctx).into_future().a())
+        .collect();
+    let scenario_calls = scenario_builders
         .into_iter()
-        .map(move |builder| builder.build(&mut builder_ctx).into_future());
+        .map(|builder| builder.build(&mut builder_ctx).into_future().boxed().b());
+    let endpoint_calls = endpoint_calls.into_iter().chain(scenario_calls);
 
     let _ = stats_tx.unbounded_send(StatsMessage::Start(duration));
+    if let (Some(search_config), Some(control), Some(target)) =
+        (search_config, rate_control, search_target)
+    {
+        debug!("create_load_test_future tokio::spawn search");
+        tokio::spawn(search::create_search_future(
+            control,
+            target.to_string(),
+            search_config,
+            stats_tx.clone(),
+            stdout.clone(),
+            test_ended_tx.clone(),
+        ));
+    }
     let mut f = try_join_all(endpoint_calls);
     let mut test_timeout = Delay::new(duration);
     let mut test_ended_rx = BroadcastStream::new(test_ended_tx.subscribe());
@@ -968,6 +1191,38 @@ fn create_load_test_future(
     Ok(f)
 }
 
+fn load_pattern_to_mod_interval<F>(load_pattern: config::LoadPattern, per_x: F) -> ModInterval
+where
+    F: Fn(f64) -> PerX,
+{
+    let mut mod_interval = ModInterval::new();
+    let pieces = match load_pattern {
+        config::LoadPattern::Linear(l) => l.pieces,
+    };
+    for piece in pieces {
+        let start = per_x(piece.start_percent);
+        let end = per_x(piece.end_percent);
+        match piece.shape {
+            config::SegmentShape::Linear => mod_interval.append_segment(start, piece.duration, end),
+            config::SegmentShape::Step => mod_interval.append_step_segment(end, piece.duration),
+            config::SegmentShape::Exponential => {
+                mod_interval.append_exponential_segment(start, piece.duration, end)
+            }
+            config::SegmentShape::Sine {
+                amplitude_percent,
+                period,
+            } => mod_interval.append_sine_segment(
+                start,
+                piece.duration,
+                end,
+                per_x(amplitude_percent),
+                period,
+            ),
+        }
+    }
+    mod_interval
+}
+
 pub(crate) fn create_http_client(
     keepalive: Duration,
 ) -> Result<
@@ -1013,10 +1268,44 @@ fn get_providers_from_config(
                         template.buffer = config::Limit::Dynamic(auto_size);
                     }
                 }
+                if let Some(persist) = &mut template.persist {
+                    util::tweak_path(persist, config_path);
+                }
                 response_providers.insert(name.clone());
-                providers::response(template, name)
+                providers::response(template, name)?
             }
             config::Provider::List(values) => providers::list(values.clone(), name),
+            config::Provider::Generate(generate) => providers::generate(generate, name),
+            config::Provider::Map(map) => {
+                response_providers.insert(name.clone());
+                providers::map(map, name)
+            }
+            config::Provider::Sequence(mut template) => {
+                if let Some(persist) = &mut template.persist {
+                    util::tweak_path(persist, config_path);
+                }
+                providers::sequence(template, name)?
+            }
+            config::Provider::Files(mut template) => {
+                // the auto_buffer_start_size is not the default
+                if auto_size != default_buffer_size {
+                    if let config::Limit::Dynamic(_) = &template.buffer {
+                        template.buffer = config::Limit::Dynamic(auto_size);
+                    }
+                }
+                util::tweak_path(&mut template.path, config_path);
+                providers::files(template, name)?
+            }
+            config::Provider::Sqlite(mut template) => {
+                // the auto_buffer_start_size is not the default
+                if auto_size != default_buffer_size {
+                    if let config::Limit::Dynamic(_) = &template.buffer {
+                        template.buffer = config::Limit::Dynamic(auto_size);
+                    }
+                }
+                util::tweak_path(&mut template.path, config_path);
+                providers::sqlite(template, test_ended_tx.clone(), name)?
+            }
         };
         providers.insert(name.clone(), provider);
     }
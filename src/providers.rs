@@ -1,8 +1,12 @@
 mod csv_reader;
 mod json_reader;
 mod line_reader;
+mod sqlite_reader;
 
-use self::{csv_reader::CsvReader, json_reader::JsonReader, line_reader::LineReader};
+use self::{
+    csv_reader::CsvReader, json_reader::JsonReader, line_reader::LineReader,
+    sqlite_reader::SqliteReader,
+};
 
 use crate::error::TestError;
 use crate::line_writer::MsgType;
@@ -13,22 +17,31 @@ use ether::Either3;
 use futures::{
     channel::mpsc::{self, channel, Sender as FCSender},
     executor::block_on,
+    future,
     sink::{Sink, SinkExt},
-    stream, Stream, StreamExt, TryStreamExt,
+    stream, FutureExt, Stream, StreamExt, TryStreamExt,
 };
-use log::debug;
+use futures_timer::Delay;
+use log::{debug, warn};
+use rand::seq::SliceRandom;
 use serde_json as json;
-use tokio::{sync::broadcast, task::spawn_blocking};
+use tokio::{
+    sync::{broadcast, Notify},
+    task::spawn_blocking,
+};
 
 use std::{
     borrow::Cow,
-    io,
+    collections::BTreeMap,
+    io, iter,
+    path::{Path, PathBuf},
     pin::Pin,
     sync::{
-        atomic::{AtomicIsize, Ordering},
-        Arc,
+        atomic::{AtomicIsize, AtomicUsize, Ordering},
+        Arc, Mutex,
     },
     task::{Context, Poll},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 #[derive(Clone)]
@@ -37,6 +50,11 @@ pub struct Provider {
     pub rx: channel::Receiver<json::Value>,
     pub tx: channel::Sender<json::Value>,
     pub on_demand: channel::OnDemandReceiver<json::Value>,
+    pub persist: Option<String>,
+    pub lookup: Option<config::ProviderLookup>,
+    pub last_issued: Option<Arc<Mutex<Option<i64>>>>,
+    // the number of values taken from the provider which requests have yet to auto return
+    pub auto_returns_held: Arc<AtomicUsize>,
 }
 
 impl Provider {
@@ -48,10 +66,118 @@ impl Provider {
         Provider {
             auto_return,
             on_demand: channel::OnDemandReceiver::new(&rx),
+            persist: None,
+            lookup: None,
+            last_issued: None,
+            auto_returns_held: Default::default(),
             rx,
             tx,
         }
     }
+
+    // wait until the values held by requests have been auto returned, so they are persisted too.
+    // When a test ends its requests are dropped, which returns their values right away, so this
+    // only gives up if something is holding on to them
+    pub async fn wait_for_auto_returns(&self) {
+        if self.persist.is_none() {
+            return;
+        }
+        let give_up = Instant::now() + Duration::from_secs(5);
+        while self.auto_returns_held.load(Ordering::Acquire) > 0 && Instant::now() < give_up {
+            Delay::new(Duration::from_millis(10)).await;
+        }
+    }
+
+    // write the values currently in the provider to its `persist` file so they can be loaded by a
+    // later run. Each line is a json object with the `value` and, if it has one, when it
+    // `expires` in milliseconds since the epoch. The provider is left empty
+    pub fn persist_values(&self) -> Result<(), TestError> {
+        let path = match &self.persist {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        // a sequence provider only saves the last number it issued so the next run continues from it
+        if let Some(last_issued) = &self.last_issued {
+            let last_issued = *last_issued.lock().expect("sequence lock");
+            return match last_issued {
+                Some(n) => {
+                    debug!("Provider::persist_values writing {} to {}", n, path);
+                    std::fs::write(path, format!("{}\n", n))
+                        .map_err(|e| TestError::WritingToFile(path.clone(), e.into()))
+                }
+                None => Ok(()),
+            };
+        }
+        let values = self.rx.drain();
+        debug!(
+            "Provider::persist_values writing {} values to {}",
+            values.len(),
+            path
+        );
+        let mut contents = String::new();
+        for (value, expires) in values {
+            let mut line = json::json!({ "value": value });
+            if let Some(expires) = expires {
+                let expires = SystemTime::now() + expires.saturating_duration_since(Instant::now());
+                let expires = expires.duration_since(UNIX_EPOCH).unwrap_or_default();
+                line["expires"] = (expires.as_millis() as u64).into();
+            }
+            contents.push_str(&line.to_string());
+            contents.push('\n');
+        }
+        std::fs::write(path, contents).map_err(|e| TestError::WritingToFile(path.clone(), e.into()))
+    }
+}
+
+// read the json lines previously written by `Provider::persist_values`. A missing file is treated
+// as having no values so the first run can create it
+fn read_persisted_values(path: &str) -> Result<Vec<json::Value>, TestError> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(TestError::FileReading(path.into(), e.into())),
+    };
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            json::from_str(line).map_err(|e| {
+                let e = io::Error::new(io::ErrorKind::InvalidData, e);
+                TestError::FileReading(path.into(), e.into())
+            })
+        })
+        .collect()
+}
+
+// split a line written by `Provider::persist_values` into its value and when it expires
+fn persisted_value(line: json::Value) -> Option<(json::Value, Option<SystemTime>)> {
+    let mut line = match line {
+        json::Value::Object(line) => line,
+        _ => return None,
+    };
+    let value = line.remove("value")?;
+    let expires = match line.remove("expires") {
+        Some(expires) => Some(UNIX_EPOCH + Duration::from_millis(expires.as_u64()?)),
+        None => None,
+    };
+    Some((value, expires))
+}
+
+// read the last number issued by a sequence provider in a previous run
+fn read_persisted_sequence(path: &str) -> Result<Option<i64>, TestError> {
+    match read_persisted_values(path)?.pop() {
+        None => Ok(None),
+        Some(v) => match v.as_i64() {
+            Some(n) => Ok(Some(n)),
+            None => {
+                let e = io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("expected an integer, got `{}`", v),
+                );
+                Err(TestError::FileReading(path.into(), e.into()))
+            }
+        },
+    }
 }
 
 // create a file provider. It takes a "test_killer" because a file provider has the means of killing a test
@@ -106,14 +232,183 @@ pub fn file(
     Ok(Provider::new(fp.auto_return, rx, tx))
 }
 
-// create a response provider
-pub fn response(rp: config::ResponseProvider, name: &str) -> Provider {
+// create a sqlite provider. Like a file provider it takes a "test_killer" so an error while
+// streaming the rows kills the test
+pub fn sqlite(
+    mut sp: config::SqliteProvider,
+    test_killer: broadcast::Sender<Result<TestEndReason, TestError>>,
+    name: &str,
+) -> Result<Provider, TestError> {
+    let file = std::mem::take(&mut sp.path);
+    debug!("providers::sqlite={}", file);
+    let file2 = file.clone();
+    // create a stream from the query results that yields values
+    let stream = into_stream(
+        SqliteReader::new(&sp, &file)
+            .map_err(|e| TestError::CannotOpenFile(file.into(), e.into()))?,
+    );
+
+    // create the channel for the provider
+    let limit = config_limit_to_channel_limit(sp.buffer);
+    let (tx, rx) = channel::channel(limit, sp.unique, name);
+    let tx2 = tx.clone();
+
+    // create a new task that pushes the rows into the channel
+    let primer_task = async move {
+        let r = stream
+            .map_err(move |e| {
+                let e = TestError::FileReading(file2.clone(), e.into());
+                channel::ChannelClosed::wrapped(e)
+            })
+            .forward(tx2)
+            .await;
+        if let Err(e) = r {
+            if let Some(e) = e.inner_cast() {
+                let _ = test_killer.send(Err(*e));
+            }
+        }
+    };
+    debug!("Provider::sqlite tokio::spawn primer_task");
+    tokio::spawn(primer_task);
+
+    Ok(Provider::new(sp.auto_return, rx, tx))
+}
+
+// create a response provider. If it has a `persist` file, the values saved there by a previous
+// run are loaded into the provider
+pub fn response(rp: config::ResponseProvider, name: &str) -> Result<Provider, TestError> {
     debug!("providers::response={:?}", rp);
     // create the channel for the provider
     let limit = config_limit_to_channel_limit(rp.buffer);
-    let (tx, rx) = channel::channel(limit, rp.unique, name);
+    let (tx, rx) = channel::channel_with_ttl(limit, rp.unique, rp.ttl, name);
+
+    if let Some(path) = &rp.persist {
+        let values = read_persisted_values(path)?;
+        debug!(
+            "providers::response {} loaded {} values from {}",
+            name,
+            values.len(),
+            path
+        );
+        for line in values {
+            let (value, expires) = persisted_value(line).ok_or_else(|| {
+                let e = io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "expected an object with a `value` and an optional `expires`",
+                );
+                TestError::FileReading(path.into(), e.into())
+            })?;
+            // values which expired since they were saved are dropped, and the rest keep their
+            // expiry instead of starting the `ttl` over
+            match expires {
+                Some(expires) => match expires.duration_since(SystemTime::now()) {
+                    Ok(remaining) => {
+                        tx.force_send_with_expiry(value, Some(Instant::now() + remaining))
+                    }
+                    Err(_) => continue,
+                },
+                None => tx.force_send(value),
+            }
+        }
+    }
+
+    let mut provider = Provider::new(rp.auto_return, rx, tx);
+    provider.persist = rp.persist;
+    Ok(provider)
+}
+
+// create a files provider. The directory is read when the provider is created, and each file is
+// provided as an object with its path, name, size and a mime type guessed from its extension
+pub fn files(fp: config::FilesProvider, name: &str) -> Result<Provider, TestError> {
+    debug!("providers::files={:?}", fp);
+    let mut files = Vec::new();
+    let root = Path::new(&fp.path);
+    list_files(root, root, &fp, &mut files)
+        .map_err(|e| TestError::CannotOpenFile(fp.path.as_str().into(), e.into()))?;
+    files.sort();
+    debug!("providers::files {} found {} files", name, files.len());
+    let files: Vec<_> = files
+        .into_iter()
+        .map(|(path, size)| {
+            let mime = mime_guess::from_path(&path).first_or_octet_stream();
+            json::json!({
+                "path": path.to_string_lossy(),
+                "name": path.file_name().map(|n| n.to_string_lossy()),
+                "size": size,
+                "mime": mime.essence_str(),
+            })
+        })
+        .collect();
+
+    // create the channel for the provider
+    let limit = config_limit_to_channel_limit(fp.buffer);
+    let (tx, rx) = channel::channel(limit, fp.unique, name);
+
+    // create a new task that pushes the files into the channel
+    let mut order = RecordOrder::new(files.len(), fp.shuffle);
+    let repeat = fp.repeat;
+    let files = iter::from_fn(move || order.next_index(repeat).map(|i| Ok(files[i].clone())));
+    let prime_tx = stream::iter(files).forward(tx.clone());
+    debug!("Provider::files tokio::spawn prime_tx");
+    tokio::spawn(prime_tx);
 
-    Provider::new(rp.auto_return, rx, tx)
+    Ok(Provider::new(fp.auto_return, rx, tx))
+}
+
+// adds the files in `dir` whose paths relative to `root` match the provider's glob, along with
+// their sizes. Entries which can't be read, such as broken symbolic links, are skipped with a
+// warning. Symlinked directories are not followed so a link back to a parent can't recurse forever
+fn list_files(
+    root: &Path,
+    dir: &Path,
+    fp: &config::FilesProvider,
+    files: &mut Vec<(PathBuf, u64)>,
+) -> io::Result<()> {
+    let skip = |path: &Path, e: io::Error| {
+        warn!("files provider skipping `{}`: {}", path.display(), e);
+    };
+    for entry in std::fs::read_dir(dir)? {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                skip(dir, e);
+                continue;
+            }
+        };
+        let path = entry.path();
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(e) => {
+                skip(&path, e);
+                continue;
+            }
+        };
+        if file_type.is_dir() {
+            if fp.recursive {
+                if let Err(e) = list_files(root, &path, fp, files) {
+                    skip(&path, e);
+                }
+            }
+            continue;
+        }
+        let metadata = match std::fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                skip(&path, e);
+                continue;
+            }
+        };
+        let relative: Vec<_> = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+        if metadata.is_file() && fp.glob.matches(&relative.join("/")) {
+            files.push((path, metadata.len()));
+        }
+    }
+    Ok(())
 }
 
 // create a list provider
@@ -149,6 +444,122 @@ pub fn range(rp: config::RangeProvider, name: &str) -> Provider {
     Provider::new(None, rx, tx)
 }
 
+// create a generate provider
+pub fn generate(gp: config::GenerateProvider, name: &str) -> Provider {
+    debug!("providers::generate={:?}", gp);
+    // create the channel for the provider
+    let limit = channel::Limit::dynamic(5);
+    let (tx, rx) = channel::channel(limit, gp.unique, name);
+
+    // create a new task that pushes generated values into the channel
+    let prime_tx = stream::iter(gp.into_iter().map(Ok)).forward(tx.clone());
+    debug!("Provider::generate tokio::spawn prime_tx");
+    tokio::spawn(prime_tx);
+
+    Provider::new(None, rx, tx)
+}
+
+// create a map provider. Rather than being received in order, the values sent to a map provider
+// are objects with a `key` and a `value`, and the value is stored under the key for `lookup`s
+pub fn map(mp: config::MapProvider, name: &str) -> Provider {
+    debug!("providers::map={:?}", mp);
+    // create the channel for the provider
+    let limit = channel::Limit::dynamic(5);
+    let (tx, rx) = channel::channel(limit, false, name);
+
+    let values = Arc::new(Mutex::new(BTreeMap::new()));
+    let inserted = Arc::new(Notify::new());
+
+    // create a new task that moves the values sent to the channel into the map
+    let values2 = values.clone();
+    let inserted2 = inserted.clone();
+    let name2 = name.to_string();
+    let insert_task = rx.clone().for_each(move |v| {
+        match v {
+            json::Value::Object(mut o) if o.contains_key("key") && o.contains_key("value") => {
+                let key = o.remove("key").expect("should have a key");
+                let key = json_value_to_string(Cow::Owned(key)).into_owned();
+                let value = o.remove("value").expect("should have a value");
+                values2.lock().expect("map lock").insert(key, value);
+                inserted2.notify_waiters();
+            }
+            v => debug!(
+                "providers::map {} ignoring value without a key and value: {}",
+                name2, v
+            ),
+        }
+        future::ready(())
+    });
+    debug!("Provider::map tokio::spawn insert_task");
+    tokio::spawn(insert_task);
+
+    let block = mp.missing.is_block();
+    let lookup: config::ProviderLookup = Arc::new(move |key| {
+        let values = values.clone();
+        let inserted = inserted.clone();
+        async move {
+            loop {
+                // start listening before checking so an insert in between isn't missed
+                let notified = inserted.notified();
+                if let Some(v) = values.lock().expect("map lock").get(&key) {
+                    return Some(v.clone());
+                }
+                if !block {
+                    return None;
+                }
+                notified.await;
+            }
+        }
+        .boxed()
+    });
+
+    let mut provider = Provider::new(None, rx, tx);
+    provider.lookup = Some(lookup);
+    provider
+}
+
+// create a sequence provider
+pub fn sequence(sp: config::SequenceProvider, name: &str) -> Result<Provider, TestError> {
+    debug!("providers::sequence={:?}", sp);
+    // create the channel for the provider
+    let limit = channel::Limit::dynamic(5);
+    let (tx, rx) = channel::channel(limit, false, name);
+
+    // pick up after the last number issued by a previous run
+    let persisted = match &sp.persist {
+        Some(path) => read_persisted_sequence(path)?,
+        None => None,
+    };
+    let first = match persisted {
+        Some(n) => sp.next(n),
+        None => Some(sp.start),
+    };
+    debug!(
+        "providers::sequence {} starting at {:?}, previous run ended at {:?}",
+        name, first, persisted
+    );
+
+    // create a new task that pushes the numbers into the channel, keeping track of the last one
+    // so it can be persisted
+    let last_issued = Arc::new(Mutex::new(persisted));
+    let last_issued2 = last_issued.clone();
+    let persist = sp.persist.clone();
+    let sp = Arc::new(sp);
+    let sp2 = sp.clone();
+    let numbers = iter::successors(first, move |n| sp2.next(*n)).map(move |n| {
+        *last_issued2.lock().expect("sequence lock") = Some(n);
+        Ok(sp.format(n))
+    });
+    let prime_tx = stream::iter(numbers).forward(tx.clone());
+    debug!("Provider::sequence tokio::spawn prime_tx");
+    tokio::spawn(prime_tx);
+
+    let mut provider = Provider::new(None, rx, tx);
+    provider.persist = persist;
+    provider.last_issued = Some(last_issued);
+    Ok(provider)
+}
+
 #[derive(Clone, Debug)]
 pub struct Logger {
     limit: Option<Arc<AtomicIsize>>,
@@ -240,6 +651,58 @@ pub fn logger(
     }
 }
 
+// yields the indices of a reader's records. Used by the readers when records are read by their
+// positions, in order for a `partition` or in random order for `shuffle`. When shuffled, every
+// record is used once before any record is used again
+struct RecordOrder {
+    next: usize,
+    order: Vec<usize>,
+    shuffle: bool,
+}
+
+impl RecordOrder {
+    fn new(len: usize, shuffle: bool) -> Self {
+        let mut order: Vec<_> = (0..len).collect();
+        if shuffle {
+            order.shuffle(&mut rand::thread_rng());
+        }
+        RecordOrder {
+            next: 0,
+            order,
+            shuffle,
+        }
+    }
+
+    // returns `None` once every index has been used, unless `repeat` in which case another pass
+    // starts (shuffled again if `shuffle`)
+    fn next_index(&mut self, repeat: bool) -> Option<usize> {
+        if self.next == self.order.len() {
+            if !repeat || self.order.is_empty() {
+                return None;
+            }
+            if self.shuffle {
+                self.order.shuffle(&mut rand::thread_rng());
+            }
+            self.next = 0;
+        }
+        let i = self.order[self.next];
+        self.next += 1;
+        Some(i)
+    }
+}
+
+// keeps only the records which belong to the partition, every `count`th record starting at `index`
+fn partition_records<T>(records: Vec<T>, partition: Option<config::Partition>) -> Vec<T> {
+    match partition {
+        Some(p) => records
+            .into_iter()
+            .skip(p.index)
+            .step_by(p.count.get())
+            .collect(),
+        None => records,
+    }
+}
+
 // a helper function used by the different types of file readers to turn blocking iterators
 // into a stream
 fn into_stream<I: Iterator<Item = Result<json::Value, io::Error>> + Send + 'static>(
@@ -342,6 +805,8 @@ mod tests {
                 values: jsons.clone(),
                 repeat: false,
                 random: false,
+                weights: None,
+                shuffle: false,
                 unique: false,
             };
 
@@ -359,6 +824,8 @@ mod tests {
                 values: jsons.clone(),
                 repeat: false,
                 random: true,
+                weights: None,
+                shuffle: false,
                 unique: false,
             };
 
@@ -379,6 +846,8 @@ mod tests {
                 values: jsons.clone(),
                 repeat: true,
                 random: false,
+                weights: None,
+                shuffle: false,
                 unique: false,
             };
 
@@ -393,6 +862,8 @@ mod tests {
                 values: jsons.clone(),
                 repeat: true,
                 random: true,
+                weights: None,
+                shuffle: false,
                 unique: false,
             };
 
@@ -421,6 +892,8 @@ mod tests {
                 values: vec![json!(1), json!(2), json!(1), json!(2), json!(1)],
                 repeat: false,
                 random: false,
+                weights: None,
+                shuffle: false,
                 unique: true,
             };
 
@@ -441,15 +914,70 @@ mod tests {
         });
     }
 
+    #[test]
+    fn generate_provider_works() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async move {
+            let generate_params = r#"
+                seed: 1
+                template:
+                    id: uuid
+                    name: first_name
+            "#;
+            let gp = config::GenerateProvider::from_yaml_str(generate_params).unwrap();
+            let expect: Vec<_> = gp.clone().into_iter().take(50).collect();
+            let p = generate(gp, "generate_provider_works");
+
+            let values: Vec<_> = p.rx.take(50).collect().await;
+
+            assert_eq!(values, expect);
+        });
+    }
+
+    #[test]
+    fn map_provider_works() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async move {
+            let mut p = map(Default::default(), "map_provider_works");
+            let lookup = p.lookup.clone().unwrap();
+
+            // a lookup waits for the key to be inserted
+            let waiting = tokio::spawn(lookup("2".into()));
+            p.tx.send(json!({"key": 1, "value": "a"})).await.unwrap();
+            p.tx.send(json!("no key")).await.unwrap();
+            p.tx.send(json!({"key": "2", "value": {"token": "b"}}))
+                .await
+                .unwrap();
+
+            assert_eq!(waiting.await.unwrap(), Some(json!({"token": "b"})));
+            assert_eq!(lookup("1".into()).await, Some(json!("a")));
+
+            // inserting a key again replaces the value
+            p.tx.send(json!({"key": 1, "value": "c"})).await.unwrap();
+            while lookup("1".into()).await != Some(json!("c")) {
+                tokio::task::yield_now().await;
+            }
+
+            let mp = config::MapProvider {
+                missing: config::MapProviderMissing::Error,
+            };
+            let p = map(mp, "map_provider_works2");
+            let lookup = p.lookup.unwrap();
+            assert_eq!(lookup("1".into()).await, None);
+        });
+    }
+
     #[test]
     fn response_provider_works() {
         let jsons = vec![json!(1), json!(2), json!(3)];
         let rp = config::ResponseProvider {
             auto_return: None,
             buffer: config::Limit::dynamic(),
+            persist: None,
+            ttl: None,
             unique: false,
         };
-        let mut p = response(rp, &"response_provider_works".to_string());
+        let mut p = response(rp, "response_provider_works").unwrap();
         for value in &jsons {
             let _ = block_on(p.tx.send(value.clone()));
         }
@@ -480,9 +1008,11 @@ mod tests {
         let rp = config::ResponseProvider {
             auto_return: None,
             buffer: config::Limit::Static(jsons.len()),
+            persist: None,
+            ttl: None,
             unique: true,
         };
-        let mut p = response(rp, &"unique_response_provider_works".to_string());
+        let mut p = response(rp, "unique_response_provider_works").unwrap();
         for value in &jsons {
             let _ = block_on(p.tx.send(value.clone()));
         }
@@ -497,6 +1027,261 @@ mod tests {
         assert_eq!(values, expects);
     }
 
+    #[test]
+    fn persisted_response_provider_works() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions.json");
+        let rp = config::ResponseProvider {
+            persist: Some(path.to_string_lossy().into()),
+            ..Default::default()
+        };
+
+        let mut p = response(rp.clone(), "persisted_response_provider_works").unwrap();
+        let jsons = vec![json!({"token": "a"}), json!({"token": "b"}), json!(3)];
+        for value in &jsons {
+            let _ = block_on(p.tx.send(value.clone()));
+        }
+        p.persist_values().unwrap();
+        assert_eq!(p.tx.len(), 0);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            contents,
+            "{\"value\":{\"token\":\"a\"}}\n{\"value\":{\"token\":\"b\"}}\n{\"value\":3}\n"
+        );
+
+        let Provider { rx, tx, .. } = response(rp, "persisted_response_provider_works2").unwrap();
+        drop(tx);
+
+        let values: Vec<_> = block_on_stream(rx).collect();
+
+        assert_eq!(values, jsons);
+
+        std::fs::write(&path, "not json\n").unwrap();
+        let rp = config::ResponseProvider {
+            persist: Some(path.to_string_lossy().into()),
+            ..Default::default()
+        };
+        assert!(response(rp, "persisted_response_provider_works3").is_err());
+    }
+
+    #[test]
+    fn persisted_values_keep_their_expiry() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions.json");
+        let rp = config::ResponseProvider {
+            persist: Some(path.to_string_lossy().into()),
+            ttl: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let since_epoch = |d: Duration| (SystemTime::now() + d).duration_since(UNIX_EPOCH);
+        let since_epoch = |d| since_epoch(d).unwrap().as_millis() as u64;
+
+        let p = response(rp.clone(), "persisted_values_keep_their_expiry").unwrap();
+        p.tx.force_send(json!("a"));
+        let before = since_epoch(Duration::from_secs(59));
+        p.persist_values().unwrap();
+        let after = since_epoch(Duration::from_secs(61));
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let line: json::Value = json::from_str(&contents).unwrap();
+        assert_eq!(line["value"], json!("a"));
+        let expires = line["expires"].as_u64().unwrap();
+        assert!(before <= expires && expires <= after, "{}", contents);
+
+        let in_an_hour = since_epoch(Duration::from_secs(3600));
+        let contents = format!(
+            "{}\n{}\n{}\n",
+            json!({"value": "expired", "expires": 1000}),
+            json!({"value": "kept", "expires": in_an_hour}),
+            json!({"value": "new"}),
+        );
+        std::fs::write(&path, contents).unwrap();
+        let p = response(rp, "persisted_values_keep_their_expiry2").unwrap();
+        let values = p.rx.drain();
+        let names: Vec<_> = values.iter().map(|(v, _)| v.clone()).collect();
+        assert_eq!(names, vec![json!("kept"), json!("new")]);
+        let in_half_an_hour = Instant::now() + Duration::from_secs(1800);
+        assert!(
+            values[0].1.unwrap() > in_half_an_hour,
+            "a loaded value keeps its expiry"
+        );
+        assert!(
+            values[1].1.unwrap() < in_half_an_hour,
+            "a value saved without an expiry gets the ttl"
+        );
+    }
+
+    #[test]
+    fn dropped_auto_returns_are_returned() {
+        let rp = config::ResponseProvider {
+            auto_return: Some(config::EndpointProvidesSendOptions::Force),
+            ..Default::default()
+        };
+        let p = response(rp, "dropped_auto_returns_are_returned").unwrap();
+        let expires = Some(Instant::now() + Duration::from_secs(60));
+        let ar = crate::request::AutoReturn::new(
+            config::EndpointProvidesSendOptions::Force,
+            p.tx.clone(),
+            vec![json!(1)],
+            expires,
+            p.auto_returns_held.clone(),
+        );
+        assert_eq!(p.auto_returns_held.load(Ordering::Acquire), 1);
+        drop(ar);
+        assert_eq!(p.auto_returns_held.load(Ordering::Acquire), 0);
+        assert_eq!(p.rx.drain(), vec![(json!(1), expires)]);
+    }
+
+    #[test]
+    fn sequence_provider_works() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async move {
+            let sp = config::SequenceProvider {
+                start: 8,
+                end: 11,
+                step: 1,
+                wrap: true,
+                prefix: Some("ORD-".into()),
+                suffix: None,
+                width: Some(3),
+                persist: None,
+            };
+            let Provider { rx, tx, .. } = sequence(sp, "sequence_provider_works").unwrap();
+            drop(tx);
+
+            let values: Vec<_> = rx.take(6).collect().await;
+            let expect = vec![
+                json!("ORD-008"),
+                json!("ORD-009"),
+                json!("ORD-010"),
+                json!("ORD-011"),
+                json!("ORD-008"),
+                json!("ORD-009"),
+            ];
+            assert_eq!(values, expect);
+
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("order_id");
+            let sp = config::SequenceProvider {
+                start: 1,
+                end: 6,
+                step: 2,
+                wrap: false,
+                prefix: None,
+                suffix: None,
+                width: None,
+                persist: Some(path.to_string_lossy().into()),
+            };
+
+            // the next run continues after the last number issued
+            let p = sequence(sp.clone(), "sequence_provider_works2").unwrap();
+            let value = p.rx.clone().next().await;
+            assert_eq!(value, Some(json!(1)));
+            while *p.last_issued.as_ref().unwrap().lock().unwrap() != Some(5) {
+                tokio::task::yield_now().await;
+            }
+            p.persist_values().unwrap();
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), "5\n");
+
+            std::fs::write(&path, "2\n").unwrap();
+            let Provider { rx, tx, .. } = sequence(sp.clone(), "sequence_provider_works3").unwrap();
+            drop(tx);
+            let values: Vec<_> = rx.collect().await;
+            assert_eq!(values, vec![json!(4), json!(6)]);
+
+            std::fs::write(&path, "\"a\"\n").unwrap();
+            assert!(sequence(sp, "sequence_provider_works4").is_err());
+        });
+    }
+
+    #[test]
+    fn files_provider_works() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async move {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::create_dir(dir.path().join("nested")).unwrap();
+            std::fs::write(dir.path().join("a.png"), "png").unwrap();
+            std::fs::write(dir.path().join("b.txt"), "text").unwrap();
+            std::fs::write(dir.path().join("nested").join("c.png"), "png!").unwrap();
+            let path = |p: &str| dir.path().join(p).to_string_lossy().into_owned();
+
+            let mut fp = config::FilesProvider {
+                auto_return: None,
+                buffer: Default::default(),
+                glob: config::FileGlob::new("*.png").unwrap(),
+                path: path(""),
+                recursive: false,
+                repeat: false,
+                shuffle: false,
+                unique: false,
+            };
+            let Provider { rx, tx, .. } = files(fp.clone(), "files_provider_works").unwrap();
+            drop(tx);
+            let values: Vec<_> = rx.collect().await;
+            let expect = vec![json!({
+                "path": path("a.png"),
+                "name": "a.png",
+                "size": 3,
+                "mime": "image/png",
+            })];
+            assert_eq!(values, expect);
+
+            fp.recursive = true;
+            fp.glob = config::FileGlob::new("*").unwrap();
+            let Provider { rx, tx, .. } = files(fp.clone(), "files_provider_works2").unwrap();
+            drop(tx);
+            let values: Vec<_> = rx.map(|v| v["name"].clone()).collect().await;
+            assert_eq!(values, vec![json!("a.png"), json!("b.txt"), json!("c.png")]);
+
+            // the glob is matched against the path relative to the provider's directory
+            fp.glob = config::FileGlob::new("nested/*").unwrap();
+            let Provider { rx, tx, .. } = files(fp.clone(), "files_provider_works2b").unwrap();
+            drop(tx);
+            let values: Vec<_> = rx.map(|v| v["name"].clone()).collect().await;
+            assert_eq!(values, vec![json!("c.png")]);
+            fp.glob = config::FileGlob::new("*").unwrap();
+
+            // a broken symbolic link is skipped rather than failing the provider
+            #[cfg(unix)]
+            {
+                let link = dir.path().join("nested").join("broken.png");
+                std::os::unix::fs::symlink(dir.path().join("gone.png"), link).unwrap();
+                let Provider { rx, tx, .. } = files(fp.clone(), "files_provider_works2c").unwrap();
+                drop(tx);
+                let values: Vec<_> = rx.map(|v| v["name"].clone()).collect().await;
+                assert_eq!(values, vec![json!("a.png"), json!("b.txt"), json!("c.png")]);
+            }
+
+            fp.repeat = true;
+            let values: Vec<_> = files(fp, "files_provider_works3")
+                .unwrap()
+                .rx
+                .map(|v| v["mime"].clone())
+                .take(4)
+                .collect()
+                .await;
+            let expect = vec![
+                json!("image/png"),
+                json!("text/plain"),
+                json!("image/png"),
+                json!("image/png"),
+            ];
+            assert_eq!(values, expect);
+
+            let fp = config::FilesProvider {
+                auto_return: None,
+                buffer: Default::default(),
+                glob: config::FileGlob::new("*").unwrap(),
+                path: path("missing"),
+                recursive: false,
+                repeat: false,
+                shuffle: false,
+                unique: false,
+            };
+            assert!(files(fp, "files_provider_works4").is_err());
+        });
+    }
+
     #[test]
     fn basic_logger_works() {
         let rt = Runtime::new().unwrap();
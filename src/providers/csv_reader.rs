@@ -1,3 +1,4 @@
+use super::{partition_records, RecordOrder};
 use crate::util::str_to_json;
 use rand::distributions::{Distribution, Uniform};
 use serde_json as json;
@@ -18,6 +19,7 @@ pub struct CsvReader {
     random: Option<Uniform<usize>>,
     reader: csv::Reader<File>,
     repeat: bool,
+    order: Option<RecordOrder>,
 }
 
 impl CsvReader {
@@ -66,14 +68,16 @@ impl CsvReader {
             random: None,
             reader,
             repeat: config.repeat,
+            order: None,
         };
-        if config.random || (first_row_headers && config.repeat) {
-            // get position of the csv records. Get all of them if config.random,
-            // otherwise just the first. It's important to always get the first one
-            // so if we need to seek back to the beginning, we can account for any
+        let all_positions = config.random || config.shuffle || config.partition.is_some();
+        if all_positions || (first_row_headers && config.repeat) {
+            // get position of the csv records. Get all of them if config.random, config.shuffle
+            // or config.partition, otherwise just the first. It's important to always get the first
+            // one so if we need to seek back to the beginning, we can account for any
             // possible header row
             loop {
-                if !config.random && !cr.positions.is_empty() {
+                if !all_positions && !cr.positions.is_empty() {
                     break;
                 }
                 match cr.reader.read_byte_record(&mut byte_record) {
@@ -86,7 +90,11 @@ impl CsvReader {
                     Err(e) => return Err(e.into()),
                 }
             }
-            let pos_index = if config.random && !cr.positions.is_empty() {
+            cr.positions = partition_records(std::mem::take(&mut cr.positions), config.partition);
+            if config.shuffle || (config.partition.is_some() && !config.random) {
+                cr.order = Some(RecordOrder::new(cr.positions.len(), config.shuffle));
+            }
+            let pos_index = if config.random && cr.order.is_none() && !cr.positions.is_empty() {
                 let random = Uniform::new(0, cr.positions.len());
                 let pos_index = random.sample(&mut rand::thread_rng());
                 cr.random = Some(random);
@@ -109,7 +117,12 @@ impl Iterator for CsvReader {
 
     fn next(&mut self) -> Option<Self::Item> {
         let mut record = csv::StringRecord::new();
-        if let Some(random) = self.random {
+        if let Some(order) = &mut self.order {
+            let i = order.next_index(self.repeat)?;
+            if let Err(e) = self.reader.seek(self.positions[i].clone()) {
+                return Some(Err(e.into()));
+            }
+        } else if let Some(random) = self.random {
             if self.positions.is_empty() {
                 return None;
             }
@@ -189,4 +202,78 @@ mod tests {
             assert_eq!(values, expect);
         }
     }
+
+    #[test]
+    fn csv_reader_shuffle_works() {
+        let mut fp = config::FileProvider {
+            csv: config::CsvSettings {
+                headers: config::CsvHeader::Bool(true),
+                ..Default::default()
+            },
+            format: config::FileFormat::Csv,
+            repeat: true,
+            shuffle: true,
+            ..Default::default()
+        };
+
+        let mut tmp = NamedTempFile::new().unwrap();
+        write!(tmp, "id\n1\n2\n3\n4").unwrap();
+        let path = tmp.path().to_str().unwrap().to_string();
+
+        let values: Vec<_> = CsvReader::new(&fp, &path)
+            .unwrap()
+            .take(40)
+            .map(|v| v.unwrap()["id"].as_u64().unwrap())
+            .collect();
+
+        // every record is used once per pass
+        for pass in values.chunks(4) {
+            let mut pass = pass.to_vec();
+            pass.sort_unstable();
+            assert_eq!(pass, vec![1, 2, 3, 4]);
+        }
+
+        fp.repeat = false;
+        let mut values: Vec<_> = CsvReader::new(&fp, &path)
+            .unwrap()
+            .map(|v| v.unwrap()["id"].as_u64().unwrap())
+            .collect();
+        values.sort_unstable();
+        assert_eq!(values, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn csv_reader_partition_works() {
+        let mut tmp = NamedTempFile::new().unwrap();
+        write!(tmp, "id\n1\n2\n3\n4\n5").unwrap();
+        let path = tmp.path().to_str().unwrap().to_string();
+
+        let mut fp = config::FileProvider {
+            csv: config::CsvSettings {
+                headers: config::CsvHeader::Bool(true),
+                ..Default::default()
+            },
+            format: config::FileFormat::Csv,
+            partition: Some(config::Partition {
+                count: std::num::NonZeroUsize::new(2).unwrap(),
+                index: 1,
+            }),
+            ..Default::default()
+        };
+
+        let values: Vec<_> = CsvReader::new(&fp, &path)
+            .unwrap()
+            .map(|v| v.unwrap()["id"].as_u64().unwrap())
+            .collect();
+        assert_eq!(values, vec![2, 4]);
+
+        fp.random = true;
+        fp.repeat = true;
+        let values: Vec<_> = CsvReader::new(&fp, &path)
+            .unwrap()
+            .take(50)
+            .map(|v| v.unwrap()["id"].as_u64().unwrap())
+            .collect();
+        assert!(values.iter().all(|id| *id == 2 || *id == 4), "{:?}", values);
+    }
 }
@@ -1,3 +1,4 @@
+use super::{partition_records, RecordOrder};
 use rand::distributions::{Distribution, Uniform};
 use serde_json as json;
 
@@ -16,6 +17,7 @@ pub struct JsonReader {
     random: Option<Uniform<usize>>,
     reader: File,
     repeat: bool,
+    order: Option<RecordOrder>,
 }
 
 impl JsonReader {
@@ -28,8 +30,9 @@ impl JsonReader {
             random: None,
             reader: File::open(file)?,
             repeat: config.repeat,
+            order: None,
         };
-        if config.random {
+        if config.random || config.shuffle || config.partition.is_some() {
             loop {
                 match jr.get_value(None) {
                     Some(Ok((_, pos, length))) => {
@@ -39,7 +42,10 @@ impl JsonReader {
                     None => break,
                 }
             }
-            if !jr.positions.is_empty() {
+            jr.positions = partition_records(std::mem::take(&mut jr.positions), config.partition);
+            if config.shuffle || (config.partition.is_some() && !config.random) {
+                jr.order = Some(RecordOrder::new(jr.positions.len(), config.shuffle));
+            } else if !jr.positions.is_empty() {
                 let random = Uniform::new(0, jr.positions.len());
                 let rand_pos = jr.positions.get(random.sample(&mut rand::thread_rng()));
                 if let Some((pos, _)) = rand_pos {
@@ -114,7 +120,14 @@ impl Iterator for JsonReader {
     type Item = Result<json::Value, io::Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let size_hint = if let Some(random) = self.random {
+        let size_hint = if let Some(order) = &mut self.order {
+            let i = order.next_index(self.repeat)?;
+            let (pos, size) = self.positions[i];
+            if let Err(e) = self.seek(pos) {
+                return Some(Err(e));
+            }
+            Some(size)
+        } else if let Some(random) = self.random {
             if self.positions.is_empty() {
                 return None;
             }
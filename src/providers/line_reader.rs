@@ -1,3 +1,4 @@
+use super::{partition_records, RecordOrder};
 use crate::util::str_to_json;
 use rand::distributions::{Distribution, Uniform};
 use serde_json as json;
@@ -20,6 +21,7 @@ pub struct LineReader {
     random: Option<Uniform<usize>>,
     reader: File,
     repeat: bool,
+    order: Option<RecordOrder>,
 }
 
 impl LineReader {
@@ -32,8 +34,9 @@ impl LineReader {
             random: None,
             reader: File::open(file)?,
             repeat: config.repeat,
+            order: None,
         };
-        if config.random {
+        if config.random || config.shuffle || config.partition.is_some() {
             loop {
                 match jr.get_value(None) {
                     Some(Ok((_, pos, length))) => {
@@ -43,7 +46,10 @@ impl LineReader {
                     None => break,
                 }
             }
-            if !jr.positions.is_empty() {
+            jr.positions = partition_records(std::mem::take(&mut jr.positions), config.partition);
+            if config.shuffle || (config.partition.is_some() && !config.random) {
+                jr.order = Some(RecordOrder::new(jr.positions.len(), config.shuffle));
+            } else if !jr.positions.is_empty() {
                 let random = Uniform::new(0, jr.positions.len());
                 let rand_pos = jr.positions.get(random.sample(&mut rand::thread_rng()));
                 if let Some((pos, _)) = rand_pos {
@@ -130,7 +136,14 @@ impl Iterator for LineReader {
     type Item = Result<json::Value, io::Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let size_hint = if let Some(random) = self.random {
+        let size_hint = if let Some(order) = &mut self.order {
+            let i = order.next_index(self.repeat)?;
+            let (pos, size) = self.positions[i];
+            if let Err(e) = self.seek(pos) {
+                return Some(Err(e));
+            }
+            Some(size)
+        } else if let Some(random) = self.random {
             if self.positions.is_empty() {
                 return None;
             }
@@ -264,4 +277,61 @@ mod tests {
             assert_eq!(values.len(), 5);
         }
     }
+
+    #[test]
+    fn line_reader_repeat_shuffle_works() {
+        let fp = config::FileProvider {
+            repeat: true,
+            shuffle: true,
+            ..Default::default()
+        };
+
+        let mut tmp = NamedTempFile::new().unwrap();
+        write!(tmp, "{}", LINES.join("\n")).unwrap();
+        let path = tmp.path().to_str().unwrap().to_string();
+
+        let values: Vec<_> = LineReader::new(&fp, &path)
+            .unwrap()
+            .map(|v| v.unwrap().to_string())
+            .take(1000)
+            .collect();
+
+        // every line is used once per pass
+        let mut first_pass = values[..LINES.len()].to_vec();
+        first_pass.sort_unstable();
+        for pass in values.chunks(LINES.len()) {
+            let mut pass = pass.to_vec();
+            pass.sort_unstable();
+            assert_eq!(pass, first_pass);
+        }
+        first_pass.dedup();
+        assert_eq!(first_pass.len(), LINES.len());
+    }
+
+    #[test]
+    fn line_reader_partition_works() {
+        let mut tmp = NamedTempFile::new().unwrap();
+        let lines: Vec<_> = (0..10).map(|i| i.to_string()).collect();
+        write!(tmp, "{}", lines.join("\n")).unwrap();
+        let path = tmp.path().to_str().unwrap().to_string();
+
+        let count = std::num::NonZeroUsize::new(3).unwrap();
+        let read_partition = |index, repeat, take| {
+            let fp = config::FileProvider {
+                partition: Some(config::Partition { count, index }),
+                repeat,
+                ..Default::default()
+            };
+            LineReader::new(&fp, &path)
+                .unwrap()
+                .take(take)
+                .map(|v| v.unwrap().as_u64().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(read_partition(0, false, 100), vec![0, 3, 6, 9]);
+        assert_eq!(read_partition(1, false, 100), vec![1, 4, 7]);
+        assert_eq!(read_partition(2, false, 100), vec![2, 5, 8]);
+        assert_eq!(read_partition(1, true, 7), vec![1, 4, 7, 1, 4, 7, 1]);
+    }
 }
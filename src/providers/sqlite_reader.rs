@@ -0,0 +1,227 @@
+use super::RecordOrder;
+use rand::distributions::{Distribution, Uniform};
+use rusqlite::{types::ValueRef, Connection, OpenFlags};
+use serde_json as json;
+
+use std::{io, iter::Iterator};
+
+// A type of reader which runs a query against a sqlite database.
+// Each row returned by the query is converted into a json object with properties matching the
+// column names. The rows are read when the reader is created so an invalid query is reported
+// before the test starts
+pub struct SqliteReader {
+    position: usize,
+    random: Option<Uniform<usize>>,
+    repeat: bool,
+    rows: Vec<json::Value>,
+    order: Option<RecordOrder>,
+}
+
+impl SqliteReader {
+    pub fn new(config: &config::SqliteProvider, file: &str) -> Result<Self, io::Error> {
+        let to_io_error = io::Error::other;
+        // open read only so a typo in the path doesn't create an empty database
+        let connection = Connection::open_with_flags(file, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(to_io_error)?;
+        let mut statement = connection.prepare(&config.query).map_err(to_io_error)?;
+        let columns: Vec<String> = statement
+            .column_names()
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        let rows = statement
+            .query_map([], |row| {
+                let mut object = json::Map::with_capacity(columns.len());
+                for (i, column) in columns.iter().enumerate() {
+                    object.insert(column.clone(), value_ref_to_json(row.get_ref(i)?));
+                }
+                Ok(json::Value::Object(object))
+            })
+            .map_err(to_io_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(to_io_error)?;
+        let order = if config.shuffle {
+            Some(RecordOrder::new(rows.len(), true))
+        } else {
+            None
+        };
+        let random = if config.random && order.is_none() && !rows.is_empty() {
+            Some(Uniform::new(0, rows.len()))
+        } else {
+            None
+        };
+        Ok(SqliteReader {
+            position: 0,
+            random,
+            repeat: config.repeat,
+            rows,
+            order,
+        })
+    }
+}
+
+fn value_ref_to_json(value: ValueRef<'_>) -> json::Value {
+    match value {
+        ValueRef::Null => json::Value::Null,
+        ValueRef::Integer(i) => i.into(),
+        ValueRef::Real(f) => json::Number::from_f64(f).map_or(json::Value::Null, Into::into),
+        ValueRef::Text(t) => String::from_utf8_lossy(t).into_owned().into(),
+        ValueRef::Blob(b) => base64::encode(b).into(),
+    }
+}
+
+impl Iterator for SqliteReader {
+    type Item = Result<json::Value, io::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(order) = &mut self.order {
+            let i = order.next_index(self.repeat)?;
+            let value = &mut self.rows[i];
+            let value = if self.repeat {
+                value.clone()
+            } else {
+                value.take()
+            };
+            return Some(Ok(value));
+        }
+        if let Some(random) = self.random {
+            if self.rows.is_empty() {
+                return None;
+            }
+            let i = random.sample(&mut rand::thread_rng()) % self.rows.len();
+            let value = if self.repeat {
+                self.rows[i].clone()
+            } else {
+                self.rows.swap_remove(i)
+            };
+            return Some(Ok(value));
+        }
+        if self.position >= self.rows.len() {
+            if !self.repeat || self.rows.is_empty() {
+                return None;
+            }
+            self.position = 0;
+        }
+        let value = &mut self.rows[self.position];
+        let value = if self.repeat {
+            value.clone()
+        } else {
+            value.take()
+        };
+        self.position += 1;
+        Some(Ok(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    fn create_database() -> NamedTempFile {
+        let file = NamedTempFile::new().unwrap();
+        let connection = Connection::open(file.path()).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE users (id INTEGER, name TEXT, score REAL, avatar BLOB);
+                INSERT INTO users VALUES (1, 'alice', 1.5, x'0102');
+                INSERT INTO users VALUES (2, 'bob', NULL, NULL);
+                INSERT INTO users VALUES (3, 'carol', 3.0, NULL);",
+            )
+            .unwrap();
+        file
+    }
+
+    fn sqlite_provider(query: &str) -> config::SqliteProvider {
+        config::SqliteProvider {
+            query: query.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sqlite_reader_basics_works() {
+        let db = create_database();
+        let path = db.path().to_str().unwrap();
+        let sp = sqlite_provider("SELECT id, name, score, avatar FROM users ORDER BY id");
+
+        let expect = vec![
+            json::json!({ "id": 1, "name": "alice", "score": 1.5, "avatar": "AQI=" }),
+            json::json!({ "id": 2, "name": "bob", "score": null, "avatar": null }),
+            json::json!({ "id": 3, "name": "carol", "score": 3.0, "avatar": null }),
+        ];
+
+        let values: Vec<_> = SqliteReader::new(&sp, path)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(values, expect);
+    }
+
+    #[test]
+    fn sqlite_reader_repeat_and_random_works() {
+        let db = create_database();
+        let path = db.path().to_str().unwrap();
+        let mut sp = sqlite_provider("SELECT id FROM users ORDER BY id");
+
+        sp.repeat = true;
+        let values: Vec<_> = SqliteReader::new(&sp, path)
+            .unwrap()
+            .take(7)
+            .map(|v| v.unwrap()["id"].as_u64().unwrap())
+            .collect();
+        assert_eq!(values, vec![1, 2, 3, 1, 2, 3, 1]);
+
+        sp.repeat = false;
+        sp.random = true;
+        let mut values: Vec<_> = SqliteReader::new(&sp, path)
+            .unwrap()
+            .map(|v| v.unwrap()["id"].as_u64().unwrap())
+            .collect();
+        values.sort_unstable();
+        assert_eq!(values, vec![1, 2, 3]);
+
+        sp.repeat = true;
+        let count = SqliteReader::new(&sp, path)
+            .unwrap()
+            .take(20)
+            .filter(|v| matches!(v, Ok(json::Value::Object(_))))
+            .count();
+        assert_eq!(count, 20);
+    }
+
+    #[test]
+    fn sqlite_reader_shuffle_works() {
+        let db = create_database();
+        let path = db.path().to_str().unwrap();
+        let mut sp = sqlite_provider("SELECT id FROM users");
+        sp.shuffle = true;
+        sp.repeat = true;
+
+        let values: Vec<_> = SqliteReader::new(&sp, path)
+            .unwrap()
+            .take(30)
+            .map(|v| v.unwrap()["id"].as_u64().unwrap())
+            .collect();
+        for pass in values.chunks(3) {
+            let mut pass = pass.to_vec();
+            pass.sort_unstable();
+            assert_eq!(pass, vec![1, 2, 3]);
+        }
+    }
+
+    #[test]
+    fn sqlite_reader_errors_on_bad_input() {
+        let db = create_database();
+        let path = db.path().to_str().unwrap();
+
+        let sp = sqlite_provider("SELECT nope FROM users");
+        assert!(SqliteReader::new(&sp, path).is_err(), "invalid query");
+
+        let sp = sqlite_provider("SELECT id FROM users");
+        assert!(
+            SqliteReader::new(&sp, "does-not-exist.db").is_err(),
+            "missing database"
+        );
+    }
+}
@@ -1,10 +1,14 @@
 #![allow(clippy::type_complexity)]
 mod body_handler;
+mod rate_limiter;
 mod request_maker;
 mod response_handler;
+mod scenario;
 
 use self::body_handler::BodyHandler;
+pub use self::rate_limiter::RateLimiter;
 use self::request_maker::RequestMaker;
+pub use self::scenario::ScenarioBuilder;
 
 use log::debug;
 use request_maker::ProviderDelays;
@@ -16,7 +20,7 @@ use futures::{
     channel::mpsc as futures_channel,
     future::{self, try_join_all},
     sink::SinkExt,
-    stream, FutureExt, Stream, StreamExt, TryFutureExt, TryStreamExt,
+    stream, FutureExt, Stream, StreamExt, TryFutureExt,
 };
 use hyper::{
     client::HttpConnector,
@@ -39,6 +43,7 @@ use crate::stats;
 use crate::util::tweak_path;
 use config::{
     BodyTemplate, EndpointProvidesSendOptions, MultipartBody, ProviderStream, Select, Template,
+    ThinkTime,
 };
 
 use std::{
@@ -50,16 +55,22 @@ use std::{
     path::PathBuf,
     pin::Pin,
     str,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
     task::{Context, Poll},
     time::{Duration, Instant},
 };
 
-#[derive(Clone)]
 pub struct AutoReturn {
     send_option: EndpointProvidesSendOptions,
     channel: channel::Sender<json::Value>,
     jsons: Vec<json::Value>,
+    // when the values expire in the provider, so returning them does not restart the ttl
+    expires: Option<Instant>,
+    // the provider's count of values waiting to be auto returned
+    held: Arc<AtomicUsize>,
 }
 
 impl AutoReturn {
@@ -67,11 +78,16 @@ impl AutoReturn {
         send_option: EndpointProvidesSendOptions,
         channel: channel::Sender<json::Value>,
         jsons: Vec<json::Value>,
+        expires: Option<Instant>,
+        held: Arc<AtomicUsize>,
     ) -> Self {
+        held.fetch_add(jsons.len(), Ordering::AcqRel);
         AutoReturn {
             send_option,
             channel,
             jsons,
+            expires,
+            held,
         }
     }
 
@@ -79,21 +95,31 @@ impl AutoReturn {
         debug!("AutoReturn::into_future.send_option={:?}", self.send_option);
         match self.send_option {
             EndpointProvidesSendOptions::Block => {
-                let _ = self
-                    .channel
-                    .send_all(&mut stream::iter(self.jsons).map(Ok))
-                    .await;
+                while !self.jsons.is_empty() {
+                    let channel = &mut self.channel;
+                    if future::poll_fn(|cx| channel.poll_ready_unpin(cx))
+                        .await
+                        .is_err()
+                    {
+                        self.jsons.clear();
+                        break;
+                    }
+                    let json = self.jsons.remove(0);
+                    self.channel.force_send_with_expiry(json, self.expires);
+                }
             }
             EndpointProvidesSendOptions::Force => {
                 while let Some(json) = self.jsons.pop() {
                     log::trace!("AutoReturn::into_future::Force json={}", json);
-                    self.channel.force_send(json);
+                    self.channel.force_send_with_expiry(json, self.expires);
                 }
             }
             EndpointProvidesSendOptions::IfNotFull => {
                 while let Some(json) = self.jsons.pop() {
                     log::trace!("AutoReturn::into_future::IfNotFull json={}", json);
-                    if self.channel.send(json).now_or_never().is_none() {
+                    let state = self.channel.try_send_with_expiry(json, self.expires);
+                    if let channel::SendState::Full(_) = state {
+                        self.jsons.clear();
                         break;
                     }
                 }
@@ -102,6 +128,38 @@ impl AutoReturn {
     }
 }
 
+impl Clone for AutoReturn {
+    fn clone(&self) -> Self {
+        AutoReturn::new(
+            self.send_option,
+            self.channel.clone(),
+            self.jsons.clone(),
+            self.expires,
+            self.held.clone(),
+        )
+    }
+}
+
+// a request which is dropped before its values are returned, as happens when a test ends, still
+// returns them so they are not lost to the provider (or its `persist` file)
+impl Drop for AutoReturn {
+    fn drop(&mut self) {
+        let held = self.jsons.len();
+        while let Some(json) = self.jsons.pop() {
+            match self.send_option {
+                EndpointProvidesSendOptions::IfNotFull => {
+                    let state = self.channel.try_send_with_expiry(json, self.expires);
+                    if let channel::SendState::Full(_) = state {
+                        break;
+                    }
+                }
+                _ => self.channel.force_send_with_expiry(json, self.expires),
+            }
+        }
+        self.held.fetch_sub(held, Ordering::AcqRel);
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct TemplateValues(json::Value);
 
@@ -156,6 +214,7 @@ impl ProviderOrLogger {
     }
 }
 
+#[derive(Clone)]
 struct Outgoing {
     select: Arc<Select>,
     tx: ProviderOrLogger,
@@ -179,16 +238,29 @@ type ProviderStreamStream<Ar> = Box<
 
 impl ProviderStream<AutoReturn> for providers::Provider {
     fn into_stream(&self) -> ProviderStreamStream<AutoReturn> {
-        let auto_return = self.auto_return.map(|ar| (ar, self.tx.clone()));
-        let future = self.rx.clone().map(move |v| {
+        let auto_return = self
+            .auto_return
+            .map(|ar| (ar, self.tx.clone(), self.auto_returns_held.clone()));
+        let future = self.rx.clone().with_expiry().map(move |(v, expires)| {
             let mut outgoing = Vec::new();
-            if let Some((ar, tx)) = &auto_return {
-                outgoing.push(AutoReturn::new(*ar, tx.clone(), vec![v.clone()]));
+            if let Some((ar, tx, held)) = &auto_return {
+                let jsons = vec![v.clone()];
+                outgoing.push(AutoReturn::new(
+                    *ar,
+                    tx.clone(),
+                    jsons,
+                    expires,
+                    held.clone(),
+                ));
             };
             Ok((v, outgoing))
         });
         Box::new(future)
     }
+
+    fn lookup(&self) -> Option<config::ProviderLookup> {
+        self.lookup.clone()
+    }
 }
 
 pub struct BuilderContext {
@@ -203,6 +275,8 @@ pub struct BuilderContext {
     pub loggers: BTreeMap<String, providers::Logger>,
     // channel that receives and aggregates stats for the test
     pub stats_tx: StatsTx,
+    // the rate limits endpoints have referenced, shared between them
+    pub rate_limiters: BTreeMap<String, RateLimiter>,
 }
 
 pub struct EndpointBuilder {
@@ -241,7 +315,10 @@ impl EndpointBuilder {
             logs,
             on_demand,
             tags,
+            rate_limit,
             request_timeout,
+            think_time,
+            warmup,
             ..
         } = self.endpoint;
         debug!("EndpointBuilder.build method=\"{}\" url=\"{}\" body=\"{}\" headers=\"{:?}\" no_auto_returns=\"{}\" \
@@ -250,6 +327,21 @@ impl EndpointBuilder {
             max_parallel_requests, convert_to_debug(&provides), convert_to_debug(&logs), on_demand, request_timeout);
 
         let timeout = request_timeout.unwrap_or(ctx.config.client.request_timeout);
+        let warmup_until = warmup.map(|w| Instant::now() + w);
+
+        let rate_limits = &ctx.config.general.rate_limits;
+        let rate_limiters = &mut ctx.rate_limiters;
+        let rate_limiter = rate_limit.map(|name| {
+            rate_limiters
+                .entry(name)
+                .or_insert_with_key(|name| {
+                    let hits_per = rate_limits
+                        .get(name)
+                        .expect("rate_limit should reference a valid rate limit");
+                    RateLimiter::new(hits_per)
+                })
+                .clone()
+        });
 
         let mut provides_set = if self.start_stream.is_none() && !provides.is_empty() {
             Some(BTreeSet::new())
@@ -309,16 +401,18 @@ impl EndpointBuilder {
                 Some(p) => p,
                 None => continue,
             };
-            let receiver = provider.rx.clone();
-            let ar = provider
-                .auto_return
-                .map(|send_option| (send_option, provider.tx.clone()));
-            let provider_stream = Box::new(receiver.map(move |v| {
+            let receiver = provider.rx.clone().with_expiry();
+            let ar = provider.auto_return.map(|send_option| {
+                let held = provider.auto_returns_held.clone();
+                (send_option, provider.tx.clone(), held)
+            });
+            let provider_stream = Box::new(receiver.map(move |(v, expires)| {
                 let ar = if no_auto_returns {
                     None
                 } else {
-                    ar.clone()
-                        .map(|(send_option, tx)| AutoReturn::new(send_option, tx, vec![v.clone()]))
+                    ar.clone().map(|(send_option, tx, held)| {
+                        AutoReturn::new(send_option, tx, vec![v.clone()], expires, held)
+                    })
                 };
                 Ok(StreamItem::TemplateValue(
                     name.clone(),
@@ -331,12 +425,19 @@ impl EndpointBuilder {
         }
 
         for (name, vce) in self.endpoint.declare {
-            let stream = vce
-                .into_stream(&ctx.providers, false)
-                .map_ok(move |(v, returns)| {
-                    StreamItem::Declare(name.clone(), v, returns, Instant::now())
-                })
-                .map_err(Into::into);
+            let stream = vce.into_stream(&ctx.providers, false).map(move |r| {
+                match r.map_err(TestError::from) {
+                    Ok((v, returns)) => Ok(StreamItem::Declare(
+                        name.clone(),
+                        v,
+                        returns,
+                        Instant::now(),
+                    )),
+                    // a recoverable error only skips the request the value was for
+                    Err(TestError::Recoverable(r)) => Ok(StreamItem::Recoverable(r)),
+                    Err(e) => Err(e),
+                }
+            });
             streams.push((false, Box::new(stream)));
         }
         let stats_tx = ctx.stats_tx.clone();
@@ -352,12 +453,15 @@ impl EndpointBuilder {
             outgoing,
             precheck_rr_providers,
             provides,
+            rate_limiter,
             rr_providers,
             tags: Arc::new(tags),
             stats_tx,
             stream_collection: streams,
+            think_time,
             url,
             timeout,
+            warmup_until,
         }
     }
 }
@@ -366,6 +470,7 @@ pub enum StreamItem {
     Instant(Option<Instant>),
     Declare(String, json::Value, Vec<AutoReturn>, Instant),
     None,
+    Recoverable(RecoverableError),
     TemplateValue(String, json::Value, Option<AutoReturn>, Instant),
 }
 
@@ -610,12 +715,15 @@ pub struct Endpoint {
     outgoing: Vec<Outgoing>,
     precheck_rr_providers: u16,
     provides: Vec<Outgoing>,
+    rate_limiter: Option<RateLimiter>,
     rr_providers: u16,
     tags: Arc<BTreeMap<String, Template>>,
     stats_tx: StatsTx,
     stream_collection: StreamCollection,
+    think_time: Option<ThinkTime>,
     timeout: Duration,
     url: Template,
+    warmup_until: Option<Instant>,
 }
 
 impl Endpoint {
@@ -637,6 +745,30 @@ impl Endpoint {
         }
     }
 
+    // Creates a `RequestMaker` for requests that are triggered outside of the endpoint's own
+    // streams, along with the rate limit those requests should wait on
+    fn into_request_maker(self) -> (RequestMaker, Option<RateLimiter>) {
+        let mut outgoing = self.outgoing;
+        outgoing.extend(self.provides);
+        let rm = RequestMaker {
+            url: self.url,
+            method: self.method,
+            headers: self.headers,
+            body: self.body,
+            rr_providers: self.rr_providers,
+            client: self.client,
+            stats_tx: self.stats_tx,
+            no_auto_returns: self.no_auto_returns,
+            outgoing: Arc::new(outgoing),
+            precheck_rr_providers: self.precheck_rr_providers,
+            tags: self.tags,
+            think_time: self.think_time,
+            timeout: self.timeout,
+            warmup_until: self.warmup_until,
+        };
+        (rm, self.rate_limiter)
+    }
+
     // This returns a boxed future because otherwise the type system runs out of memory for the type
     pub fn into_future(self) -> Box<dyn Future<Output = Result<(), TestError>> + Send + Unpin> {
         let url = self.url;
@@ -671,6 +803,21 @@ impl Endpoint {
         } else {
             zipped_streams.b()
         };
+        // each request waits its turn in the shared rate limit before it counts against
+        // `max_parallel_requests`
+        let stream = match self.rate_limiter {
+            Some(rate_limiter) => stream
+                .then(move |values| {
+                    let rate_limiter = rate_limiter.clone();
+                    async move {
+                        rate_limiter.wait().await;
+                        values
+                    }
+                })
+                .boxed()
+                .a(),
+            None => stream.b(),
+        };
         let mut outgoing = self.outgoing;
         outgoing.extend(self.provides);
         let outgoing = Arc::new(outgoing);
@@ -678,6 +825,7 @@ impl Endpoint {
         let timeout = self.timeout;
         let max_parallel_requests = self.max_parallel_requests;
         let tags = self.tags;
+        let think_time = self.think_time;
         let blocking_outgoing: Vec<_> = outgoing
             .iter()
             .filter_map(|o| match (&o.tx, o.select.get_send_behavior().is_block()) {
@@ -701,7 +849,9 @@ impl Endpoint {
             outgoing,
             precheck_rr_providers,
             tags,
+            think_time,
             timeout,
+            warmup_until: self.warmup_until,
         };
         let limit_fn: Option<Box<dyn FnMut(usize) -> usize + Send + Unpin>> =
             match (blocking_outgoing.is_empty(), max_parallel_requests) {
@@ -22,12 +22,15 @@ use super::{BlockSender, Outgoing, ProviderDelays, ProviderOrLogger, StatsTx, Te
 pub(super) struct BodyHandler {
     pub(super) included_outgoing_indexes: BTreeSet<usize>,
     pub(super) now: Instant,
+    // when the request was meant to be sent, which is before `now` if it was held up
+    pub(super) intended_start: Instant,
     pub(super) outgoing: Arc<Vec<Outgoing>>,
     pub(super) provider_delays: ProviderDelays,
     pub(super) stats_tx: StatsTx,
     pub(super) status: u16,
     pub(super) tags: Arc<BTreeMap<String, Template>>,
     pub(super) template_values: TemplateValues,
+    pub(super) warmup: bool,
 }
 
 impl BodyHandler {
@@ -45,6 +48,7 @@ impl BodyHandler {
         let outgoing = self.outgoing.clone();
         let has_logger = outgoing.iter().any(|o| o.tx.is_logger());
         let rtt = self.now.elapsed().as_micros() as u64;
+        let corrected_rtt = self.intended_start.elapsed().as_micros() as u64;
         let mut template_values = self.template_values;
         template_values.insert("stats".into(), json::json!({ "rtt": rtt as f64 / 1000.0 }));
         let error_result = match result {
@@ -72,9 +76,10 @@ impl BodyHandler {
             })
             .collect();
         let tags = Arc::new(tags);
-        self.provider_delays.log(&tags, &stats_tx);
+        let warmup = self.warmup;
+        self.provider_delays.log(&tags, &stats_tx, warmup);
 
-        let send_response_stat = move |kind, rtt| {
+        let send_response_stat = move |kind, rtt, corrected_rtt| {
             let mut futures = Vec::new();
             if let stats::StatKind::RecoverableError(e) = &kind {
                 if has_logger {
@@ -106,8 +111,10 @@ impl BodyHandler {
                 stats::ResponseStat {
                     kind,
                     rtt,
+                    corrected_rtt,
                     time: SystemTime::now(),
                     tags: tags.clone(),
+                    warmup,
                 }
                 .into(),
             );
@@ -119,7 +126,7 @@ impl BodyHandler {
         }
         if let Some(e) = error_result {
             let kind = stats::StatKind::RecoverableError(e);
-            futures.push(send_response_stat(kind, None).a3());
+            futures.push(send_response_stat(kind, None, None).a3());
         } else {
             let mut blocked = Vec::new();
             for (i, o) in self.outgoing.iter().enumerate() {
@@ -133,7 +140,7 @@ impl BodyHandler {
                     Err(e) => {
                         let r = RecoverableError::ExecutingExpression(e);
                         let kind = stats::StatKind::RecoverableError(r);
-                        futures.push(send_response_stat(kind, None).a3());
+                        futures.push(send_response_stat(kind, None, None).a3());
                         continue;
                     }
                 };
@@ -153,7 +160,7 @@ impl BodyHandler {
                                 Ok(v) => v,
                                 Err(r) => {
                                     let kind = stats::StatKind::RecoverableError(r);
-                                    futures.push(send_response_stat(kind, None).a3());
+                                    futures.push(send_response_stat(kind, None, None).a3());
                                     break;
                                 }
                             };
@@ -168,7 +175,7 @@ impl BodyHandler {
                                 Ok(v) => v,
                                 Err(r) => {
                                     let kind = stats::StatKind::RecoverableError(r);
-                                    futures.push(send_response_stat(kind, None).a3());
+                                    futures.push(send_response_stat(kind, None, None).a3());
                                     break;
                                 }
                             };
@@ -192,7 +199,14 @@ impl BodyHandler {
                 futures.push(f.b().b3());
             }
         }
-        futures.push(send_response_stat(stats::StatKind::Response(self.status), Some(rtt)).a3());
+        futures.push(
+            send_response_stat(
+                stats::StatKind::Response(self.status),
+                Some(rtt),
+                Some(corrected_rtt),
+            )
+            .a3(),
+        );
         try_join_all(futures).map_ok(|_| ())
     }
 }
@@ -245,6 +259,7 @@ mod tests {
 
         let bh = BodyHandler {
             now,
+            intended_start: now,
             provider_delays: ProviderDelays::new(),
             template_values,
             included_outgoing_indexes,
@@ -252,6 +267,7 @@ mod tests {
             stats_tx,
             status,
             tags,
+            warmup: false,
         };
 
         let auto_return_called = Arc::new(AtomicBool::new(false));
@@ -364,6 +380,7 @@ mod tests {
 
         let bh = BodyHandler {
             now,
+            intended_start: now,
             provider_delays: ProviderDelays::new(),
             template_values,
             included_outgoing_indexes,
@@ -371,6 +388,7 @@ mod tests {
             stats_tx,
             status,
             tags,
+            warmup: false,
         };
 
         type AutoReturns = Option<Box<dyn Future<Output = ()> + Send + Unpin>>;
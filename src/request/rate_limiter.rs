@@ -0,0 +1,102 @@
+use config::HitsPer;
+use futures_timer::Delay;
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+// A named rate limit from `config.general.rate_limits`, shared by every endpoint which references
+// it. This is a token bucket holding up to one second's worth of requests, so traffic under the
+// limit goes out as it arrives and requests only wait once the bucket is empty
+#[derive(Clone)]
+pub struct RateLimiter {
+    per_second: f64,
+    capacity: f64,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+struct Bucket {
+    // goes negative when requests are waiting for tokens which have not been refilled yet
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(hits_per: &HitsPer) -> Self {
+        let per_second = match hits_per {
+            HitsPer::Second(s) => *s as f64,
+            HitsPer::Minute(m) => *m as f64 / 60.0,
+        };
+        // always hold at least one token so a limit under 1hps can still send
+        let capacity = per_second.max(1.0);
+        let bucket = Bucket {
+            tokens: capacity,
+            refilled_at: Instant::now(),
+        };
+        RateLimiter {
+            per_second,
+            capacity,
+            bucket: Arc::new(Mutex::new(bucket)),
+        }
+    }
+
+    // takes a token and returns when it is available
+    fn reserve(&self, now: Instant) -> Instant {
+        let mut bucket = self
+            .bucket
+            .lock()
+            .expect("rate limiter lock should not be poisoned");
+        if now > bucket.refilled_at {
+            let refill = (now - bucket.refilled_at).as_secs_f64() * self.per_second;
+            bucket.tokens = (bucket.tokens + refill).min(self.capacity);
+            bucket.refilled_at = now;
+        }
+        bucket.tokens -= 1.0;
+        if bucket.tokens >= 0.0 {
+            now
+        } else {
+            now + Duration::from_secs_f64(-bucket.tokens / self.per_second)
+        }
+    }
+
+    // resolves once a request can be sent without going over the limit
+    pub async fn wait(&self) {
+        let now = Instant::now();
+        let at = self.reserve(now);
+        if at > now {
+            Delay::new(at - now).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spaces_requests_evenly() {
+        let limiter = RateLimiter::new(&HitsPer::Second(10.0));
+        let shared = limiter.clone();
+        let now = Instant::now();
+        // a burst up to the bucket's capacity is not delayed
+        for _ in 0..5 {
+            assert_eq!(limiter.reserve(now), now);
+            assert_eq!(shared.reserve(now), now);
+        }
+        // once the bucket is empty requests are spaced at the rate
+        let first = limiter.reserve(now);
+        let second = shared.reserve(now);
+        assert_eq!(first - now, Duration::from_millis(100));
+        assert_eq!(second - first, Duration::from_millis(100));
+
+        // the bucket refills over time
+        let later = now + Duration::from_secs(2);
+        assert_eq!(limiter.reserve(later), later);
+
+        let limiter = RateLimiter::new(&HitsPer::Minute(30.0));
+        let now = Instant::now();
+        assert_eq!(limiter.reserve(now), now);
+        assert_eq!(limiter.reserve(now) - now, Duration::from_secs(2));
+    }
+}
@@ -2,8 +2,8 @@ use crate::error::{RecoverableError, TestError};
 use crate::stats;
 
 use config::{
-    BodyTemplate, Template, REQUEST_BODY, REQUEST_HEADERS, REQUEST_HEADERS_ALL, REQUEST_STARTLINE,
-    REQUEST_URL,
+    BodyTemplate, Template, ThinkTime, REQUEST_BODY, REQUEST_HEADERS, REQUEST_HEADERS_ALL,
+    REQUEST_STARTLINE, REQUEST_URL,
 };
 use ether::EitherExt;
 use futures::{
@@ -18,6 +18,7 @@ use hyper::{
 };
 use hyper_tls::HttpsConnector;
 use log::{debug, info};
+use rand::Rng;
 use serde_json as json;
 
 use super::{
@@ -48,7 +49,10 @@ pub(super) struct RequestMaker {
     pub(super) outgoing: Arc<Vec<Outgoing>>,
     pub(super) precheck_rr_providers: u16,
     pub(super) tags: Arc<BTreeMap<String, Template>>,
+    pub(super) think_time: Option<ThinkTime>,
     pub(super) timeout: Duration,
+    // requests meant to go out before this are part of the warmup
+    pub(super) warmup_until: Option<Instant>,
 }
 
 pub(super) struct ProviderDelays {
@@ -64,15 +68,22 @@ impl ProviderDelays {
         self.inner.push(name)
     }
 
-    pub(super) fn log(self, tags: &Arc<BTreeMap<String, String>>, stats_tx: &StatsTx) {
+    pub(super) fn log(
+        self,
+        tags: &Arc<BTreeMap<String, String>>,
+        stats_tx: &StatsTx,
+        warmup: bool,
+    ) {
         for provider in self.inner {
             let kind = stats::StatKind::RecoverableError(RecoverableError::ProviderDelay(provider));
             let _ = stats_tx.unbounded_send(
                 stats::ResponseStat {
                     kind,
                     rtt: None,
+                    corrected_rtt: None,
                     time: SystemTime::now(),
                     tags: tags.clone(),
+                    warmup,
                 }
                 .into(),
             );
@@ -80,40 +91,67 @@ impl ProviderDelays {
     }
 }
 
+// evaluates how long to wait before sending a request. A value which is not a valid duration is
+// a recoverable error, as it likely came from a provider
+fn think_time_duration(
+    think_time: &ThinkTime,
+    template_values: &TemplateValues,
+) -> Result<Duration, TestError> {
+    let evaluate = |template: &Template| {
+        let d = template.evaluate(Cow::Borrowed(template_values.as_json()), None)?;
+        config::think_time_from_string(d.clone())
+            .map_err(|_| RecoverableError::InvalidThinkTime(d).into())
+    };
+    match think_time {
+        ThinkTime::Fixed(t) => evaluate(t),
+        ThinkTime::Random(min, max) => {
+            let (min, max) = (evaluate(min)?, evaluate(max)?);
+            if min >= max {
+                Ok(min)
+            } else {
+                Ok(rand::thread_rng().gen_range(min..=max))
+            }
+        }
+    }
+}
+
 impl RequestMaker {
     // this function is not async because of a compiler bug which raises a nonsensical error
     // https://github.com/rust-lang/rust/issues/71723
     pub(super) fn send_request(
         &self,
         values: Vec<StreamItem>,
+    ) -> impl Future<Output = Result<(), TestError>> {
+        self.send_request_with_outgoing(values, self.outgoing.clone())
+    }
+
+    // the same as `send_request` but with the providers and loggers the response is sent to
+    // given by the caller, rather than those of the endpoint
+    pub(super) fn send_request_with_outgoing(
+        &self,
+        values: Vec<StreamItem>,
+        outgoing: Arc<Vec<Outgoing>>,
     ) -> impl Future<Output = Result<(), TestError>> {
         let mut template_values = TemplateValues::new();
         let mut auto_returns = Vec::new();
         let mut target_instant = None;
         let mut provider_delays = ProviderDelays::new();
+        let mut received = Vec::new();
+        let mut recoverable = None;
         for tv in values {
             match tv {
                 StreamItem::Instant(next_trigger) => {
                     target_instant = next_trigger;
                 }
                 StreamItem::Declare(name, value, returns, instant) => {
-                    match target_instant {
-                        Some(target_instant) if instant > target_instant => {
-                            provider_delays.push(name.clone());
-                        }
-                        _ => (),
-                    }
+                    received.push((name.clone(), instant));
                     template_values.insert(name, value);
                     auto_returns.extend(returns.into_iter().map(AutoReturn::into_future));
                 }
                 StreamItem::None => (),
+                StreamItem::Recoverable(r) => recoverable = Some(r),
                 StreamItem::TemplateValue(name, value, auto_return, instant) => {
-                    match target_instant {
-                        Some(target_instant) if instant > target_instant => {
-                            provider_delays.push(name.clone());
-                        }
-                        _ => (),
-                    }
+                    received.push((name.clone(), instant));
                     template_values.insert(name, value);
                     if let (Some(ar), false) = (auto_return, self.no_auto_returns) {
                         auto_returns.push(ar.into_future());
@@ -121,6 +159,53 @@ impl RequestMaker {
                 }
             };
         }
+        let warmup = self
+            .warmup_until
+            .is_some_and(|w| target_instant.unwrap_or_else(Instant::now) < w);
+        let think_time = match (recoverable, &self.think_time) {
+            (Some(r), _) => Err(TestError::Recoverable(r)),
+            (None, Some(t)) => think_time_duration(t, &template_values),
+            (None, None) => Ok(Duration::default()),
+        };
+        let think_time = match think_time {
+            Ok(d) => d,
+            // only this request is skipped, and its provider values are still returned
+            Err(TestError::Recoverable(r)) => {
+                let tags = self
+                    .tags
+                    .iter()
+                    .filter_map(|(k, v)| {
+                        v.evaluate(Cow::Borrowed(template_values.as_json()), None)
+                            .ok()
+                            .map(move |v| (k.clone(), v))
+                    })
+                    .collect();
+                let _ = self.stats_tx.unbounded_send(
+                    stats::ResponseStat {
+                        kind: stats::StatKind::RecoverableError(r),
+                        rtt: None,
+                        corrected_rtt: None,
+                        time: SystemTime::now(),
+                        tags: Arc::new(tags),
+                        warmup,
+                    }
+                    .into(),
+                );
+                return join_all(auto_returns).map(|_| Ok(())).c3().b();
+            }
+            Err(e) => return future::ready(Err(e)).a(),
+        };
+        // a value is only late if it held the request up past its target time. The target
+        // includes the think time, so an intentional wait is not reported as a provider delay
+        // or counted in the corrected rtt
+        let target_instant = target_instant.map(|t| t + think_time);
+        if let Some(target_instant) = target_instant {
+            for (name, instant) in received {
+                if instant > target_instant {
+                    provider_delays.push(name);
+                }
+            }
+        }
         let auto_returns = if auto_returns.is_empty() {
             None
         } else {
@@ -172,7 +257,6 @@ impl RequestMaker {
 
         let client = self.client.clone();
         let stats_tx = self.stats_tx.clone();
-        let outgoing = self.outgoing.clone();
         let timeout_in_micros = self.timeout.as_micros() as u64;
         let precheck_rr_providers = self.precheck_rr_providers;
         let rr_providers = self.rr_providers;
@@ -181,7 +265,7 @@ impl RequestMaker {
         let tags = self.tags.clone();
         let auto_returns2 = auto_returns.clone();
 
-        body.and_then(move |(content_length, body)| {
+        let request = body.and_then(move |(content_length, body)| {
             let request = request.body(body);
             let mut request = match request {
                 Ok(r) => r,
@@ -301,6 +385,9 @@ impl RequestMaker {
             let stats_tx2 = stats_tx.clone();
             let tags2 = tags.clone();
             let now = Instant::now();
+            // when the request was meant to go out, so time spent waiting on providers or
+            // `max_parallel_requests` is included in the corrected rtt
+            let intended_start = target_instant.map_or(now, |t| t.min(now));
 
             let mut timeout = Delay::new(timeout);
                 future::poll_fn(move |cx| {
@@ -324,8 +411,10 @@ impl RequestMaker {
                         rr_providers,
                         outgoing,
                         now,
+                        intended_start,
                         stats_tx,
                         tags,
+                        warmup,
                     };
                     rh.handle(response, auto_returns)
                         .map_err(TestError::from)
@@ -367,16 +456,21 @@ impl RequestMaker {
                         RecoverableError::Timeout(t) | RecoverableError::ConnectionErr(t, _) => t,
                         _ => SystemTime::now(),
                     };
-                    let rtt = match r {
-                        RecoverableError::Timeout(_) => Some(timeout_in_micros),
-                        _ => None,
+                    let (rtt, corrected_rtt) = match r {
+                        RecoverableError::Timeout(_) => (
+                            Some(timeout_in_micros),
+                            Some(intended_start.elapsed().as_micros() as u64),
+                        ),
+                        _ => (None, None),
                     };
                     let _ = stats_tx2.unbounded_send(
                         stats::ResponseStat {
                             kind: stats::StatKind::RecoverableError(r),
                             rtt,
+                            corrected_rtt,
                             time,
                             tags,
+                            warmup,
                         }
                         .into(),
                     );
@@ -390,7 +484,12 @@ impl RequestMaker {
             } else {
                 future::ready(Ok(())).b()
             }
-        }).b()
+        });
+        if think_time == Duration::default() {
+            request.a3().b()
+        } else {
+            Delay::new(think_time).then(move |_| request).b3().b()
+        }
     }
 }
 
@@ -398,7 +497,7 @@ impl RequestMaker {
 mod tests {
     use super::*;
     use crate::create_http_client;
-    use futures::channel::mpsc as futures_channel;
+    use futures::{channel::mpsc as futures_channel, StreamExt};
     use tokio::runtime::Runtime;
 
     #[test]
@@ -418,6 +517,7 @@ mod tests {
             let outgoing = Vec::new().into();
             let timeout = Duration::from_secs(120);
             let tags = Arc::new(BTreeMap::new());
+            let think_time = None;
 
             let rm = RequestMaker {
                 url,
@@ -431,11 +531,223 @@ mod tests {
                 outgoing,
                 precheck_rr_providers,
                 tags,
+                think_time,
                 timeout,
+                warmup_until: None,
             };
 
             let r = rm.send_request(Vec::new()).await;
             assert!(r.is_ok());
         });
     }
+
+    #[test]
+    fn corrects_rtt_for_late_requests() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (port, kill_server, server) = test_common::start_test_server(None);
+            tokio::spawn(server);
+            let url = Template::simple(&format!("http://127.0.0.1:{}", port));
+            let client = create_http_client(Duration::from_secs(60)).unwrap().into();
+            let (stats_tx, mut stats_rx) = futures_channel::unbounded();
+
+            let rm = RequestMaker {
+                url,
+                method: Method::GET,
+                headers: Vec::new(),
+                body: BodyTemplate::None,
+                rr_providers: 0,
+                client,
+                stats_tx,
+                no_auto_returns: true,
+                outgoing: Vec::new().into(),
+                precheck_rr_providers: 0,
+                tags: Arc::new(BTreeMap::new()),
+                think_time: None,
+                timeout: Duration::from_secs(120),
+                warmup_until: None,
+            };
+
+            // the request should have gone out 50ms ago
+            let late = Instant::now() - Duration::from_millis(50);
+            let r = rm.send_request(vec![StreamItem::Instant(Some(late))]).await;
+            assert!(r.is_ok());
+            drop(rm);
+
+            let stat = match stats_rx.next().await {
+                Some(stats::StatsMessage::ResponseStat(stat)) => stat,
+                _ => unreachable!("should get a response stat"),
+            };
+            let rtt = stat.rtt.expect("should have an rtt");
+            let corrected_rtt = stat.corrected_rtt.expect("should have a corrected rtt");
+            assert!(corrected_rtt >= rtt + 50_000, "{} {}", rtt, corrected_rtt);
+            let _ = kill_server.send(());
+        });
+    }
+
+    #[test]
+    fn think_time_is_not_in_corrected_rtt() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (port, kill_server, server) = test_common::start_test_server(None);
+            tokio::spawn(server);
+            let url = Template::simple(&format!("http://127.0.0.1:{}", port));
+            let client = create_http_client(Duration::from_secs(60)).unwrap().into();
+            let (stats_tx, mut stats_rx) = futures_channel::unbounded();
+
+            let rm = RequestMaker {
+                url,
+                method: Method::GET,
+                headers: Vec::new(),
+                body: BodyTemplate::None,
+                rr_providers: 0,
+                client,
+                stats_tx,
+                no_auto_returns: true,
+                outgoing: Vec::new().into(),
+                precheck_rr_providers: 0,
+                tags: Arc::new(BTreeMap::new()),
+                think_time: Some(ThinkTime::Fixed(Template::simple("1s"))),
+                timeout: Duration::from_secs(120),
+                warmup_until: None,
+            };
+
+            let start = Instant::now();
+            let r = rm
+                .send_request(vec![StreamItem::Instant(Some(start))])
+                .await;
+            assert!(r.is_ok());
+            assert!(start.elapsed() >= Duration::from_secs(1));
+            drop(rm);
+
+            let stat = match stats_rx.next().await {
+                Some(stats::StatsMessage::ResponseStat(stat)) => stat,
+                _ => unreachable!("should get a response stat"),
+            };
+            let corrected_rtt = stat.corrected_rtt.expect("should have a corrected rtt");
+            assert!(corrected_rtt < 500_000, "{}", corrected_rtt);
+            let _ = kill_server.send(());
+        });
+    }
+
+    #[test]
+    fn flags_warmup_requests() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (port, kill_server, server) = test_common::start_test_server(None);
+            tokio::spawn(server);
+            let url = Template::simple(&format!("http://127.0.0.1:{}", port));
+            let client = create_http_client(Duration::from_secs(60)).unwrap().into();
+            let (stats_tx, stats_rx) = futures_channel::unbounded();
+
+            let rm = RequestMaker {
+                url,
+                method: Method::GET,
+                headers: Vec::new(),
+                body: BodyTemplate::None,
+                rr_providers: 0,
+                client,
+                stats_tx,
+                no_auto_returns: true,
+                outgoing: Vec::new().into(),
+                precheck_rr_providers: 0,
+                tags: Arc::new(BTreeMap::new()),
+                think_time: None,
+                timeout: Duration::from_secs(120),
+                warmup_until: Some(Instant::now() + Duration::from_secs(60)),
+            };
+
+            let warmup = Instant::now();
+            let r = rm
+                .send_request(vec![StreamItem::Instant(Some(warmup))])
+                .await;
+            assert!(r.is_ok());
+            let after_warmup = Instant::now() + Duration::from_secs(61);
+            let r = rm
+                .send_request(vec![StreamItem::Instant(Some(after_warmup))])
+                .await;
+            assert!(r.is_ok());
+            drop(rm);
+
+            let warmups: Vec<_> = stats_rx
+                .filter_map(|s| async move {
+                    match s {
+                        stats::StatsMessage::ResponseStat(stat) => Some(stat.warmup),
+                        _ => None,
+                    }
+                })
+                .collect()
+                .await;
+            assert_eq!(warmups, vec![true, false]);
+            let _ = kill_server.send(());
+        });
+    }
+
+    #[test]
+    fn invalid_think_time_is_recoverable() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async move {
+            let client = create_http_client(Duration::from_secs(60)).unwrap().into();
+            let (stats_tx, stats_rx) = futures_channel::unbounded();
+            let (tx, mut rx) = channel::channel(channel::Limit::statik(1), false, "token");
+
+            let rm = RequestMaker {
+                url: Template::simple("http://127.0.0.1:1"),
+                method: Method::GET,
+                headers: Vec::new(),
+                body: BodyTemplate::None,
+                rr_providers: 0,
+                client,
+                stats_tx,
+                no_auto_returns: false,
+                outgoing: Vec::new().into(),
+                precheck_rr_providers: 0,
+                tags: Arc::new(BTreeMap::new()),
+                think_time: Some(ThinkTime::Fixed(Template::simple("soon"))),
+                timeout: Duration::from_secs(120),
+                warmup_until: None,
+            };
+
+            let auto_return = AutoReturn::new(
+                config::EndpointProvidesSendOptions::Force,
+                tx,
+                vec![json::json!("abc")],
+                None,
+                Default::default(),
+            );
+            let values = vec![StreamItem::TemplateValue(
+                "token".into(),
+                json::json!("abc"),
+                Some(auto_return),
+                Instant::now(),
+            )];
+            let r = rm.send_request(values).await;
+            assert!(r.is_ok(), "the test should continue");
+            drop(rm);
+
+            let left = rx.next().await;
+            let right = Some(json::json!("abc"));
+            assert_eq!(left, right, "the provider value is returned");
+
+            let kinds: Vec<_> = stats_rx
+                .filter_map(|s| async move {
+                    match s {
+                        stats::StatsMessage::ResponseStat(stat) => Some(stat.kind),
+                        _ => None,
+                    }
+                })
+                .collect()
+                .await;
+            assert!(
+                matches!(
+                    &kinds[..],
+                    [stats::StatKind::RecoverableError(
+                        RecoverableError::InvalidThinkTime(d)
+                    )] if d == "soon"
+                ),
+                "{:?}",
+                kinds
+            );
+        });
+    }
 }
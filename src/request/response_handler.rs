@@ -10,8 +10,10 @@ pub(super) struct ResponseHandler {
     pub(super) rr_providers: u16,
     pub(super) outgoing: Arc<Vec<Outgoing>>,
     pub(super) now: Instant,
+    pub(super) intended_start: Instant,
     pub(super) stats_tx: StatsTx,
     pub(super) tags: Arc<BTreeMap<String, Template>>,
+    pub(super) warmup: bool,
 }
 
 impl ResponseHandler {
@@ -120,20 +122,24 @@ impl ResponseHandler {
         };
         let provider_delays = self.provider_delays;
         let now = self.now;
+        let intended_start = self.intended_start;
         let outgoing = self.outgoing;
         let stats_tx = self.stats_tx;
         let tags = self.tags;
+        let warmup = self.warmup;
         body_future
             .then(move |body_value| {
                 let bh = BodyHandler {
                     included_outgoing_indexes,
                     now,
+                    intended_start,
                     outgoing,
                     provider_delays,
                     stats_tx,
                     status,
                     tags,
                     template_values,
+                    warmup,
                 };
                 bh.handle(body_value, auto_returns)
             })
@@ -212,8 +218,10 @@ mod tests {
             rr_providers,
             outgoing,
             now,
+            intended_start: now,
             stats_tx,
             tags,
+            warmup: false,
         };
 
         let auto_returns: Option<futures::future::Pending<_>> = None;
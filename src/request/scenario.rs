@@ -0,0 +1,283 @@
+use crate::error::TestError;
+use crate::providers;
+
+use channel::Limit;
+use config::Select;
+use futures::{
+    future::{self, join_all},
+    stream::FuturesUnordered,
+    FutureExt, StreamExt,
+};
+use futures_timer::Delay;
+use log::debug;
+use mod_interval::ModInterval;
+use serde_json as json;
+
+use super::{
+    AutoReturn, BuilderContext, EndpointBuilder, Outgoing, ProviderOrLogger, RateLimiter,
+    RequestMaker, StreamItem,
+};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+// how often the number of virtual users is checked against the load_pattern
+const USER_ADJUST_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct ScenarioBuilder {
+    name: String,
+    steps: Vec<config::Endpoint>,
+    // the number of virtual users over time. When `None` a single user makes one pass through
+    // the steps (as is done for a try run)
+    users: Option<ModInterval>,
+    start_at: Option<Duration>,
+}
+
+impl ScenarioBuilder {
+    pub fn new(
+        scenario: config::Scenario,
+        users: Option<ModInterval>,
+        start_at: Option<Duration>,
+    ) -> Self {
+        ScenarioBuilder {
+            name: scenario.name,
+            steps: scenario.steps,
+            users,
+            start_at,
+        }
+    }
+
+    pub fn build(self, ctx: &mut BuilderContext) -> Scenario {
+        debug!(
+            "ScenarioBuilder.build name=\"{}\" steps={}",
+            self.name,
+            self.steps.len()
+        );
+        // the names given values by the `provides` of the steps so far. Those values only go to
+        // the user which made the request, so later steps never take them from the provider
+        let mut provided_so_far = BTreeSet::new();
+        let steps = self
+            .steps
+            .into_iter()
+            .map(|mut step| {
+                let provides = std::mem::take(&mut step.provides);
+                let (provided, providers) = step
+                    .providers_to_stream
+                    .clone()
+                    .unique_providers()
+                    .into_iter()
+                    .partition::<Vec<_>, _>(|name| provided_so_far.contains(name));
+                let providers = providers
+                    .into_iter()
+                    .filter_map(|name| ctx.providers.get(&name).cloned().map(|p| (name, p)))
+                    .collect();
+                provided_so_far.extend(provides.iter().map(|(name, _)| name.clone()));
+                let (request_maker, rate_limiter) = EndpointBuilder::new(step, None)
+                    .build(ctx)
+                    .into_request_maker();
+                Step {
+                    provided,
+                    provides,
+                    providers,
+                    rate_limiter,
+                    request_maker,
+                }
+            })
+            .collect();
+        Scenario {
+            steps: Arc::new(steps),
+            users: self.users,
+            start_at: self.start_at,
+        }
+    }
+}
+
+struct Step {
+    // the values this step needs which an earlier step provides
+    provided: Vec<String>,
+    // the values this step provides to the later steps of the same user
+    provides: Vec<(String, Select)>,
+    // the providers this step needs which are not yet bound for the user
+    providers: Vec<(String, providers::Provider)>,
+    rate_limiter: Option<RateLimiter>,
+    request_maker: RequestMaker,
+}
+
+pub struct Scenario {
+    steps: Arc<Vec<Step>>,
+    users: Option<ModInterval>,
+    start_at: Option<Duration>,
+}
+
+impl Scenario {
+    pub async fn into_future(self) -> Result<(), TestError> {
+        let users = match self.users {
+            Some(users) => users,
+            None => {
+                return VirtualUser::new(0, self.steps, None)
+                    .run()
+                    .await
+                    .map(|_| ());
+            }
+        };
+        let start_time = Instant::now() - self.start_at.unwrap_or_default();
+        let target_users = Arc::new(AtomicUsize::new(0));
+        let mut live_users = BTreeSet::new();
+        let mut running = FuturesUnordered::new();
+        while let Some(users_now) = users.hits_per_second_at(start_time.elapsed()) {
+            let users_now = users_now.round() as usize;
+            target_users.store(users_now, Ordering::Relaxed);
+            // users above the target stop on their own after finishing their current pass
+            for id in 0..users_now {
+                if live_users.insert(id) {
+                    let user = VirtualUser::new(id, self.steps.clone(), Some(target_users.clone()));
+                    running.push(user.run());
+                }
+            }
+
+            let mut adjust = Delay::new(USER_ADJUST_INTERVAL);
+            loop {
+                match future::select(&mut adjust, running.next()).await {
+                    future::Either::Left(_) => break,
+                    future::Either::Right((Some(r), _)) => match r? {
+                        Some(id) => {
+                            live_users.remove(&id);
+                        }
+                        // a provider has ended, so no user can continue
+                        None => return Ok(()),
+                    },
+                    future::Either::Right((None, _)) => {
+                        (&mut adjust).await;
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct VirtualUser {
+    id: usize,
+    // for each step, where its response is sent. This is the step's loggers along with
+    // channels owned by this user for the step's provides
+    outgoing: Vec<Arc<Vec<Outgoing>>>,
+    // the receiving side of the channels the steps provide to
+    provided: BTreeMap<String, channel::Receiver<json::Value>>,
+    steps: Arc<Vec<Step>>,
+    target_users: Option<Arc<AtomicUsize>>,
+}
+
+impl VirtualUser {
+    fn new(id: usize, steps: Arc<Vec<Step>>, target_users: Option<Arc<AtomicUsize>>) -> Self {
+        let mut senders = BTreeMap::new();
+        let mut provided = BTreeMap::new();
+        let outgoing = steps
+            .iter()
+            .map(|step| {
+                let mut outgoing: Vec<_> = step.request_maker.outgoing.iter().cloned().collect();
+                for (name, select) in &step.provides {
+                    let tx = senders.entry(name.clone()).or_insert_with(|| {
+                        // the channel is drained after every step, so it never needs to block
+                        let (tx, rx) = channel::channel(Limit::statik(usize::MAX), false, name);
+                        provided.insert(name.clone(), rx);
+                        tx
+                    });
+                    let tx = ProviderOrLogger::Provider(tx.clone());
+                    outgoing.push(Outgoing::new(select.clone(), tx));
+                }
+                Arc::new(outgoing)
+            })
+            .collect();
+        VirtualUser {
+            id,
+            outgoing,
+            provided,
+            steps,
+            target_users,
+        }
+    }
+
+    // Makes passes through the steps until there are more users than the target. Resolves to the
+    // user's id, or `None` if a provider the steps need has ended
+    async fn run(mut self) -> Result<Option<usize>, TestError> {
+        loop {
+            if let Some(target_users) = &self.target_users {
+                if self.id >= target_users.load(Ordering::Relaxed) {
+                    break;
+                }
+            }
+            // provider values are bound the first time a step needs them and then stay the
+            // same for the rest of the steps in this pass
+            let mut bound: BTreeMap<String, (json::Value, Option<AutoReturn>)> = BTreeMap::new();
+            let mut replaced = Vec::new();
+            for (step, outgoing) in self.steps.iter().zip(&self.outgoing) {
+                // an earlier step did not provide a value this step needs (likely because its
+                // request failed), so this pass ends early
+                if step.provided.iter().any(|name| !bound.contains_key(name)) {
+                    break;
+                }
+                for (name, provider) in &step.providers {
+                    if bound.contains_key(name) {
+                        continue;
+                    }
+                    let (value, expires) = match provider.rx.clone().with_expiry().next().await {
+                        Some(v) => v,
+                        None => return Ok(None),
+                    };
+                    let auto_return = provider.auto_return.map(|send_option| {
+                        let tx = provider.tx.clone();
+                        let held = provider.auto_returns_held.clone();
+                        AutoReturn::new(send_option, tx, vec![value.clone()], expires, held)
+                    });
+                    bound.insert(name.clone(), (value, auto_return));
+                }
+                let values = bound
+                    .iter()
+                    .map(|(name, (value, _))| {
+                        StreamItem::TemplateValue(name.clone(), value.clone(), None, Instant::now())
+                    })
+                    .collect();
+                if let Some(rate_limiter) = &step.rate_limiter {
+                    rate_limiter.wait().await;
+                }
+                step.request_maker
+                    .send_request_with_outgoing(values, outgoing.clone())
+                    .await?;
+                // the first value a step provides is bound for the rest of the pass
+                for (name, _) in &step.provides {
+                    let rx = self
+                        .provided
+                        .get_mut(name)
+                        .expect("provided channel should exist");
+                    let mut value = None;
+                    while let Some(Some(v)) = rx.next().now_or_never() {
+                        value.get_or_insert(v);
+                    }
+                    if let Some(value) = value {
+                        if let Some((_, Some(ar))) = bound.insert(name.clone(), (value, None)) {
+                            replaced.push(ar);
+                        }
+                    }
+                }
+            }
+            // bound values are returned once, after every step has used them
+            let auto_returns = bound
+                .into_iter()
+                .filter_map(|(_, (_, auto_return))| auto_return)
+                .chain(replaced)
+                .map(AutoReturn::into_future);
+            join_all(auto_returns).await;
+            if self.target_users.is_none() {
+                break;
+            }
+        }
+        Ok(Some(self.id))
+    }
+}
@@ -0,0 +1,298 @@
+use crate::error::TestError;
+use crate::line_writer::MsgType;
+use crate::stats::{BucketGroupStats, StatsMessage};
+use crate::{RunOutputFormat, SearchConfig, TestEndReason};
+
+use futures::{
+    channel::{
+        mpsc::{Sender as FCSender, UnboundedSender as FCUnboundedSender},
+        oneshot,
+    },
+    future, FutureExt, SinkExt, StreamExt,
+};
+use futures_timer::Delay;
+use log::debug;
+use mod_interval::{PerX, RateControl};
+use serde_json as json;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use yansi::Paint;
+
+use std::time::{Duration, Instant};
+
+// the outcome of holding the searched endpoint at one rate
+struct StepResult {
+    rate: f64,
+    request_count: u64,
+    rtt: Duration,
+    error_rate: f64,
+    passed: bool,
+}
+
+impl StepResult {
+    fn new(rate: f64, stats: Option<BucketGroupStats>, search_config: &SearchConfig) -> Self {
+        let stats = stats.unwrap_or_default();
+        let request_count = stats.request_count();
+        let rtt = stats.rtt_at_percentile(search_config.percentile);
+        let error_rate = stats.error_rate() * 100.0;
+        // a step with no requests can't show the rate is sustainable
+        let passed = request_count > 0
+            && rtt <= search_config.latency
+            && error_rate <= search_config.error_rate;
+        StepResult {
+            rate,
+            request_count,
+            rtt,
+            error_rate,
+            passed,
+        }
+    }
+
+    fn to_message(&self, percentile: f64, format: RunOutputFormat) -> String {
+        let rtt = self.rtt.as_micros() as f64 / 1_000.0;
+        match format {
+            RunOutputFormat::Human => {
+                let outcome = if self.passed {
+                    Paint::green("passed")
+                } else {
+                    Paint::red("failed")
+                };
+                format!(
+                    "Search step {:.2} hps: {} calls, p{}: {}ms, error rate: {:.2}% - {}\n",
+                    self.rate, self.request_count, percentile, rtt, self.error_rate, outcome
+                )
+            }
+            RunOutputFormat::Json => {
+                let output = json::json!({
+                    "type": "search_step",
+                    "rate": self.rate,
+                    "callCount": self.request_count,
+                    "percentile": percentile,
+                    "rtt": rtt,
+                    "errorRate": self.error_rate,
+                    "passed": self.passed,
+                });
+                format!("{}\n", output)
+            }
+        }
+    }
+}
+
+// the next rate to try, or `None` once the search has narrowed down the highest passing rate
+fn next_rate(
+    highest_passed: Option<f64>,
+    lowest_failed: Option<f64>,
+    search_config: &SearchConfig,
+) -> Option<f64> {
+    match (highest_passed, lowest_failed) {
+        // still ramping up
+        (Some(passed), None) => match search_config.max_rate {
+            Some(max_rate) if passed >= max_rate => None,
+            Some(max_rate) => Some((passed * 2.0).min(max_rate)),
+            None => Some(passed * 2.0),
+        },
+        // binary search between the highest passing and lowest failing rates
+        (Some(passed), Some(failed)) => {
+            if (failed - passed) / passed <= search_config.precision / 100.0 {
+                None
+            } else {
+                Some((passed + failed) / 2.0)
+            }
+        }
+        // the starting rate failed, so the search stops
+        (None, _) => None,
+    }
+}
+
+fn result_message(
+    highest_passed: Option<f64>,
+    out_of_time: bool,
+    search_config: &SearchConfig,
+    format: RunOutputFormat,
+) -> String {
+    match format {
+        RunOutputFormat::Human => {
+            let mut msg = match highest_passed {
+                Some(rate) => format!(
+                    "\n{} {:.2} hps\n",
+                    Paint::new("Highest sustainable rate:").bold(),
+                    rate
+                ),
+                None => format!(
+                    "\n{}\n",
+                    Paint::yellow(format!(
+                        "No rate met the SLO. The starting rate of {} hps failed",
+                        search_config.start_rate
+                    ))
+                    .bold()
+                ),
+            };
+            if out_of_time {
+                msg.push_str("The search was stopped early after reaching its max duration\n");
+            }
+            msg
+        }
+        RunOutputFormat::Json => {
+            let output = json::json!({
+                "type": "search_result",
+                "rate": highest_passed,
+                "stoppedEarly": out_of_time,
+            });
+            format!("{}\n", output)
+        }
+    }
+}
+
+// gets the stats for the searched endpoint since the last time this was called
+async fn take_window(
+    stats_tx: &FCUnboundedSender<StatsMessage>,
+    endpoint_id: &str,
+) -> Option<BucketGroupStats> {
+    let (tx, rx) = oneshot::channel();
+    stats_tx.unbounded_send(StatsMessage::TakeWindow(tx)).ok()?;
+    let window = rx.await.ok()?;
+    window
+        .into_iter()
+        .filter(|(tags, _)| tags.get("_id").map(String::as_str) == Some(endpoint_id))
+        .map(|(_, stats)| stats)
+        .reduce(|mut a, b| {
+            a.combine(&b);
+            a
+        })
+}
+
+// Adjusts the rate of the searched endpoint based on the live stats. The rate doubles until a
+// step breaks the SLO, then a binary search narrows in on the highest rate which meets the SLO
+async fn run_search(
+    control: RateControl,
+    endpoint_id: String,
+    search_config: SearchConfig,
+    stats_tx: FCUnboundedSender<StatsMessage>,
+    mut stdout: FCSender<MsgType>,
+) {
+    let format = search_config.run_config.output_format;
+    let start = Instant::now();
+    let step_time = search_config.settle_time + search_config.step_duration;
+    let mut highest_passed = None;
+    let mut lowest_failed = None;
+    let mut rate = Some(search_config.start_rate);
+    let mut out_of_time = false;
+    while let Some(r) = rate {
+        if start.elapsed() + step_time > search_config.max_duration {
+            out_of_time = true;
+            break;
+        }
+        debug!("search rate={}", r);
+        control.set(PerX::second(r));
+        // give the service time to settle at the new rate (and recover after a failed step)
+        // before measuring
+        Delay::new(search_config.settle_time).await;
+        let _ = take_window(&stats_tx, &endpoint_id).await;
+        Delay::new(search_config.step_duration).await;
+        let stats = take_window(&stats_tx, &endpoint_id).await;
+
+        let step = StepResult::new(r, stats, &search_config);
+        let msg = step.to_message(search_config.percentile, format);
+        let _ = stdout.send(MsgType::Other(msg)).await;
+        if step.passed {
+            highest_passed = Some(r);
+        } else {
+            lowest_failed = Some(r);
+        }
+        rate = next_rate(highest_passed, lowest_failed, &search_config);
+    }
+    let msg = result_message(highest_passed, out_of_time, &search_config, format);
+    let _ = stdout.send(MsgType::Other(msg)).await;
+}
+
+// Creates the future which runs the search and ends the test when the search is done
+pub(crate) fn create_search_future(
+    control: RateControl,
+    endpoint_id: String,
+    search_config: SearchConfig,
+    stats_tx: FCUnboundedSender<StatsMessage>,
+    stdout: FCSender<MsgType>,
+    test_ended_tx: broadcast::Sender<Result<TestEndReason, TestError>>,
+) -> impl std::future::Future<Output = ()> {
+    let mut test_ended_rx = BroadcastStream::new(test_ended_tx.subscribe());
+    let search = run_search(control, endpoint_id, search_config, stats_tx, stdout).boxed();
+    let test_ended = async move {
+        while let Some(r) = test_ended_rx.next().await {
+            if !matches!(r, Ok(Ok(TestEndReason::ConfigUpdate(_)))) {
+                break;
+            }
+        }
+    }
+    .boxed();
+    future::select(search, test_ended).map(move |r| {
+        if let future::Either::Left(_) = r {
+            let _ = test_ended_tx.send(Ok(TestEndReason::Completed));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RunConfig, StatsFileFormat};
+
+    fn search_config(max_rate: Option<f64>) -> SearchConfig {
+        SearchConfig {
+            run_config: RunConfig {
+                config_file: "".into(),
+                output_format: RunOutputFormat::Human,
+                results_dir: None,
+                start_at: None,
+                stats_file: "".into(),
+                stats_file_format: StatsFileFormat::Json,
+                watch_config_file: false,
+            },
+            filters: Vec::new(),
+            latency: Duration::from_millis(300),
+            percentile: 99.0,
+            error_rate: 1.0,
+            start_rate: 10.0,
+            max_rate,
+            step_duration: Duration::from_secs(30),
+            settle_time: Duration::from_secs(5),
+            precision: 5.0,
+            max_duration: Duration::from_secs(3600),
+        }
+    }
+
+    #[test]
+    fn next_rate_ramps_then_bisects() {
+        let config = search_config(Some(50.0));
+        assert_eq!(next_rate(Some(10.0), None, &config), Some(20.0));
+        assert_eq!(next_rate(Some(40.0), None, &config), Some(50.0));
+        assert_eq!(next_rate(Some(50.0), None, &config), None);
+        assert_eq!(next_rate(Some(20.0), Some(40.0), &config), Some(30.0));
+        assert_eq!(next_rate(Some(100.0), Some(104.0), &config), None);
+        assert_eq!(next_rate(None, Some(10.0), &config), None);
+
+        let config = search_config(None);
+        assert_eq!(next_rate(Some(400.0), None, &config), Some(800.0));
+    }
+
+    #[test]
+    fn search_finds_highest_passing_rate() {
+        let config = search_config(None);
+        let breaking_point = 137.0;
+        let mut highest_passed = None;
+        let mut lowest_failed = None;
+        let mut rate = Some(config.start_rate);
+        let mut steps = 0;
+        while let Some(r) = rate {
+            steps += 1;
+            if r <= breaking_point {
+                highest_passed = Some(r);
+            } else {
+                lowest_failed = Some(r);
+            }
+            rate = next_rate(highest_passed, lowest_failed, &config);
+        }
+        let found = highest_passed.unwrap();
+        assert!(found <= breaking_point && found >= breaking_point * 0.95);
+        assert!(steps < 15, "took {} steps", steps);
+    }
+}
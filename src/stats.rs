@@ -8,7 +8,10 @@ use channel::ChannelStatsReader;
 use chrono::{DateTime, Duration as ChronoDuration, Local, NaiveDateTime, Utc};
 use ether::Either;
 use futures::{
-    channel::mpsc::{self as futures_channel, Sender as FCSender},
+    channel::{
+        mpsc::{self as futures_channel, Sender as FCSender},
+        oneshot,
+    },
     future::join_all,
     sink::SinkExt,
     stream, FutureExt, StreamExt,
@@ -186,13 +189,17 @@ impl TimeBucket {
 }
 
 // The aggregate statistics that are tracked for each bucket group in a given interval (bucket size)
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
-struct BucketGroupStats {
+pub(crate) struct BucketGroupStats {
     #[serde(skip_serializing_if = "is_zero")]
     request_timeouts: u64,
     #[serde(with = "histogram_serde", skip_serializing_if = "Histogram::is_empty")]
     rtt_histogram: Histogram<u64>,
+    // the rtt measured from when the request was meant to be sent, which corrects for
+    // coordinated omission when requests are held up
+    #[serde(with = "histogram_serde", skip_serializing_if = "Histogram::is_empty")]
+    corrected_rtt_histogram: Histogram<u64>,
     #[serde(skip_serializing_if = "BTreeMap::is_empty")]
     status_counts: BTreeMap<u16, u64>,
     #[serde(skip_serializing_if = "BTreeMap::is_empty")]
@@ -204,6 +211,7 @@ impl Default for BucketGroupStats {
         BucketGroupStats {
             request_timeouts: 0,
             rtt_histogram: Histogram::new(3).expect("could not create histogram"),
+            corrected_rtt_histogram: Histogram::new(3).expect("could not create histogram"),
             status_counts: Default::default(),
             test_errors: Default::default(),
         }
@@ -232,12 +240,48 @@ impl BucketGroupStats {
         if let Some(rtt) = stat.rtt {
             self.rtt_histogram += rtt;
         }
+        if let Some(rtt) = stat.corrected_rtt {
+            self.corrected_rtt_histogram += rtt;
+        }
+    }
+
+    // the number of requests which got a response, timed out or errored
+    pub(crate) fn request_count(&self) -> u64 {
+        self.status_counts.values().sum::<u64>()
+            + self.request_timeouts
+            + self.test_errors.values().sum::<u64>()
+    }
+
+    // the fraction of requests which timed out, errored or got a 4xx/5xx response
+    pub(crate) fn error_rate(&self) -> f64 {
+        let request_count = self.request_count();
+        if request_count == 0 {
+            return 0.0;
+        }
+        let failed_statuses: u64 = self
+            .status_counts
+            .iter()
+            .filter(|(status, _)| **status >= 400)
+            .map(|(_, count)| count)
+            .sum();
+        let errors =
+            failed_statuses + self.request_timeouts + self.test_errors.values().sum::<u64>();
+        errors as f64 / request_count as f64
+    }
+
+    // the round trip time at the given percentile (0 to 100)
+    pub(crate) fn rtt_at_percentile(&self, percentile: f64) -> Duration {
+        let micros = self.rtt_histogram.value_at_quantile(percentile / 100.0);
+        Duration::from_micros(micros)
     }
 
     // Combine two `BucketGroupStats`
-    fn combine(&mut self, rhs: &BucketGroupStats) {
+    pub(crate) fn combine(&mut self, rhs: &BucketGroupStats) {
         self.request_timeouts += rhs.request_timeouts;
         let _ = self.rtt_histogram.add(&rhs.rtt_histogram);
+        let _ = self
+            .corrected_rtt_histogram
+            .add(&rhs.corrected_rtt_histogram);
         for (status, count) in &rhs.status_counts {
             self.status_counts
                 .entry(*status)
@@ -278,12 +322,25 @@ impl BucketGroupStats {
         let max = self.rtt_histogram.max() as f64 / MICROS_TO_MS;
         let mean = self.rtt_histogram.mean().round() / MICROS_TO_MS;
         let stddev = self.rtt_histogram.stdev().round() / MICROS_TO_MS;
+        let corrected = &self.corrected_rtt_histogram;
+        let corrected_p50 = corrected.value_at_quantile(0.5) as f64 / MICROS_TO_MS;
+        let corrected_p90 = corrected.value_at_quantile(0.90) as f64 / MICROS_TO_MS;
+        let corrected_p95 = corrected.value_at_quantile(0.95) as f64 / MICROS_TO_MS;
+        let corrected_p99 = corrected.value_at_quantile(0.99) as f64 / MICROS_TO_MS;
+        let corrected_p99_9 = corrected.value_at_quantile(0.999) as f64 / MICROS_TO_MS;
+        let corrected_max = corrected.max() as f64 / MICROS_TO_MS;
         match format {
             RunOutputFormat::Human => {
                 // human format
+                let heading = match (tags.get("scenario"), tags.get("step")) {
+                    (Some(scenario), Some(step)) => {
+                        format!("- {} {} ({} step {}):", method, url, scenario, step)
+                    }
+                    _ => format!("- {} {}:", method, url),
+                };
                 let piece = format!(
                     "\n{}\n  calls made: {}\n  status counts: {:?}\n",
-                    Paint::yellow(format!("- {} {}:", method, url)).dimmed(),
+                    Paint::yellow(heading).dimmed(),
                     calls_made,
                     self.status_counts
                 );
@@ -302,6 +359,17 @@ impl BucketGroupStats {
                     p50, p90, p95, p99, p99_9, min, max, mean, stddev
                 );
                 print_string.push_str(&piece);
+                let piece = format!(
+                    "  corrected for coordinated omission:\n    \
+                     p50: {}ms, p90: {}ms, p95: {}ms, p99: {}ms, p99.9: {}ms, max: {}ms\n",
+                    corrected_p50,
+                    corrected_p90,
+                    corrected_p95,
+                    corrected_p99,
+                    corrected_p99_9,
+                    corrected_max
+                );
+                print_string.push_str(&piece);
             }
             RunOutputFormat::Json => {
                 // json format
@@ -335,6 +403,14 @@ impl BucketGroupStats {
                     "max": max,
                     "mean": mean,
                     "stddev": stddev,
+                    "corrected": {
+                        "p50": corrected_p50,
+                        "p90": corrected_p90,
+                        "p95": corrected_p95,
+                        "p99": corrected_p99,
+                        "p99_9": corrected_p99_9,
+                        "max": corrected_max,
+                    },
                     "tags": tags.iter()
                         .filter(|(k, _)| k.as_str() != "method" && k.as_str() != "url")
                         .collect::<BTreeMap<_, _>>(),
@@ -365,6 +441,9 @@ struct Stats {
     providers: Vec<ChannelStatsReader<json::Value>>,
     tags: BTreeMap<Tags, usize>,
     totals: TimeBucket,
+    // stats gathered since the last `StatsMessage::TakeWindow`. Only tracked once a window
+    // has been requested
+    window: Option<BTreeMap<usize, BucketGroupStats>>,
 }
 
 // round the current time to the nearest bucket
@@ -402,6 +481,7 @@ impl Stats {
             providers,
             tags: BTreeMap::new(),
             totals: TimeBucket::new(get_epoch()),
+            window: None,
         })
     }
 
@@ -438,6 +518,9 @@ impl Stats {
 
     // append stats to the current bucket
     async fn append(&mut self, stat: ResponseStat) {
+        if stat.warmup {
+            return;
+        }
         let mut new_tag = None;
         // check that the tags from the incoming stat exist in our tags map, if not create a new
         // entry
@@ -453,12 +536,24 @@ impl Stats {
                 i
             }
         };
+        if let Some(window) = &mut self.window {
+            window.entry(index).or_default().append(stat.clone());
+        }
         self.current.append(stat, index);
         if let Some(new_tag) = new_tag {
             self.write_file_message(FileMessage::Tags(new_tag)).await;
         }
     }
 
+    // take the stats gathered since the last time a window was taken
+    fn take_window(&mut self) -> Vec<(Tags, BucketGroupStats)> {
+        let mut window = self.window.replace(BTreeMap::new()).unwrap_or_default();
+        self.tags
+            .iter()
+            .filter_map(|(tags, index)| window.remove(index).map(|stats| (tags.clone(), stats)))
+            .collect()
+    }
+
     // Write to the stats file the given message
     // this fn returns an impl future instead of being async, so as not to capture a reference to `self`
     fn write_file_message(&self, msg: FileMessage) -> impl Future<Output = ()> {
@@ -487,12 +582,14 @@ impl Stats {
             let piece = if is_human_format {
                 format!(
                     "\n- {}:\n  length: {}\n  limit: {}\n  \
-                     number of receivers: {}\n  number of senders: {}\n",
+                     number of receivers: {}\n  number of senders: {}\n  \
+                     number of expired values: {}\n",
                     Paint::yellow(stats.provider).dimmed(),
                     stats.len,
                     stats.limit,
                     stats.receiver_count,
                     stats.sender_count,
+                    stats.expired_count,
                 )
             } else {
                 let mut s = json::to_string(&stats).expect("could not serialize provider stats");
@@ -556,7 +653,7 @@ impl Stats {
     }
 }
 
-type Tags = BTreeMap<String, String>;
+pub(crate) type Tags = BTreeMap<String, String>;
 
 // get the current time as a unix epoch
 fn get_epoch() -> u64 {
@@ -587,19 +684,26 @@ pub enum StatsMessage {
     ResponseStat(ResponseStat),
     // sent at the beginning of the test
     Start(Duration),
+    // requests the stats gathered since the previous window was taken (used for an adaptive
+    // search). The first request starts the window
+    TakeWindow(oneshot::Sender<Vec<(Tags, BucketGroupStats)>>),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ResponseStat {
     pub kind: StatKind,
     pub rtt: Option<u64>,
+    // the rtt measured from when the request was meant to be sent
+    pub corrected_rtt: Option<u64>,
     pub time: SystemTime,
     pub tags: Arc<Tags>,
+    // the request was sent during the warmup, so it is left out of the stats
+    pub warmup: bool,
 }
 
 // A `ResponseStat` is sent when a `RecoverableError` happens, or when an HTTP response is
 // received
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum StatKind {
     RecoverableError(RecoverableError),
     Response(u16), // u16 represents the HTTP response status code
@@ -886,6 +990,9 @@ pub fn create_stats_channel(
                     join_all(futures).await;
                 }
                 StreamItem::StatsMessage(StatsMessage::ResponseStat(rs)) => stats.append(rs).await,
+                StreamItem::StatsMessage(StatsMessage::TakeWindow(tx)) => {
+                    let _ = tx.send(stats.take_window());
+                }
             }
         }
     };
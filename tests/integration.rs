@@ -1,6 +1,7 @@
-use std::env;
+use std::{collections::BTreeSet, env};
 
 use futures::FutureExt;
+use serde_json as json;
 use test_common::{start_test_server, TestWriter};
 use tokio::runtime::Runtime;
 
@@ -70,3 +71,78 @@ fn int_on_demand() {
         );
     }
 }
+
+#[test]
+fn int_scenarios() {
+    let (success, _stdin, stderr) = run_test("tests/int_scenarios.yaml");
+
+    assert!(success, "test run failed. {}", stderr);
+
+    assert!(
+        !stderr.is_empty(),
+        "expected stderr to be a bunch of 'true'. Instead saw: {}",
+        stderr
+    );
+
+    for line in stderr.lines() {
+        assert_eq!(
+            line, "true",
+            "expected stderr to be a bunch of 'true'. Instead saw: {}",
+            stderr
+        );
+    }
+}
+
+#[test]
+fn int_scenario_users() {
+    let (success, _stdin, stderr) = run_test("tests/int_scenario_users.yaml");
+
+    assert!(success, "test run failed. {}", stderr);
+
+    let mut users = BTreeSet::new();
+    for line in stderr.lines() {
+        let value: json::Value = json::from_str(line)
+            .unwrap_or_else(|_| panic!("expected stderr to be json. Instead saw: {}", stderr));
+        assert_eq!(
+            value["user"], value["token"],
+            "expected each user to only see its own token. Instead saw: {}",
+            stderr
+        );
+        users.insert(value["user"].to_string());
+    }
+    assert!(
+        users.len() > 1,
+        "expected requests from several users. Instead saw: {}",
+        stderr
+    );
+}
+
+#[test]
+fn int_map_provider() {
+    let (success, _stdin, stderr) = run_test("tests/int_map_provider.yaml");
+
+    assert!(success, "test run failed. {}", stderr);
+
+    assert!(
+        !stderr.is_empty(),
+        "expected stderr to be a bunch of tokens. Instead saw: {}",
+        stderr
+    );
+
+    for line in stderr.lines() {
+        let value: json::Value = json::from_str(line)
+            .unwrap_or_else(|_| panic!("expected stderr to be json. Instead saw: {}", stderr));
+        let user = value["user"].as_str().unwrap_or_default();
+        assert!(
+            ["alice", "bob", "carol"].contains(&user),
+            "expected each line to have a user. Instead saw: {}",
+            stderr
+        );
+        assert_eq!(
+            value["token"],
+            format!("token-{}", user),
+            "expected each user to get its own token. Instead saw: {}",
+            stderr
+        );
+    }
+}